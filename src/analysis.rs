@@ -1,12 +1,205 @@
-use crate::graph::{TransactionGraph, AccountMetrics};
+use crate::balance::{BalanceMismatch, BalancePath, balance_mismatches, balance_paths};
+use crate::classify::{AccountClass, ClassThresholds, DetectorThresholds};
+use crate::csr::CsrGraph;
+use crate::graph::{TransactionGraph, TransactionRecord, AccountMetrics};
+use crate::intern::{AccountId, AccountTable};
+use crate::lists::AccountLists;
+use crate::model::{FeatureMatrix, LogisticModel};
+use crate::reciprocity::{ReciprocalPair, reciprocal_pairs};
+use crate::utilities::{map_ranges, resolve_threads};
 use std::collections::HashMap;
 // fraud detection and analysis for transaction networks.
 // provides utilities to identify suspicious accounts based on transaction patterns.
 
+// settings that control how a fraud analysis is run.
+#[derive(Debug, Clone)]
+pub struct AnalysisConfig {
+    // number of worker threads for metric computation and detection.
+    // 1 runs everything on the calling thread, 0 uses one thread per available core.
+    // results are identical for every thread count
+    pub threads: usize,
+    // the collector and money mule thresholds for each class of account
+    pub thresholds: ClassThresholds,
+    // what makes a pair of accounts that send money back and forth suspicious
+    pub reciprocity: ReciprocityThresholds,
+    // how balance columns are checked for transactions that do not add up
+    pub balances: BalanceThresholds,
+    // the most label propagation passes community_report runs before giving up on convergence
+    pub community_iterations: usize,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            threads: 1,
+            thresholds: ClassThresholds::default(),
+            reciprocity: ReciprocityThresholds::default(),
+            balances: BalanceThresholds::default(),
+            community_iterations: 20,
+        }
+    }
+}
+
+// thresholds of the reciprocal pair detector.
+// a pair is flagged when it meets all of them.
+#[derive(Debug, Clone)]
+pub struct ReciprocityThresholds {
+    // the fewest times the direction of the pair's transfers must flip
+    pub min_reversals: usize,
+    // the lowest amount symmetry (smaller volume / larger volume) between the two directions
+    pub min_symmetry: f64,
+    // the longest mean step gap between a transfer and the reply, or None for no limit
+    pub max_mean_gap: Option<f64>,
+}
+
+impl Default for ReciprocityThresholds {
+    fn default() -> Self {
+        ReciprocityThresholds {
+            min_reversals: 2,
+            min_symmetry: 0.8,
+            max_mean_gap: Some(24.0),
+        }
+    }
+}
+
+// settings of the balance consistency checks
+#[derive(Debug, Clone)]
+pub struct BalanceThresholds {
+    // the largest difference between two balances still treated as equal
+    pub tolerance: f64,
+    // whether the destination side of each transaction is checked as well as the origin, and is
+    // part of the balance histories. paysim leaves merchant balances at 0, so every payment to a
+    // merchant fails this check
+    pub check_destination: bool,
+}
+
+impl Default for BalanceThresholds {
+    fn default() -> Self {
+        BalanceThresholds {
+            tolerance: 0.01,
+            check_destination: true,
+        }
+    }
+}
+
+// the rule-based detectors an account can be flagged by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Detector {
+    // receives from many sources and keeps most of it
+    Collector,
+    // receives and quickly forwards large amounts
+    MoneyMule,
+}
+
+impl Detector {
+    // every detector, in report order
+    pub const ALL: [Detector; 2] = [Detector::Collector, Detector::MoneyMule];
+
+    // checks whether an account's metrics meet this detector's criteria.
+    // takes in `metrics` - the account's metrics, and `thresholds` - the thresholds for the account's class
+    // returns false if the thresholds disable this detector
+    pub fn matches(&self, metrics: &AccountMetrics, thresholds: &DetectorThresholds) -> bool {
+        match self {
+            Detector::Collector => metrics.is_collector(thresholds),
+            Detector::MoneyMule => metrics.is_money_mule(thresholds),
+        }
+    }
+
+    // a short human readable name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Detector::Collector => "collector",
+            Detector::MoneyMule => "money mule",
+        }
+    }
+}
+
+// an account whose flag status for one detector changed after a batch was applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagChange {
+    // the account whose status changed
+    pub account: AccountId,
+    // the detector whose verdict changed
+    pub detector: Detector,
+    // true if the account is now flagged, false if it no longer is
+    pub flagged: bool,
+    // the account's metrics after the batch
+    pub metrics: AccountMetrics,
+}
+
+// an account in the deduplicated review of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlaggedAccount {
+    // the reported account
+    pub account: AccountId,
+    // every detector the account matched (empty for a denylisted account no detector matched)
+    pub detectors: Vec<Detector>,
+    // the reason the account is denylisted, if it is
+    pub denylisted: Option<String>,
+    // true if the previous run did not report this account
+    pub is_new: bool,
+    // the account's metrics
+    pub metrics: AccountMetrics,
+}
+
+// an account a detector matched that the allowlist suppressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuppressedAccount {
+    // the suppressed account
+    pub account: AccountId,
+    // the detectors that matched it
+    pub detectors: Vec<Detector>,
+    // why the account is allowlisted
+    pub reason: String,
+}
+
+// the deduplicated outcome of a run after the allowlist and denylist are applied
+#[derive(Debug, Clone, Default)]
+pub struct Review {
+    // accounts to report, one entry per account, sorted by account handle
+    pub reported: Vec<FlaggedAccount>,
+    // flagged accounts excluded by the allowlist, sorted by account handle
+    pub suppressed: Vec<SuppressedAccount>,
+    // denylist entries that name no known account, as (name, reason) sorted by name
+    pub unmatched_denylist: Vec<(String, String)>,
+}
+
+// what the community report ranks communities by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommunityRanking {
+    // the fraction of members flagged by at least one detector
+    FlaggedDensity,
+    // the fraction of the community's internal transactions labeled as fraud
+    FraudRate,
+}
+
+// a community of accounts found by community detection, with its fraud indicators
+#[derive(Debug, Clone, PartialEq)]
+pub struct Community {
+    // the member accounts, sorted by account handle
+    pub members: Vec<AccountId>,
+    // how many members at least one detector flags
+    pub flagged: usize,
+    // flagged / members
+    pub flagged_density: f64,
+    // transactions between two members of the community
+    pub transactions: usize,
+    // how many of those transactions are labeled as fraud
+    pub fraud_transactions: usize,
+    // fraud_transactions / transactions (0 if there are none)
+    pub fraud_rate: f64,
+}
+
 pub struct FraudAnalysis {
     // analyzes transaction data to identify fraudulent account behavior.
     // uses network metrics to detect money mules and collector accounts.
-    account_metrics: HashMap<String, AccountMetrics>,
+    account_metrics: HashMap<AccountId, AccountMetrics>,
+    // the class of every account, which picks the thresholds its detectors use
+    classes: HashMap<AccountId, AccountClass>,
+    // settings the analysis was created with
+    config: AnalysisConfig,
+    // allowlist, denylist and previous-run state consulted by review
+    lists: AccountLists,
 }
 
 impl FraudAnalysis {
@@ -14,47 +207,491 @@ impl FraudAnalysis {
     // takes a reference to a transaction graph and calculates account metrics.
     // returns a new fraud analysis struct with calculated account metrics
     pub fn new(graph: &TransactionGraph) -> Self {
-        let account_metrics = graph.calculate_account_metrics();
+        Self::with_config(graph, AnalysisConfig::default())
+    }
+    
+    // creates a new fraud analysis from a transaction graph with explicit settings.
+    // takes a reference to a transaction graph and the analysis settings.
+    // returns a new fraud analysis struct with calculated account metrics
+    pub fn with_config(graph: &TransactionGraph, config: AnalysisConfig) -> Self {
+        let mut analysis = Self::from_csr(&graph.to_csr(), config);
+        analysis.set_classes(graph.classes.clone());
+        analysis
+    }
+    
+    // creates a new fraud analysis from an already frozen csr graph.
+    // lets callers drop the hashmap based graph before analysis to lower peak memory.
+    // the csr graph carries no account classes, so every account is treated as Other
+    // until set_classes is called with the classes of the graph.
+    // returns a new fraud analysis struct with calculated account metrics
+    pub fn from_csr(csr: &CsrGraph<AccountId, f64>, config: AnalysisConfig) -> Self {
+        let account_metrics = csr.account_metrics_parallel(config.threads)
+            .into_iter()
+            .enumerate()
+            .map(|(pos, metrics)| (*csr.node(pos), metrics))
+            .collect();
         FraudAnalysis {
             account_metrics,
+            classes: HashMap::new(),
+            config,
+            lists: AccountLists::new(),
         }
     }
     
+    // selects the accounts that satisfy `is_flagged`, sharding the accounts across
+    // the configured number of threads.
+    // the result is sorted by `volume` (highest first) with ties broken by account handle,
+    // so the order does not depend on hashmap iteration or on the thread count.
+    fn detect<F, V>(&self, is_flagged: F, volume: V) -> Vec<(AccountId, AccountMetrics)>
+    where
+        F: Fn(AccountId, &AccountMetrics) -> bool + Sync,
+        V: Fn(&AccountMetrics) -> f64,
+    {
+        let entries: Vec<(&AccountId, &AccountMetrics)> = self.account_metrics.iter().collect();
+        let mut flagged = map_ranges(entries.len(), resolve_threads(self.config.threads), |range| {
+            entries[range]
+                .iter()
+                .filter(|(account, metrics)| is_flagged(**account, metrics))
+                .map(|(account, metrics)| (**account, (*metrics).clone()))
+                .collect()
+        });
+        flagged.sort_by(|a, b| volume(&b.1).total_cmp(&volume(&a.1)).then(a.0.cmp(&b.0)));
+        flagged
+    }
+    
     // identifies the accounts that collect large amounts of money with minimal outflows.
-    // returns a vec of (account_id, metrics) pairs sorted by incoming volume (highest first)
-    pub fn identify_collector_accounts(&self) -> Vec<(String, AccountMetrics)> {
-        let mut collectors = Vec::new();
-        // filter accounts based on collector criteria
-        for (account, metrics) in &self.account_metrics {
-            if metrics.is_collector() {
-                collectors.push((account.clone(), metrics.clone()));
-            }
-        }
+    // returns a vec of (account handle, metrics) pairs sorted by incoming volume (highest first)
+    pub fn identify_collector_accounts(&self) -> Vec<(AccountId, AccountMetrics)> {
         // sort by incoming volume (descending) to prioritize largest volumed collectors
-        collectors.sort_by(|a, b| b.1.incoming_volume.partial_cmp(&a.1.incoming_volume).unwrap());
-        collectors
+        self.detect(|account, metrics| self.flags(account, metrics, Detector::Collector), |metrics| metrics.incoming_volume)
     }
     
     // identifies accounts that rapidly move money from many sources to other destinations.
-    // returns a vec of (account_id, metrics) pairs sorted by outgoing volume (highest first)
-    pub fn identify_money_mule_accounts(&self) -> Vec<(String, AccountMetrics)> {
-        let mut mules = Vec::new();
-        // filter accounts based on money mule criteria
-        for (account, metrics) in &self.account_metrics {
-            if metrics.is_money_mule() {
-                mules.push((account.clone(), metrics.clone()));
+    // returns a vec of (account handle, metrics) pairs sorted by outgoing volume (highest first)
+    pub fn identify_money_mule_accounts(&self) -> Vec<(AccountId, AccountMetrics)> {
+        // sort by outgoing volume (descending) to prioritize most active mules
+        self.detect(|account, metrics| self.flags(account, metrics, Detector::MoneyMule), |metrics| metrics.outgoing_volume)
+    }
+    
+    // get the calculated metrics of an account, if it appears in the graph
+    pub fn metrics(&self, account: AccountId) -> Option<&AccountMetrics> {
+        self.account_metrics.get(&account)
+    }
+
+    // get every account with its calculated metrics, in no particular order
+    pub fn accounts(&self) -> impl Iterator<Item = (AccountId, &AccountMetrics)> {
+        self.account_metrics.iter().map(|(account, metrics)| (*account, metrics))
+    }
+
+    // get the settings the analysis was created with
+    pub fn config(&self) -> &AnalysisConfig {
+        &self.config
+    }
+
+    // sets the class of every account, as stored in TransactionGraph::classes.
+    // accounts without a class are treated as Other
+    pub fn set_classes(&mut self, classes: HashMap<AccountId, AccountClass>) {
+        self.classes = classes;
+    }
+    
+    // get the class of an account, or Other if it has none
+    pub fn account_class(&self, account: AccountId) -> AccountClass {
+        self.classes.get(&account).copied().unwrap_or_default()
+    }
+    
+    // get the detector thresholds that apply to an account, chosen by its class
+    pub fn thresholds(&self, account: AccountId) -> &DetectorThresholds {
+        self.config.thresholds.for_class(self.account_class(account))
+    }
+    
+    // checks whether `detector` flags an account with the given metrics under the thresholds of its class
+    pub fn flags(&self, account: AccountId, metrics: &AccountMetrics, detector: Detector) -> bool {
+        detector.matches(metrics, self.thresholds(account))
+    }
+    
+    // sets the allowlist, denylist and previous-run state that review consults
+    pub fn set_lists(&mut self, lists: AccountLists) {
+        self.lists = lists;
+    }
+    
+    // builds the deduplicated review of this run.
+    // every account matched by at least one detector appears once with all of its detectors.
+    // allowlisted accounts are moved to the suppressed list with their reason, denylisted
+    // accounts are always reported (the denylist wins over the allowlist), and each reported
+    // account is marked new if the previous run did not report it.
+    // takes in `accounts` - the string table the lists' account names are resolved against
+    // returns the reported and suppressed accounts
+    pub fn review(&self, accounts: &AccountTable) -> Review {
+        let mut matched: HashMap<AccountId, Vec<Detector>> = HashMap::new();
+        for detector in Detector::ALL {
+            for (account, _) in self.detect(|a, m| self.flags(a, m, detector), |_| 0.0) {
+                matched.entry(account).or_default().push(detector);
             }
         }
         
-        // sort by outgoing volume (descending) to prioritize most active mules
-        mules.sort_by(|a, b| b.1.outgoing_volume.partial_cmp(&a.1.outgoing_volume).unwrap());
-        mules
+        // denylisted accounts are reported even if no detector matched them; entries that name
+        // no known account are listed separately so they are not lost
+        let mut review = Review::default();
+        for (name, reason) in &self.lists.deny {
+            match accounts.get(name) {
+                Some(account) => {
+                    matched.entry(account).or_default();
+                }
+                None => review.unmatched_denylist.push((name.clone(), reason.clone())),
+            }
+        }
+        
+        let empty = AccountMetrics::new();
+        for (account, detectors) in matched {
+            let name = accounts.name(account);
+            let denylisted = self.lists.deny_reason(name).map(str::to_string);
+            match (self.lists.allow_reason(name), &denylisted) {
+                (Some(reason), None) => review.suppressed.push(SuppressedAccount {
+                    account,
+                    detectors,
+                    reason: reason.to_string(),
+                }),
+                _ => review.reported.push(FlaggedAccount {
+                    account,
+                    detectors,
+                    denylisted,
+                    is_new: self.lists.is_new(name),
+                    // a denylisted account may have no transactions in this graph
+                    metrics: self.account_metrics.get(&account).unwrap_or(&empty).clone(),
+                }),
+            }
+        }
+        
+        review.reported.sort_by_key(|flagged| flagged.account);
+        review.suppressed.sort_by_key(|suppressed| suppressed.account);
+        review.unmatched_denylist.sort();
+        review
+    }
+    
+    // prints the deduplicated review: reported accounts with their detectors and new marker,
+    // followed by the suppressed accounts and their reasons and any unmatched denylist entries.
+    // takes in `review` - the review to print, and `accounts` - the string table for account names
+    pub fn print_review(review: &Review, accounts: &AccountTable) {
+        let new_count = review.reported.iter().filter(|flagged| flagged.is_new).count();
+        println!("\n=== {} accounts reported ({} new since the last run) ===", review.reported.len(), new_count);
+        println!("{:<15} {:<5} {:<24} {:<15} {:<15} {:<10}", 
+            "Account", "New", "Detectors", "In Volume", "Out Volume", "Denylisted");
+        
+        for flagged in &review.reported {
+            let detectors: Vec<&str> = flagged.detectors.iter().map(|d| d.name()).collect();
+            println!("{:<15} {:<5} {:<24} {:<15.2} {:<15.2} {}", 
+                accounts.name(flagged.account),
+                if flagged.is_new { "yes" } else { "" },
+                detectors.join(", "),
+                flagged.metrics.incoming_volume,
+                flagged.metrics.outgoing_volume,
+                flagged.denylisted.as_deref().unwrap_or(""));
+        }
+        
+        println!("\n=== {} flagged accounts suppressed by the allowlist ===", review.suppressed.len());
+        for suppressed in &review.suppressed {
+            println!("{:<15} {}", accounts.name(suppressed.account), suppressed.reason);
+        }
+        
+        if !review.unmatched_denylist.is_empty() {
+            println!("\n=== {} unmatched denylist entries ===", review.unmatched_denylist.len());
+            for (name, reason) in &review.unmatched_denylist {
+                println!("{:<15} {}", name, reason);
+            }
+        }
+    }
+    
+    // brings the analysis up to date after a batch was appended to the graph, or after
+    // accounts or transfers were removed from it.
+    // only the touched accounts are recomputed, and the result is identical to building a new
+    // analysis from the changed graph. touched accounts no longer in the graph are dropped,
+    // and their flags are reported as cleared.
+    // takes in `graph` - the graph after the change, and `touched` - the accounts returned by
+    // TransactionGraph::add_batch or append_transaction_dataset, or both ends of every removed transaction
+    // returns the accounts whose flag status changed, ordered by account then detector
+    pub fn apply_batch(&mut self, graph: &TransactionGraph, touched: &[AccountId]) -> Vec<FlagChange> {
+        let mut changes = Vec::new();
+        
+        for account in touched {
+            let Some(updated) = graph.account_metrics(*account) else {
+                if let Some(removed) = self.account_metrics.remove(account) {
+                    for detector in Detector::ALL.into_iter().filter(|d| self.flags(*account, &removed, *d)) {
+                        changes.push(FlagChange {
+                            account: *account,
+                            detector,
+                            flagged: false,
+                            metrics: removed.clone(),
+                        });
+                    }
+                }
+                self.classes.remove(account);
+                continue;
+            };
+            
+            let previous_class = self.account_class(*account);
+            let class = graph.account_class(*account);
+            for detector in Detector::ALL {
+                let was_flagged = self.account_metrics.get(account)
+                    .is_some_and(|m| detector.matches(m, self.config.thresholds.for_class(previous_class)));
+                let is_flagged = detector.matches(&updated, self.config.thresholds.for_class(class));
+                if was_flagged != is_flagged {
+                    changes.push(FlagChange {
+                        account: *account,
+                        detector,
+                        flagged: is_flagged,
+                        metrics: updated.clone(),
+                    });
+                }
+            }
+            
+            self.account_metrics.insert(*account, updated);
+            self.classes.insert(*account, class);
+        }
+        
+        changes.sort_by(|a, b| a.account.cmp(&b.account).then(a.detector.cmp(&b.detector)));
+        changes
+    }
+    
+    // finds the communities of the graph and ranks them by their density of flagged accounts
+    // or of fraud-labeled transactions, so fraud rings surface as groups.
+    // takes in `csr` and `transactions` - the frozen graph this analysis was built from and its
+    // transactions, `min_size` - the smallest community worth reporting, and `ranking` - what to rank by
+    // returns the communities with at least `min_size` members, highest ranked first; ties are
+    // broken by the other measure, then by size (largest first), then by smallest member
+    pub fn community_report(&self, csr: &CsrGraph<AccountId, f64>, transactions: &[TransactionRecord], min_size: usize, ranking: CommunityRanking) -> Vec<Community> {
+        let communities: Vec<Vec<AccountId>> = csr.communities(self.config.community_iterations)
+            .into_iter()
+            .filter(|members| members.len() >= min_size.max(1))
+            .map(|members| members.into_iter().map(|pos| *csr.node(pos)).collect())
+            .collect();
+        
+        let mut community_of: HashMap<AccountId, usize> = HashMap::new();
+        let mut report: Vec<Community> = Vec::with_capacity(communities.len());
+        for (i, mut members) in communities.into_iter().enumerate() {
+            members.sort_unstable();
+            let flagged = members.iter()
+                .filter(|account| self.metrics(**account).is_some_and(|m| Detector::ALL.iter().any(|d| self.flags(**account, m, *d))))
+                .count();
+            for account in &members {
+                community_of.insert(*account, i);
+            }
+            report.push(Community {
+                flagged_density: flagged as f64 / members.len() as f64,
+                members,
+                flagged,
+                transactions: 0,
+                fraud_transactions: 0,
+                fraud_rate: 0.0,
+            });
+        }
+        
+        for record in transactions {
+            if let (Some(a), Some(b)) = (community_of.get(&record.name_orig), community_of.get(&record.name_dest))
+                && a == b
+            {
+                report[*a].transactions += 1;
+                report[*a].fraud_transactions += record.is_fraud as usize;
+            }
+        }
+        for community in &mut report {
+            if community.transactions > 0 {
+                community.fraud_rate = community.fraud_transactions as f64 / community.transactions as f64;
+            }
+        }
+        
+        let key = |c: &Community| match ranking {
+            CommunityRanking::FlaggedDensity => (c.flagged_density, c.fraud_rate),
+            CommunityRanking::FraudRate => (c.fraud_rate, c.flagged_density),
+        };
+        report.sort_by(|a, b| {
+            let (a_first, a_second) = key(a);
+            let (b_first, b_second) = key(b);
+            b_first.total_cmp(&a_first)
+                .then(b_second.total_cmp(&a_second))
+                .then(b.members.len().cmp(&a.members.len()))
+                .then(a.members[0].cmp(&b.members[0]))
+        });
+        report
+    }
+    
+    // identifies the account pairs that repeatedly send money back and forth with similar
+    // amounts in both directions, using the configured reciprocity thresholds.
+    // takes in `transactions` - the transactions of the graph this analysis was built from
+    // returns the flagged pairs sorted by the volume moved between them (highest first),
+    // ties broken by account handles
+    pub fn identify_reciprocal_pairs(&self, transactions: &[TransactionRecord]) -> Vec<ReciprocalPair> {
+        let thresholds = &self.config.reciprocity;
+        let mut pairs: Vec<ReciprocalPair> = reciprocal_pairs(transactions)
+            .into_iter()
+            .filter(|pair| pair.reversals >= thresholds.min_reversals
+                && pair.amount_symmetry >= thresholds.min_symmetry
+                && thresholds.max_mean_gap.is_none_or(|gap| pair.mean_gap <= gap))
+            .collect();
+        pairs.sort_by(|a, b| b.volume().total_cmp(&a.volume()).then((a.a, a.b).cmp(&(b.a, b.b))));
+        pairs
+    }
+    
+    // scores every account of a graph with a trained model, using the metrics and account
+    // classes of this analysis for the account features.
+    // takes in `csr` and `transactions` - the frozen graph this analysis was built from and its
+    // transactions, and `model` - a model from LogisticModel::train or LogisticModel::load
+    // returns (account, fraud probability) pairs, highest first, ties broken by account handle
+    pub fn score_accounts(&self, csr: &CsrGraph<AccountId, f64>, transactions: &[TransactionRecord], model: &LogisticModel) -> Vec<(AccountId, f64)> {
+        let empty = AccountMetrics::new();
+        let features = FeatureMatrix::from_parts(
+            csr,
+            transactions,
+            |pos| self.account_metrics.get(csr.node(pos)).unwrap_or(&empty),
+            |account| self.account_class(account),
+        );
+        model.score(&features)
+    }
+    
+    // prints the highest scored accounts with their metrics.
+    // takes in `scores` - the output of score_accounts, `limit` - how many to print,
+    // and `accounts` - the string table for account names
+    pub fn print_account_scores(&self, scores: &[(AccountId, f64)], limit: usize, accounts: &AccountTable) {
+        println!("\n=== Top {} of {} accounts by model score ===", limit.min(scores.len()), scores.len());
+        println!("{:<15} {:<10} {:<12} {:<12} {:<15} {:<15}", 
+            "Account", "Score", "In Count", "Out Count", "In Volume", "Out Volume");
+        
+        for (account, score) in scores.iter().take(limit) {
+            let metrics = self.metrics(*account).cloned().unwrap_or_default();
+            println!("{:<15} {:<10.4} {:<12} {:<12} {:<15.2} {:<15.2}", 
+                accounts.name(*account),
+                score,
+                metrics.incoming_count,
+                metrics.outgoing_count,
+                metrics.incoming_volume,
+                metrics.outgoing_volume);
+        }
+    }
+    
+    // prints the top reciprocal pairs, one line per pair.
+    // takes in `pairs` - the flagged pairs, `limit` - how many to print,
+    // and `accounts` - the string table for account names
+    pub fn print_reciprocal_pairs(pairs: &[ReciprocalPair], limit: usize, accounts: &AccountTable) {
+        println!("\n=== Top {} of {} reciprocal account pairs ===", limit.min(pairs.len()), pairs.len());
+        println!("{:<15} {:<15} {:<10} {:<10} {:<15} {:<10} {:<10}", 
+            "Account A", "Account B", "A to B", "B to A", "Volume", "Symmetry", "Mean Gap");
+        
+        for pair in pairs.iter().take(limit) {
+            println!("{:<15} {:<15} {:<10} {:<10} {:<15.2} {:<10.2} {:<10.1}", 
+                accounts.name(pair.a),
+                accounts.name(pair.b),
+                pair.a_to_b,
+                pair.b_to_a,
+                pair.volume(),
+                pair.amount_symmetry,
+                pair.mean_gap);
+        }
+    }
+    
+    // identifies the transactions whose balance columns do not add up, using the configured thresholds.
+    // takes in `transactions` - the transactions of the graph this analysis was built from
+    // returns the mismatches sorted by their largest error (highest first), ties in transaction order
+    pub fn identify_balance_mismatches(&self, transactions: &[TransactionRecord]) -> Vec<BalanceMismatch> {
+        let thresholds = &self.config.balances;
+        let mut mismatches = balance_mismatches(transactions, thresholds.tolerance, thresholds.check_destination);
+        mismatches.sort_by(|a, b| b.largest_error().total_cmp(&a.largest_error()));
+        mismatches
+    }
+    
+    // identifies the accounts whose reconstructed balance history is inconsistent: a transaction
+    // opens on a different balance than the previous one closed on, or does not add up.
+    // takes in `transactions` - the transactions of the graph this analysis was built from
+    // returns the inconsistent paths, most breaks first, then most mismatches, then largest
+    // break, with ties broken by account handle
+    pub fn identify_inconsistent_balance_paths(&self, transactions: &[TransactionRecord]) -> Vec<BalancePath> {
+        let thresholds = &self.config.balances;
+        let mut paths: Vec<BalancePath> = balance_paths(transactions, thresholds.tolerance, thresholds.check_destination)
+            .into_iter()
+            .filter(BalancePath::is_inconsistent)
+            .collect();
+        paths.sort_by(|a, b| b.breaks.cmp(&a.breaks)
+            .then(b.mismatches.cmp(&a.mismatches))
+            .then(b.largest_break.total_cmp(&a.largest_break))
+            .then(a.account.cmp(&b.account)));
+        paths
+    }
+    
+    // prints the largest balance mismatches and the most inconsistent balance paths.
+    // takes in `mismatches` and `paths` - the flagged transactions and accounts, `limit` - how
+    // many of each to print, and `accounts` - the string table for account names
+    pub fn print_balance_report(mismatches: &[BalanceMismatch], paths: &[BalancePath], limit: usize, accounts: &AccountTable) {
+        println!("\n=== Top {} of {} transactions whose balances do not add up ===", limit.min(mismatches.len()), mismatches.len());
+        println!("{:<8} {:<15} {:<15} {:<15} {:<15} {:<15}", 
+            "Step", "From", "To", "Amount", "Origin Error", "Dest Error");
+        
+        for mismatch in mismatches.iter().take(limit) {
+            let record = &mismatch.transaction;
+            println!("{:<8} {:<15} {:<15} {:<15.2} {:<15.2} {:<15.2}", 
+                record.step,
+                accounts.name(record.name_orig),
+                accounts.name(record.name_dest),
+                record.amount,
+                mismatch.origin_error,
+                mismatch.destination_error);
+        }
+        
+        println!("\n=== Top {} of {} accounts with inconsistent balance paths ===", limit.min(paths.len()), paths.len());
+        println!("{:<15} {:<14} {:<10} {:<12} {:<15}", 
+            "Account", "Transactions", "Breaks", "Mismatches", "Largest Break");
+        
+        for path in paths.iter().take(limit) {
+            println!("{:<15} {:<14} {:<10} {:<12} {:<15.2}", 
+                accounts.name(path.account),
+                path.balances.len(),
+                path.breaks,
+                path.mismatches,
+                path.largest_break);
+        }
+    }
+    
+    // prints the top communities of a community report, one line per community.
+    // takes in `report` - the ranked communities, `limit` - how many to print,
+    // and `accounts` - the string table for account names
+    pub fn print_community_report(report: &[Community], limit: usize, accounts: &AccountTable) {
+        println!("\n=== Top {} of {} communities ===", limit.min(report.len()), report.len());
+        println!("{:<8} {:<10} {:<10} {:<10} {:<14} {:<10} {:<10}", 
+            "Rank", "Members", "Flagged", "Density", "Transactions", "Fraud", "Sample");
+        
+        for (rank, community) in report.iter().take(limit).enumerate() {
+            let sample: Vec<&str> = community.members.iter().take(3).map(|a| accounts.name(*a)).collect();
+            println!("{:<8} {:<10} {:<10} {:<10.2} {:<14} {:<10.2} {}", 
+                rank + 1,
+                community.members.len(),
+                community.flagged,
+                community.flagged_density,
+                community.transactions,
+                community.fraud_rate,
+                sample.join(", "));
+        }
+    }
+    
+    // prints the flag changes produced by apply_batch, one line per change.
+    // takes in `changes` - the changes to print, and `accounts` - the string table for account names
+    pub fn print_flag_changes(changes: &[FlagChange], accounts: &AccountTable) {
+        println!("\n=== {} flag changes in this batch ===", changes.len());
+        println!("{:<15} {:<12} {:<10} {:<15} {:<15} {:<10}", 
+            "Account", "Detector", "Status", "In Volume", "Out Volume", "Retention");
+        
+        for change in changes {
+            println!("{:<15} {:<12} {:<10} {:<15.2} {:<15.2} {:<10.2}", 
+                accounts.name(change.account),
+                change.detector.name(),
+                if change.flagged { "flagged" } else { "cleared" },
+                change.metrics.incoming_volume,
+                change.metrics.outgoing_volume,
+                change.metrics.retention_rate);
+        }
     }
     
     // prints a formatted table of collector accounts to the console.
     // limits output to first 500 accounts to prevent the weird terminal cutoffs
+    // takes in `accounts` - the string table used to print account names
     #[cfg(not(test))]
-    pub fn print_collector_accounts(&self) {
+    pub fn print_collector_accounts(&self, accounts: &AccountTable) {
         let collectors = self.identify_collector_accounts();
         
         println!("\n=== Total of {} accounts detected as fraudulent collector accounts ===", collectors.len());
@@ -73,7 +710,7 @@ impl FraudAnalysis {
             if i >= display_limit { break; }
             
             println!("{:<15} {:<12} {:<12} {:<15.2} {:<15.2} {:<10.2}", 
-                account, 
+                accounts.name(*account), 
                 metrics.incoming_count, 
                 metrics.outgoing_count,
                 metrics.incoming_volume,
@@ -89,8 +726,9 @@ impl FraudAnalysis {
     
     // prints a formatted table of money mule accounts to the console.
     // limits output to first 500 accounts to prevent terminal cut offs.
+    // takes in `accounts` - the string table used to print account names
     #[cfg(not(test))]
-    pub fn print_money_mule_accounts(&self) {
+    pub fn print_money_mule_accounts(&self, accounts: &AccountTable) {
         let mules = self.identify_money_mule_accounts();
         
         println!("\n=== Total of {} accounts detected as fraudulent money mule accounts ===", mules.len());
@@ -109,7 +747,7 @@ impl FraudAnalysis {
             if i >= display_limit { break; }
             
             println!("{:<15} {:<12} {:<12} {:<15.2} {:<15.2} {:<10.2}", 
-                account, 
+                accounts.name(*account), 
                 metrics.incoming_count, 
                 metrics.outgoing_count,
                 metrics.incoming_volume,
@@ -125,12 +763,12 @@ impl FraudAnalysis {
     
     // tests to prevent warnings    
     #[cfg(test)]
-    pub fn print_collector_accounts(&self) {
+    pub fn print_collector_accounts(&self, _accounts: &AccountTable) {
         let _ = self.identify_collector_accounts();
     }
     
     #[cfg(test)]
-    pub fn print_money_mule_accounts(&self) {
+    pub fn print_money_mule_accounts(&self, _accounts: &AccountTable) {
         let _ = self.identify_money_mule_accounts();
     }
 }
@@ -0,0 +1,141 @@
+// balance consistency checks on the paysim balance columns.
+// in a consistent ledger the origin of a transaction loses the amount (old - amount = new) and
+// the destination gains it (old + amount = new), and an account's balance before each
+// transaction is its balance after the previous one. paysim fraud often breaks these rules, so
+// transactions that do not add up and accounts whose balance history has gaps are worth a look.
+// transactions without balances are skipped by every check.
+use std::collections::HashMap;
+use crate::graph::{TransactionGraph, TransactionRecord};
+use crate::intern::AccountId;
+
+// a transaction whose balances do not add up
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceMismatch {
+    // the transaction
+    pub transaction: TransactionRecord,
+    // (old - amount) - new on the origin side: positive when the origin lost more than the amount
+    pub origin_error: f64,
+    // (old + amount) - new on the destination side: positive when the destination gained less than the amount
+    pub destination_error: f64,
+}
+
+impl BalanceMismatch {
+    // the larger of the two errors, ignoring sign
+    pub fn largest_error(&self) -> f64 {
+        self.origin_error.abs().max(self.destination_error.abs())
+    }
+}
+
+// the reconstructed balance history of one account
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancePath {
+    // the account
+    pub account: AccountId,
+    // (step, balance before, balance after) for each of the account's transactions with
+    // balances, in step order (ties keep the order the transactions were added in)
+    pub balances: Vec<(u32, f64, f64)>,
+    // how many transactions opened on a different balance than the previous one closed on
+    pub breaks: usize,
+    // the largest of those differences, ignoring sign (0 if there are none)
+    pub largest_break: f64,
+    // how many of the account's transactions do not add up on its side
+    pub mismatches: usize,
+}
+
+impl BalancePath {
+    // checks whether the path has a break or a transaction that does not add up
+    pub fn is_inconsistent(&self) -> bool {
+        self.breaks > 0 || self.mismatches > 0
+    }
+}
+
+// one transaction seen from one of its accounts
+struct BalanceChange {
+    step: u32,
+    before: f64,
+    after: f64,
+    // true if the change matches the amount
+    adds_up: bool,
+}
+
+impl TransactionGraph {
+    // finds the transactions whose balances do not add up (see balance_mismatches).
+    // returns the mismatches in transaction order
+    pub fn balance_mismatches(&self, tolerance: f64, check_destination: bool) -> Vec<BalanceMismatch> {
+        balance_mismatches(&self.transactions, tolerance, check_destination)
+    }
+
+    // reconstructs the balance history of every account (see balance_paths).
+    // returns one path per account with at least one observed balance, sorted by account handle
+    pub fn balance_paths(&self, tolerance: f64, check_destination: bool) -> Vec<BalancePath> {
+        balance_paths(&self.transactions, tolerance, check_destination)
+    }
+}
+
+// finds the transactions whose balances do not add up.
+// takes in `transactions` - the transactions to check, `tolerance` - the largest difference still treated as equal (for rounding),
+// and `check_destination` - whether the destination side is checked as well as the origin
+// returns the mismatches in transaction order
+pub fn balance_mismatches(transactions: &[TransactionRecord], tolerance: f64, check_destination: bool) -> Vec<BalanceMismatch> {
+    transactions.iter()
+        .filter_map(|record| {
+            let balances = record.balances?;
+            let origin_error = balances.old_orig - record.amount - balances.new_orig;
+            let destination_error = balances.old_dest + record.amount - balances.new_dest;
+            let mismatched = origin_error.abs() > tolerance
+                || (check_destination && destination_error.abs() > tolerance);
+            mismatched.then(|| BalanceMismatch {
+                transaction: record.clone(),
+                origin_error,
+                destination_error: if check_destination { destination_error } else { 0.0 },
+            })
+        })
+        .collect()
+}
+
+// reconstructs the balance history of every account from the transactions it sent and received.
+// takes in `transactions` - the transactions to replay, `tolerance` - the largest difference still treated as equal (for rounding),
+// and `check_destination` - whether received transactions are part of the history. without it only
+// the origin side is trusted, so received transactions count towards neither breaks nor mismatches
+// returns one path per account with at least one observed balance, sorted by account handle
+pub fn balance_paths(transactions: &[TransactionRecord], tolerance: f64, check_destination: bool) -> Vec<BalancePath> {
+    let mut changes: HashMap<AccountId, Vec<BalanceChange>> = HashMap::new();
+    for record in transactions {
+        let Some(balances) = record.balances else {
+            continue;
+        };
+        changes.entry(record.name_orig).or_default().push(BalanceChange {
+            step: record.step,
+            before: balances.old_orig,
+            after: balances.new_orig,
+            adds_up: (balances.old_orig - record.amount - balances.new_orig).abs() <= tolerance,
+        });
+        if check_destination {
+            changes.entry(record.name_dest).or_default().push(BalanceChange {
+                step: record.step,
+                before: balances.old_dest,
+                after: balances.new_dest,
+                adds_up: (balances.old_dest + record.amount - balances.new_dest).abs() <= tolerance,
+            });
+        }
+    }
+
+    let mut paths: Vec<BalancePath> = changes.into_iter()
+        .map(|(account, mut list)| {
+            list.sort_by_key(|change| change.step);
+            let gaps: Vec<f64> = list.windows(2)
+                .map(|pair| (pair[1].before - pair[0].after).abs())
+                .filter(|gap| *gap > tolerance)
+                .collect();
+            BalancePath {
+                account,
+                breaks: gaps.len(),
+                largest_break: gaps.into_iter().fold(0.0, f64::max),
+                mismatches: list.iter().filter(|change| !change.adds_up).count(),
+                balances: list.into_iter().map(|change| (change.step, change.before, change.after)).collect(),
+            }
+        })
+        .collect();
+    paths.sort_unstable_by_key(|path| path.account);
+    paths
+}
@@ -0,0 +1,245 @@
+// account classes and the per-class thresholds of the rule-based detectors.
+// paysim account ids start with `C` for customers and `M` for merchants. merchants receive
+// payments from many customers and rarely send money on, which is exactly what the collector
+// rule looks for, so every merchant would be a false-positive collector. accounts are
+// classified once from their id prefixes when they enter a graph, and every detector looks up
+// its thresholds by the account's class. ids that do not follow the paysim layout can be
+// classified by glob patterns instead.
+use crate::graph::AccountMetrics;
+
+// the kinds of account a transaction graph distinguishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum AccountClass {
+    // a customer account (paysim `C...`)
+    Customer,
+    // a merchant account (paysim `M...`)
+    Merchant,
+    // an id with neither a customer nor a merchant prefix
+    #[default]
+    Other,
+}
+
+impl AccountClass {
+    // every class, in report order
+    pub const ALL: [AccountClass; 3] = [AccountClass::Customer, AccountClass::Merchant, AccountClass::Other];
+
+    // a short human readable name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            AccountClass::Customer => "customer",
+            AccountClass::Merchant => "merchant",
+            AccountClass::Other => "other",
+        }
+    }
+}
+
+// classifies account ids by prefix or by pattern.
+// an id belongs to the first class with a matching prefix or pattern, customers checked first.
+// `digits_only` optionally requires the rest of the id after a prefix to be digits. a pattern
+// must match the whole id: `*` matches any run of characters, `?` any one character and `#`
+// any one digit, so `SHOP-####` matches `SHOP-0042` and `*@bank` matches `alice@bank`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountClassifier {
+    // id prefixes of customer accounts
+    pub customer_prefixes: Vec<String>,
+    // id prefixes of merchant accounts
+    pub merchant_prefixes: Vec<String>,
+    // if true, the rest of the id after the prefix must be one or more digits,
+    // so `C123` is a customer but `Collector` is not. patterns are not affected
+    pub digits_only: bool,
+    // id patterns of customer accounts
+    pub customer_patterns: Vec<String>,
+    // id patterns of merchant accounts
+    pub merchant_patterns: Vec<String>,
+}
+
+impl Default for AccountClassifier {
+    fn default() -> Self {
+        AccountClassifier {
+            customer_prefixes: vec!["C".to_string()],
+            merchant_prefixes: vec!["M".to_string()],
+            digits_only: true,
+            customer_patterns: Vec::new(),
+            merchant_patterns: Vec::new(),
+        }
+    }
+}
+
+impl AccountClassifier {
+    // classifies an account id.
+    // takes in `name` - the account id as it appears in the data
+    // returns the class of the first matching prefix or pattern, or Other
+    pub fn classify(&self, name: &str) -> AccountClass {
+        let matches = |prefixes: &[String], patterns: &[String]| {
+            prefixes.iter().any(|prefix| {
+                name.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                    !self.digits_only || (!rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit()))
+                })
+            }) || patterns.iter().any(|pattern| pattern_matches(pattern, name))
+        };
+        if matches(&self.customer_prefixes, &self.customer_patterns) {
+            AccountClass::Customer
+        } else if matches(&self.merchant_prefixes, &self.merchant_patterns) {
+            AccountClass::Merchant
+        } else {
+            AccountClass::Other
+        }
+    }
+}
+
+// checks whether a whole id matches a glob pattern (see AccountClassifier).
+// a `*` first matches nothing and is widened one character at a time whenever the rest fails
+// takes in `pattern` - the pattern, and `name` - the account id
+// returns true if the pattern matches
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `*` and the name position it currently extends to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        let single = match pattern.get(p) {
+            Some('?') => true,
+            Some('#') => name[n].is_ascii_digit(),
+            Some('*') | None => false,
+            Some(c) => *c == name[n],
+        };
+        if single {
+            p += 1;
+            n += 1;
+        } else if pattern.get(p) == Some(&'*') {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// thresholds of the collector rule: an account that receives from many sources and keeps most of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectorThresholds {
+    // the fewest incoming transfers
+    pub min_incoming: u32,
+    // incoming transfers must outnumber outgoing ones by more than this factor
+    pub in_out_ratio: u32,
+    // the retention rate must be above this
+    pub min_retention: f64,
+}
+
+impl Default for CollectorThresholds {
+    fn default() -> Self {
+        CollectorThresholds {
+            min_incoming: 6,
+            in_out_ratio: 3,
+            min_retention: 0.7,
+        }
+    }
+}
+
+impl CollectorThresholds {
+    // checks whether an account's metrics meet these thresholds
+    pub fn matches(&self, metrics: &AccountMetrics) -> bool {
+        metrics.incoming_count >= self.min_incoming &&
+        metrics.incoming_count > metrics.outgoing_count * self.in_out_ratio &&
+        metrics.retention_rate > self.min_retention
+    }
+}
+
+// thresholds of the money mule rule: an account that receives large amounts and forwards most of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MuleThresholds {
+    // the fewest incoming transfers
+    pub min_incoming: u32,
+    // the fewest outgoing transfers
+    pub min_outgoing: u32,
+    // outgoing volume must be above this fraction of incoming volume
+    pub min_forwarded: f64,
+    // the retention rate must be below this
+    pub max_retention: f64,
+    // incoming volume must be above this
+    pub min_incoming_volume: f64,
+}
+
+impl Default for MuleThresholds {
+    fn default() -> Self {
+        MuleThresholds {
+            min_incoming: 1,
+            min_outgoing: 1,
+            min_forwarded: 0.5,
+            max_retention: 0.4,
+            min_incoming_volume: 10000.0,
+        }
+    }
+}
+
+impl MuleThresholds {
+    // checks whether an account's metrics meet these thresholds
+    pub fn matches(&self, metrics: &AccountMetrics) -> bool {
+        metrics.incoming_count >= self.min_incoming &&
+        metrics.outgoing_count >= self.min_outgoing &&
+        metrics.outgoing_volume > self.min_forwarded * metrics.incoming_volume &&
+        metrics.retention_rate < self.max_retention &&
+        metrics.incoming_volume > self.min_incoming_volume
+    }
+}
+
+// the thresholds of every detector for one class of account.
+// a detector with no thresholds never flags accounts of that class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectorThresholds {
+    // thresholds of the collector detector
+    pub collector: Option<CollectorThresholds>,
+    // thresholds of the money mule detector
+    pub money_mule: Option<MuleThresholds>,
+}
+
+impl Default for DetectorThresholds {
+    fn default() -> Self {
+        DetectorThresholds {
+            collector: Some(CollectorThresholds::default()),
+            money_mule: Some(MuleThresholds::default()),
+        }
+    }
+}
+
+// detector thresholds for each account class
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassThresholds {
+    // thresholds for customer accounts
+    pub customer: DetectorThresholds,
+    // thresholds for merchant accounts; by default merchants are never collectors,
+    // since receiving many payments and keeping them is what merchants do
+    pub merchant: DetectorThresholds,
+    // thresholds for accounts that are neither
+    pub other: DetectorThresholds,
+}
+
+impl Default for ClassThresholds {
+    fn default() -> Self {
+        ClassThresholds {
+            customer: DetectorThresholds::default(),
+            merchant: DetectorThresholds {
+                collector: None,
+                ..DetectorThresholds::default()
+            },
+            other: DetectorThresholds::default(),
+        }
+    }
+}
+
+impl ClassThresholds {
+    // get the thresholds for a class of account
+    pub fn for_class(&self, class: AccountClass) -> &DetectorThresholds {
+        match class {
+            AccountClass::Customer => &self.customer,
+            AccountClass::Merchant => &self.merchant,
+            AccountClass::Other => &self.other,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// community detection on the weighted, undirected projection of a transaction graph.
+// fraud rings move money among a tight group of accounts, so they show up as dense
+// communities even when no single account breaks a per-account rule.
+//
+// communities are found with weighted label propagation: every account starts in its own
+// community and repeatedly joins the community its neighbors send and receive the most money
+// with, until no account changes. accounts are visited in a fixed order and ties are broken
+// deterministically, so the same graph always yields the same communities.
+use crate::csr::CsrGraph;
+use crate::graph::{DiGraph, NodeIndex};
+
+impl<N> CsrGraph<N, f64> {
+    // runs weighted label propagation on the undirected projection of the graph, where the
+    // weight between two accounts is the sum of the edges in both directions.
+    // ties between equally heavy labels keep the current label if it is among them, otherwise
+    // the smallest label wins. self-loops are ignored.
+    // takes in `max_iterations` - the most passes over the nodes before giving up on convergence
+    // returns the community label of every node position
+    pub fn label_propagation(&self, max_iterations: usize) -> Vec<u32> {
+        let mut labels: Vec<u32> = (0..self.node_count() as u32).collect();
+        let mut weights: Vec<(u32, f64)> = Vec::new();
+
+        for _ in 0..max_iterations {
+            let mut changed = false;
+
+            for pos in 0..self.node_count() {
+                weights.clear();
+                let out_edges = self.out_neighbors(pos).iter().zip(self.out_weights(pos));
+                let in_edges = self.in_neighbors(pos).iter().zip(self.in_weights(pos));
+                for (neighbor, weight) in out_edges.chain(in_edges) {
+                    if *neighbor as usize != pos {
+                        weights.push((labels[*neighbor as usize], *weight));
+                    }
+                }
+                if weights.is_empty() {
+                    continue;
+                }
+
+                // total weight per label, visiting labels in increasing order
+                weights.sort_unstable_by_key(|(label, _)| *label);
+                let current = labels[pos];
+                let mut best = (current, f64::NEG_INFINITY);
+                let mut i = 0;
+                while i < weights.len() {
+                    let label = weights[i].0;
+                    let mut total = 0.0;
+                    while i < weights.len() && weights[i].0 == label {
+                        total += weights[i].1;
+                        i += 1;
+                    }
+                    if total > best.1 || (total == best.1 && label == current) {
+                        best = (label, total);
+                    }
+                }
+
+                if best.0 != current {
+                    labels[pos] = best.0;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        labels
+    }
+
+    // groups the nodes into communities with weighted label propagation (see label_propagation).
+    // takes in `max_iterations` - the most passes over the nodes before giving up on convergence
+    // returns every community as its member positions in increasing order, largest community
+    // first (ties by smallest member). nodes without any edges form communities of their own
+    pub fn communities(&self, max_iterations: usize) -> Vec<Vec<usize>> {
+        let mut members: Vec<Vec<usize>> = vec![Vec::new(); self.node_count()];
+        for (pos, label) in self.label_propagation(max_iterations).into_iter().enumerate() {
+            members[label as usize].push(pos);
+        }
+
+        let mut communities: Vec<Vec<usize>> = members.into_iter().filter(|m| !m.is_empty()).collect();
+        communities.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+        communities
+    }
+}
+
+impl<N: Clone> DiGraph<N, f64> {
+    // groups the nodes into communities (see CsrGraph::communities). this freezes the graph
+    // into csr form first, so callers that already hold a csr should use it directly.
+    // takes in `max_iterations` - the most passes over the nodes before giving up on convergence
+    // returns every community as its member indices in increasing order, largest community first
+    // (ties by smallest member). accounts without any edges form communities of their own
+    pub fn communities(&self, max_iterations: usize) -> Vec<Vec<NodeIndex>> {
+        let csr = CsrGraph::from_digraph(self);
+        csr.communities(max_iterations)
+            .into_iter()
+            .map(|members| members.into_iter().map(|pos| csr.node_index(pos)).collect())
+            .collect()
+    }
+}
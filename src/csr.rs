@@ -0,0 +1,247 @@
+// frozen compressed-sparse-row (csr) representation of a directed graph.
+// the hashmap based digraph is convenient while a graph is being built, but every node
+// and edge lives in its own hash bucket. once loading is done the graph can be frozen into
+// contiguous arrays: one offset array plus one target array and one weight array per
+// direction. traversals then walk plain slices instead of hashing node indices.
+use std::collections::HashMap;
+use crate::graph::{AccountMetrics, DiGraph, NodeIndex, TransactionRecord};
+use crate::intern::{AccountId, AccountTable};
+use crate::utilities::{map_ranges, resolve_threads};
+
+// a read-only directed graph stored as compressed sparse rows.
+// nodes are addressed by their dense position (0..node_count), assigned in increasing
+// NodeIndex order. the neighbors of every node are sorted by position.
+#[derive(Debug, Clone)]
+pub struct CsrGraph<N, W> {
+    // node data by position
+    nodes: Vec<N>,
+    // the digraph index each position was built from
+    node_indices: Vec<NodeIndex>,
+    // out_targets[out_offsets[p]..out_offsets[p + 1]] are the targets of node p
+    out_offsets: Vec<usize>,
+    out_targets: Vec<u32>,
+    out_weights: Vec<W>,
+    // in_sources[in_offsets[p]..in_offsets[p + 1]] are the sources of node p
+    in_offsets: Vec<usize>,
+    in_sources: Vec<u32>,
+    in_weights: Vec<W>,
+}
+
+impl<N, W> CsrGraph<N, W>
+where
+    N: Clone,
+    W: Clone,
+{
+    // freezes a digraph into csr form.
+    // takes in `graph` as an argument- the graph to copy
+    // returns a csr graph with the same nodes, edges and weights
+    pub fn from_digraph(graph: &DiGraph<N, W>) -> Self {
+        // assign dense positions in increasing index order so the layout is deterministic
+        let mut node_indices: Vec<NodeIndex> = graph.node_indices().collect();
+        node_indices.sort_unstable_by_key(|idx| idx.0);
+
+        // node indices are handed out in order and never reused, so positions are looked up in a flat array
+        let mut position = vec![u32::MAX; graph.next_index().0];
+        for (pos, idx) in node_indices.iter().enumerate() {
+            position[idx.0] = pos as u32;
+        }
+        let lookup = |idx: NodeIndex| position.get(idx.0).copied().filter(|pos| *pos != u32::MAX);
+
+        let nodes: Vec<N> = node_indices.iter().map(|idx| graph[*idx].clone()).collect();
+
+        // flatten the edges to (source position, target position, weight)
+        let mut edges: Vec<(u32, u32, W)> = Vec::with_capacity(graph.edge_count());
+        for edge in graph.edge_references() {
+            if let (Some(s), Some(t)) = (lookup(edge.source()), lookup(edge.target())) {
+                edges.push((s, t, edge.weight().clone()));
+            }
+        }
+
+        Self::from_edges(nodes, node_indices, edges)
+    }
+
+    // builds the csr arrays from the nodes by position and the (source, target, weight) edges.
+    // every (source, target) pair must appear at most once
+    fn from_edges(nodes: Vec<N>, node_indices: Vec<NodeIndex>, mut edges: Vec<(u32, u32, W)>) -> Self {
+        let node_count = nodes.len();
+
+        // outgoing rows: group by source, neighbors sorted by target
+        edges.sort_unstable_by_key(|(s, t, _)| (*s, *t));
+        let out_offsets = Self::offsets(node_count, edges.iter().map(|(s, _, _)| *s));
+        let out_targets = edges.iter().map(|(_, t, _)| *t).collect();
+        let out_weights = edges.iter().map(|(_, _, w)| w.clone()).collect();
+
+        // incoming rows: group by target, neighbors sorted by source
+        edges.sort_unstable_by_key(|(s, t, _)| (*t, *s));
+        let in_offsets = Self::offsets(node_count, edges.iter().map(|(_, t, _)| *t));
+        let in_sources = edges.iter().map(|(s, _, _)| *s).collect();
+        let in_weights = edges.into_iter().map(|(_, _, w)| w).collect();
+
+        CsrGraph {
+            nodes,
+            node_indices,
+            out_offsets,
+            out_targets,
+            out_weights,
+            in_offsets,
+            in_sources,
+            in_weights,
+        }
+    }
+
+    // builds a row offset array from the (sorted) row of every edge
+    fn offsets(node_count: usize, rows: impl Iterator<Item = u32>) -> Vec<usize> {
+        let mut offsets = vec![0usize; node_count + 1];
+        for row in rows {
+            offsets[row as usize + 1] += 1;
+        }
+        for i in 0..node_count {
+            offsets[i + 1] += offsets[i];
+        }
+        offsets
+    }
+}
+
+impl CsrGraph<AccountId, f64> {
+    // freezes the accounts and transactions of a transaction graph into csr form, straight from
+    // the transaction records instead of the hash-map edges. a later transfer between the same
+    // two accounts replaces the weight of an earlier one, as in the digraph, so the result is
+    // the same as from_digraph on the graph the records were added to.
+    // takes in `nodes` - the node index of every account, and `records` - the transactions
+    // returns a csr graph with positions in increasing node index order
+    pub fn from_records(nodes: &HashMap<AccountId, NodeIndex>, records: &[TransactionRecord]) -> Self {
+        let mut by_index: Vec<(NodeIndex, AccountId)> = nodes.iter().map(|(account, idx)| (*idx, *account)).collect();
+        by_index.sort_unstable_by_key(|(idx, _)| idx.0);
+        Self::from_sorted_records(by_index, records)
+    }
+
+    // builds the csr form of transactions that were never added to a transaction graph, so the
+    // hash-map graph does not have to be built just to be frozen. every account in the table is
+    // a node whose index is its handle, which is the index a graph loaded by
+    // read_transaction_dataset would have given it, so the result matches to_csr on that graph.
+    // takes in `accounts` - the accounts the records refer to, and `records` - the transactions
+    // returns a csr graph with positions in increasing handle order
+    pub fn from_account_records(accounts: &AccountTable, records: &[TransactionRecord]) -> Self {
+        let by_index = accounts.iter().map(|(account, _)| (NodeIndex(account.0 as usize), account)).collect();
+        Self::from_sorted_records(by_index, records)
+    }
+
+    // builds the csr arrays from the (node index, account) of every node, sorted by index, and the transactions
+    fn from_sorted_records(by_index: Vec<(NodeIndex, AccountId)>, records: &[TransactionRecord]) -> Self {
+        // account handles are dense, so positions are looked up in a flat array instead of a map
+        let handles = by_index.iter().map(|(_, account)| account.0 as usize + 1).max().unwrap_or(0);
+        let mut position = vec![u32::MAX; handles];
+        for (pos, (_, account)) in by_index.iter().enumerate() {
+            position[account.0 as usize] = pos as u32;
+        }
+        let lookup = |account: AccountId| position.get(account.0 as usize).copied().filter(|pos| *pos != u32::MAX);
+
+        let mut edges: Vec<(u32, u32, f64)> = records.iter()
+            .filter_map(|record| Some((lookup(record.name_orig)?, lookup(record.name_dest)?, record.amount)))
+            .collect();
+        drop(position);
+
+        // the sort is stable, so the last transfer of every pair ends up last among its equals
+        edges.sort_by_key(|(s, t, _)| (*s, *t));
+        let mut kept = 0;
+        for i in 0..edges.len() {
+            if kept > 0 && (edges[kept - 1].0, edges[kept - 1].1) == (edges[i].0, edges[i].1) {
+                edges[kept - 1] = edges[i];
+            } else {
+                edges[kept] = edges[i];
+                kept += 1;
+            }
+        }
+        edges.truncate(kept);
+        edges.shrink_to_fit();
+
+        let (node_indices, nodes) = by_index.into_iter().unzip();
+        Self::from_edges(nodes, node_indices, edges)
+    }
+}
+
+impl<N, W> CsrGraph<N, W> {
+    // get the number of nodes in the graph
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // get the number of edges in the graph
+    pub fn edge_count(&self) -> usize {
+        self.out_targets.len()
+    }
+
+    // get the node data stored at a position
+    pub fn node(&self, pos: usize) -> &N {
+        &self.nodes[pos]
+    }
+
+    // get the digraph index a position was built from
+    pub fn node_index(&self, pos: usize) -> NodeIndex {
+        self.node_indices[pos]
+    }
+
+    // get the number of outgoing edges of a node
+    pub fn out_degree(&self, pos: usize) -> usize {
+        self.out_offsets[pos + 1] - self.out_offsets[pos]
+    }
+
+    // get the number of incoming edges of a node
+    pub fn in_degree(&self, pos: usize) -> usize {
+        self.in_offsets[pos + 1] - self.in_offsets[pos]
+    }
+
+    // get the targets of a node's outgoing edges, sorted by position
+    pub fn out_neighbors(&self, pos: usize) -> &[u32] {
+        &self.out_targets[self.out_offsets[pos]..self.out_offsets[pos + 1]]
+    }
+
+    // get the weights of a node's outgoing edges, in the same order as out_neighbors
+    pub fn out_weights(&self, pos: usize) -> &[W] {
+        &self.out_weights[self.out_offsets[pos]..self.out_offsets[pos + 1]]
+    }
+
+    // get the sources of a node's incoming edges, sorted by position
+    pub fn in_neighbors(&self, pos: usize) -> &[u32] {
+        &self.in_sources[self.in_offsets[pos]..self.in_offsets[pos + 1]]
+    }
+
+    // get the weights of a node's incoming edges, in the same order as in_neighbors
+    pub fn in_weights(&self, pos: usize) -> &[W] {
+        &self.in_weights[self.in_offsets[pos]..self.in_offsets[pos + 1]]
+    }
+}
+
+impl<N> CsrGraph<N, f64> {
+    // calculates the account metrics of the node at a position.
+    // volumes are summed in neighbor order, so the result is the same on every run
+    pub fn metrics_at(&self, pos: usize) -> AccountMetrics {
+        let mut metrics = AccountMetrics::new();
+        metrics.outgoing_count = self.out_degree(pos) as u32;
+        metrics.outgoing_volume = self.out_weights(pos).iter().sum();
+        metrics.incoming_count = self.in_degree(pos) as u32;
+        metrics.incoming_volume = self.in_weights(pos).iter().sum();
+        metrics.calculate_retention_rate();
+        metrics
+    }
+
+    // calculates the account metrics of every node.
+    // returns a vec indexed by node position
+    pub fn account_metrics(&self) -> Vec<AccountMetrics> {
+        (0..self.node_count()).map(|pos| self.metrics_at(pos)).collect()
+    }
+}
+
+impl<N: Sync> CsrGraph<N, f64> {
+    // calculates the account metrics of every node using several threads.
+    // the edge arrays are sharded by contiguous node ranges, so every edge of an account lands
+    // in the same shard and is summed in the same order as the sequential path. the shards'
+    // partial results are merged by concatenation and match account_metrics exactly.
+    // takes in `threads` - the number of worker threads (0 for one per core)
+    // returns a vec indexed by node position
+    pub fn account_metrics_parallel(&self, threads: usize) -> Vec<AccountMetrics> {
+        map_ranges(self.node_count(), resolve_threads(threads), |range| {
+            range.map(|pos| self.metrics_at(pos)).collect()
+        })
+    }
+}
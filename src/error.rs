@@ -0,0 +1,100 @@
+// error types for the financial fraud detection system.
+// every fallible operation in the crate returns a fraud error so callers can match on the failure kind.
+use std::fmt;
+use std::io;
+use crate::graph::NodeIndex;
+
+// the different ways loading, analysis and export can fail.
+#[derive(Debug)]
+pub enum FraudError {
+    // a file could not be opened, read or written
+    Io(io::Error),
+    // the csv reader could not read a record
+    Csv(csv::Error),
+    // a field could not be converted to its expected type.
+    // `row` is the line number in the source file (1 is the header)
+    Parse {
+        column: String,
+        row: u64,
+        message: String,
+    },
+    // the input does not have the expected columns or layout
+    Schema(String),
+    // an option or setting passed to the program is invalid
+    Config(String),
+    // a saved graph snapshot is corrupt, from another version, or older than its source
+    Snapshot(String),
+    // a saved model is malformed or was trained on different features
+    Model(String),
+    // a graph operation referred to something that does not exist, or a graph is inconsistent
+    Graph(GraphError),
+}
+
+// the ways a checked graph operation or a graph consistency check can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    // no node has this index
+    MissingNode(NodeIndex),
+    // there is no edge from the first node to the second
+    MissingEdge(NodeIndex, NodeIndex),
+    // the graph's internal structures disagree; the message describes the first disagreement found
+    Inconsistent(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::MissingNode(idx) => write!(f, "no node with index {}", idx.0),
+            GraphError::MissingEdge(source, target) => write!(f, "no edge from node {} to node {}", source.0, target.0),
+            GraphError::Inconsistent(msg) => write!(f, "inconsistent graph: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl fmt::Display for FraudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FraudError::Io(e) => write!(f, "I/O error: {}", e),
+            FraudError::Csv(e) => write!(f, "CSV error: {}", e),
+            FraudError::Parse { column, row, message } => {
+                write!(f, "Failed to parse {} on row {}: {}", column, row, message)
+            }
+            FraudError::Schema(msg) => write!(f, "Schema error: {}", msg),
+            FraudError::Config(msg) => write!(f, "Configuration error: {}", msg),
+            FraudError::Snapshot(msg) => write!(f, "Snapshot error: {}", msg),
+            FraudError::Model(msg) => write!(f, "Model error: {}", msg),
+            FraudError::Graph(e) => write!(f, "Graph error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FraudError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FraudError::Io(e) => Some(e),
+            FraudError::Csv(e) => Some(e),
+            FraudError::Graph(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FraudError {
+    fn from(err: io::Error) -> Self {
+        FraudError::Io(err)
+    }
+}
+
+impl From<csv::Error> for FraudError {
+    fn from(err: csv::Error) -> Self {
+        FraudError::Csv(err)
+    }
+}
+
+impl From<GraphError> for FraudError {
+    fn from(err: GraphError) -> Self {
+        FraudError::Graph(err)
+    }
+}
@@ -0,0 +1,292 @@
+// graph export for visualization in external tools.
+// writes the whole transaction graph, or a subset of its nodes such as the k-hop neighborhood
+// of a flagged account, as graphml (yEd, Cytoscape, Gephi), graphviz dot, or a pair of
+// gephi-compatible csv node and edge lists.
+//
+// every node carries the account name, its AccountMetrics and one boolean per detector.
+// graphml and dot write one edge per account pair carrying the total amount sent, the number
+// of transfers, the first and last step, the transaction types used and whether any transfer
+// is labeled fraud. the node metrics are computed from the graph's edge weights, which hold only
+// the latest transfer between each pair, so every edge also carries that amount as `weight`:
+// a node's volumes add up its edges' `weight`, not their `amount`. the gephi edge list writes
+// one row per transaction instead, with its own amount, step, type and label.
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Write;
+use crate::analysis::{Detector, FraudAnalysis};
+use crate::error::FraudError;
+use crate::graph::{AccountMetrics, NodeIndex, TransactionGraph, TransactionRecord};
+
+// an exporter over a transaction graph and the analysis of that graph.
+// nodes are written in node index order and edges in (source, target) order,
+// so the same graph always produces the same file.
+pub struct GraphExport<'a> {
+    // the graph being exported
+    graph: &'a TransactionGraph,
+    // the analysis providing metrics and detector flags
+    analysis: &'a FraudAnalysis,
+    // the nodes to write, sorted by index
+    nodes: Vec<NodeIndex>,
+}
+
+// one exported edge, summarizing every transfer between the two accounts
+struct ExportEdge {
+    source: NodeIndex,
+    target: NodeIndex,
+    // total amount sent
+    amount: f64,
+    // the amount of the latest added transfer, the edge weight the node metrics use
+    weight: f64,
+    // number of transfers
+    transfers: usize,
+    // the earliest and latest step of the transfers
+    first_step: u32,
+    last_step: u32,
+    // the transaction types of the transfers, sorted and separated by `|`
+    types: String,
+    // true if any transfer is labeled fraud
+    is_fraud: bool,
+}
+
+impl<'a> GraphExport<'a> {
+    // creates an exporter for the whole graph.
+    // takes in `graph` - the graph to export, and `analysis` - an analysis of that graph
+    pub fn new(graph: &'a TransactionGraph, analysis: &'a FraudAnalysis) -> Self {
+        let mut nodes: Vec<NodeIndex> = graph.graph.node_indices().collect();
+        nodes.sort_unstable_by_key(|idx| idx.0);
+        GraphExport { graph, analysis, nodes }
+    }
+
+    // restricts the export to the subgraph induced by `nodes`: only those nodes and the edges
+    // between them are written. nodes that are not in the graph are ignored.
+    pub fn with_nodes(mut self, nodes: &HashSet<NodeIndex>) -> Self {
+        self.nodes.retain(|idx| nodes.contains(idx));
+        self
+    }
+
+    // get the number of nodes that will be written
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // the account name of a node
+    fn name(&self, idx: NodeIndex) -> &str {
+        self.graph.account_name(self.graph.graph[idx])
+    }
+
+    // the metrics of a node, or empty metrics for an account the analysis has not seen
+    fn metrics(&self, idx: NodeIndex) -> AccountMetrics {
+        self.analysis.metrics(self.graph.graph[idx]).cloned().unwrap_or_default()
+    }
+
+    // whether a detector flags a node, under the thresholds of its account's class
+    fn flags(&self, idx: NodeIndex, metrics: &AccountMetrics, detector: Detector) -> bool {
+        self.analysis.flags(self.graph.graph[idx], metrics, detector)
+    }
+
+    // the transactions between exported nodes in the order they were added, with their end nodes
+    fn transactions(&self) -> impl Iterator<Item = (NodeIndex, NodeIndex, &'a TransactionRecord)> + '_ {
+        let included: HashSet<NodeIndex> = self.nodes.iter().copied().collect();
+        let graph = self.graph;
+        graph.transactions.iter().filter_map(move |record| {
+            let source = *graph.node_map.get(&record.name_orig)?;
+            let target = *graph.node_map.get(&record.name_dest)?;
+            (included.contains(&source) && included.contains(&target)).then_some((source, target, record))
+        })
+    }
+
+    // the edges between exported nodes with their transfers summed up, sorted by (source, target)
+    fn edges(&self) -> Vec<ExportEdge> {
+        let mut edges: BTreeMap<(usize, usize), (ExportEdge, BTreeSet<&str>)> = BTreeMap::new();
+        for (source, target, record) in self.transactions() {
+            let (edge, types) = edges.entry((source.0, target.0)).or_insert((ExportEdge {
+                source,
+                target,
+                amount: 0.0,
+                weight: 0.0,
+                transfers: 0,
+                first_step: record.step,
+                last_step: record.step,
+                types: String::new(),
+                is_fraud: false,
+            }, BTreeSet::new()));
+            edge.amount += record.amount;
+            edge.weight = record.amount;
+            edge.transfers += 1;
+            edge.first_step = edge.first_step.min(record.step);
+            edge.last_step = edge.last_step.max(record.step);
+            edge.is_fraud |= record.is_fraud != 0;
+            types.insert(&record.r#type);
+        }
+        edges.into_values()
+            .map(|(edge, types)| ExportEdge { types: types.into_iter().collect::<Vec<_>>().join("|"), ..edge })
+            .collect()
+    }
+
+    // writes the graph as graphml.
+    // takes in `out` - where to write the document
+    // returns an i/o error if writing fails
+    pub fn write_graphml<W: Write>(&self, mut out: W) -> Result<(), FraudError> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        writeln!(out, r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <key id="incoming_count" for="node" attr.name="incoming_count" attr.type="int"/>"#)?;
+        writeln!(out, r#"  <key id="outgoing_count" for="node" attr.name="outgoing_count" attr.type="int"/>"#)?;
+        writeln!(out, r#"  <key id="incoming_volume" for="node" attr.name="incoming_volume" attr.type="double"/>"#)?;
+        writeln!(out, r#"  <key id="outgoing_volume" for="node" attr.name="outgoing_volume" attr.type="double"/>"#)?;
+        writeln!(out, r#"  <key id="retention_rate" for="node" attr.name="retention_rate" attr.type="double"/>"#)?;
+        for detector in Detector::ALL {
+            writeln!(out, r#"  <key id="{0}" for="node" attr.name="{0}" attr.type="boolean"/>"#, attribute_name(detector))?;
+        }
+        writeln!(out, r#"  <key id="amount" for="edge" attr.name="amount" attr.type="double"/>"#)?;
+        writeln!(out, r#"  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>"#)?;
+        writeln!(out, r#"  <key id="transfers" for="edge" attr.name="transfers" attr.type="int"/>"#)?;
+        writeln!(out, r#"  <key id="first_step" for="edge" attr.name="first_step" attr.type="int"/>"#)?;
+        writeln!(out, r#"  <key id="last_step" for="edge" attr.name="last_step" attr.type="int"/>"#)?;
+        writeln!(out, r#"  <key id="types" for="edge" attr.name="types" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <key id="is_fraud" for="edge" attr.name="is_fraud" attr.type="boolean"/>"#)?;
+        writeln!(out, r#"  <graph id="transactions" edgedefault="directed">"#)?;
+
+        for idx in &self.nodes {
+            let metrics = self.metrics(*idx);
+            writeln!(out, r#"    <node id="n{}">"#, idx.0)?;
+            writeln!(out, r#"      <data key="name">{}</data>"#, escape_xml(self.name(*idx)))?;
+            writeln!(out, r#"      <data key="incoming_count">{}</data>"#, metrics.incoming_count)?;
+            writeln!(out, r#"      <data key="outgoing_count">{}</data>"#, metrics.outgoing_count)?;
+            writeln!(out, r#"      <data key="incoming_volume">{}</data>"#, metrics.incoming_volume)?;
+            writeln!(out, r#"      <data key="outgoing_volume">{}</data>"#, metrics.outgoing_volume)?;
+            writeln!(out, r#"      <data key="retention_rate">{}</data>"#, metrics.retention_rate)?;
+            for detector in Detector::ALL {
+                writeln!(out, r#"      <data key="{}">{}</data>"#, attribute_name(detector), self.flags(*idx, &metrics, detector))?;
+            }
+            writeln!(out, "    </node>")?;
+        }
+
+        for edge in self.edges() {
+            writeln!(out, r#"    <edge source="n{}" target="n{}">"#, edge.source.0, edge.target.0)?;
+            writeln!(out, r#"      <data key="amount">{}</data>"#, edge.amount)?;
+            writeln!(out, r#"      <data key="weight">{}</data>"#, edge.weight)?;
+            writeln!(out, r#"      <data key="transfers">{}</data>"#, edge.transfers)?;
+            writeln!(out, r#"      <data key="first_step">{}</data>"#, edge.first_step)?;
+            writeln!(out, r#"      <data key="last_step">{}</data>"#, edge.last_step)?;
+            writeln!(out, r#"      <data key="types">{}</data>"#, escape_xml(&edge.types))?;
+            writeln!(out, r#"      <data key="is_fraud">{}</data>"#, edge.is_fraud)?;
+            writeln!(out, "    </edge>")?;
+        }
+
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    // writes the graph as a graphviz dot digraph.
+    // flagged accounts are filled red so they stand out when rendered.
+    // takes in `out` - where to write the document
+    // returns an i/o error if writing fails
+    pub fn write_dot<W: Write>(&self, mut out: W) -> Result<(), FraudError> {
+        writeln!(out, "digraph transactions {{")?;
+
+        for idx in &self.nodes {
+            let metrics = self.metrics(*idx);
+            let mut attributes = format!(
+                "label={} incoming_count={} outgoing_count={} incoming_volume={} outgoing_volume={} retention_rate={}",
+                quote_dot(self.name(*idx)),
+                metrics.incoming_count,
+                metrics.outgoing_count,
+                metrics.incoming_volume,
+                metrics.outgoing_volume,
+                metrics.retention_rate);
+            let mut flagged = false;
+            for detector in Detector::ALL {
+                let matches = self.flags(*idx, &metrics, detector);
+                flagged |= matches;
+                attributes.push_str(&format!(" {}={}", attribute_name(detector), matches));
+            }
+            if flagged {
+                attributes.push_str(" style=filled fillcolor=red");
+            }
+            writeln!(out, "  n{} [{}];", idx.0, attributes)?;
+        }
+
+        for edge in self.edges() {
+            writeln!(out, "  n{} -> n{} [amount={} weight={} transfers={} first_step={} last_step={} types={} is_fraud={}];",
+                edge.source.0, edge.target.0, edge.amount, edge.weight, edge.transfers, edge.first_step, edge.last_step,
+                quote_dot(&edge.types), edge.is_fraud)?;
+        }
+
+        writeln!(out, "}}")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    // writes the graph as gephi's spreadsheet import format: a node table keyed by `Id`
+    // and an edge table with `Source`, `Target`, `Type` and `Weight` columns holding one row
+    // per transaction, in the order the transactions were added.
+    // takes in `nodes_out` - where to write the node table, and `edges_out` - where to write the edge table
+    // returns an i/o error if writing fails
+    pub fn write_csv<N: Write, E: Write>(&self, mut nodes_out: N, mut edges_out: E) -> Result<(), FraudError> {
+        write!(nodes_out, "Id,Label,incoming_count,outgoing_count,incoming_volume,outgoing_volume,retention_rate")?;
+        for detector in Detector::ALL {
+            write!(nodes_out, ",{}", attribute_name(detector))?;
+        }
+        writeln!(nodes_out)?;
+
+        for idx in &self.nodes {
+            let metrics = self.metrics(*idx);
+            write!(nodes_out, "n{},{},{},{},{},{},{}",
+                idx.0,
+                quote_csv(self.name(*idx)),
+                metrics.incoming_count,
+                metrics.outgoing_count,
+                metrics.incoming_volume,
+                metrics.outgoing_volume,
+                metrics.retention_rate)?;
+            for detector in Detector::ALL {
+                write!(nodes_out, ",{}", self.flags(*idx, &metrics, detector))?;
+            }
+            writeln!(nodes_out)?;
+        }
+
+        // `Type` is gephi's edge direction column; the transaction type goes in `transaction_type`
+        writeln!(edges_out, "Source,Target,Type,Weight,step,transaction_type,is_fraud")?;
+        for (source, target, record) in self.transactions() {
+            writeln!(edges_out, "n{},n{},Directed,{},{},{},{}",
+                source.0, target.0, record.amount, record.step, quote_csv(&record.r#type), record.is_fraud)?;
+        }
+
+        nodes_out.flush()?;
+        edges_out.flush()?;
+        Ok(())
+    }
+}
+
+// the attribute name a detector's flag is exported under
+fn attribute_name(detector: Detector) -> &'static str {
+    match detector {
+        Detector::Collector => "is_collector",
+        Detector::MoneyMule => "is_money_mule",
+    }
+}
+
+// escapes text for use inside an xml element
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// quotes text as a dot string literal
+fn quote_dot(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// quotes a csv field if it contains a separator, quote or line break
+fn quote_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,188 @@
+// maximum flow and minimum cut between groups of accounts.
+// treats every edge as a capacity and measures how much money could have moved from a
+// set of source accounts to a set of sink accounts (for example known collectors) through the
+// network. the minimum cut is the cheapest set of transfers separating the two groups; the
+// accounts on those transfers are the chokepoints, typically the mules the money ran through.
+// on a transaction graph the capacity of an edge is the total of every transfer between the two
+// accounts, since the edge weight only holds the latest one.
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::graph::{DiGraph, NodeIndex, TransactionGraph};
+use crate::intern::AccountId;
+
+// flows smaller than this are treated as zero, so rounding never creates endless augmenting paths
+const EPSILON: f64 = 1e-9;
+
+// the result of a max-flow computation
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowResult<T> {
+    // the total flow from the sources to the sinks
+    pub value: f64,
+    // the edges of a minimum cut as (source, target, capacity), sorted; their capacities add up to `value`
+    pub cut_edges: Vec<(T, T, f64)>,
+    // the accounts at either end of a cut edge that are neither a source nor a sink, sorted
+    pub chokepoints: Vec<T>,
+}
+
+// one arc of the residual network; arcs are stored in pairs so `arc ^ 1` is the reverse arc
+struct Arc {
+    to: usize,
+    capacity: f64,
+}
+
+impl<N: Clone> DiGraph<N, f64> {
+    // computes the maximum flow from a set of sources to a set of sinks, using edge weights as
+    // capacities; see max_flow_by.
+    // takes in `sources` and `sinks` - the two groups (nodes not in the graph are ignored)
+    // returns the flow value, a minimum cut and its chokepoint accounts
+    pub fn max_flow(&self, sources: &[NodeIndex], sinks: &[NodeIndex]) -> FlowResult<NodeIndex> {
+        self.max_flow_by(sources, sinks, |_, _, weight| *weight)
+    }
+}
+
+impl<N: Clone, W: Clone> DiGraph<N, W> {
+    // computes the maximum flow from a set of sources to a set of sinks with edmonds-karp
+    // (shortest augmenting paths). negative capacities count as 0.
+    // nodes that are both a source and a sink are treated as sources only.
+    // takes in `sources` and `sinks` - the two groups (nodes not in the graph are ignored), and
+    // `capacity` - the capacity of the edge from the first node to the second with the given weight
+    // returns the flow value, a minimum cut and its chokepoint accounts
+    pub fn max_flow_by<C>(&self, sources: &[NodeIndex], sinks: &[NodeIndex], capacity: C) -> FlowResult<NodeIndex>
+    where
+        C: Fn(NodeIndex, NodeIndex, &W) -> f64,
+    {
+        let mut nodes: Vec<NodeIndex> = self.node_indices().collect();
+        nodes.sort_unstable_by_key(|idx| idx.0);
+        let position: HashMap<NodeIndex, usize> = nodes.iter().enumerate().map(|(pos, idx)| (*idx, pos)).collect();
+
+        // node positions, plus a super source and a super sink tied to the two groups
+        let super_source = nodes.len();
+        let super_sink = nodes.len() + 1;
+        let mut arcs: Vec<Arc> = Vec::new();
+        let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); nodes.len() + 2];
+        let mut add_arc = |from: usize, to: usize, capacity: f64| {
+            adjacency[from].push(arcs.len());
+            arcs.push(Arc { to, capacity });
+            adjacency[to].push(arcs.len());
+            arcs.push(Arc { to: from, capacity: 0.0 });
+        };
+
+        let mut edges: Vec<(NodeIndex, NodeIndex, f64)> = self.edge_references()
+            .map(|edge| (edge.source(), edge.target(), capacity(edge.source(), edge.target(), edge.weight()).max(0.0)))
+            .collect();
+        edges.sort_unstable_by_key(|(s, t, _)| (s.0, t.0));
+        for (source, target, capacity) in &edges {
+            add_arc(position[source], position[target], *capacity);
+        }
+
+        let source_set: HashSet<usize> = sources.iter().filter_map(|s| position.get(s).copied()).collect();
+        let sink_set: HashSet<usize> = sinks.iter()
+            .filter_map(|s| position.get(s).copied())
+            .filter(|pos| !source_set.contains(pos))
+            .collect();
+        let mut group: Vec<usize> = source_set.iter().copied().collect();
+        group.sort_unstable();
+        for pos in group {
+            add_arc(super_source, pos, f64::INFINITY);
+        }
+        let mut group: Vec<usize> = sink_set.iter().copied().collect();
+        group.sort_unstable();
+        for pos in group {
+            add_arc(pos, super_sink, f64::INFINITY);
+        }
+
+        // augment along shortest residual paths until the sink is unreachable
+        let mut value = 0.0;
+        loop {
+            let mut via: Vec<Option<usize>> = vec![None; adjacency.len()];
+            let mut queue = VecDeque::from([super_source]);
+            while let Some(node) = queue.pop_front() {
+                if node == super_sink {
+                    break;
+                }
+                for &arc in &adjacency[node] {
+                    let to = arcs[arc].to;
+                    if to != super_source && via[to].is_none() && arcs[arc].capacity > EPSILON {
+                        via[to] = Some(arc);
+                        queue.push_back(to);
+                    }
+                }
+            }
+            if via[super_sink].is_none() {
+                break;
+            }
+
+            let mut bottleneck = f64::INFINITY;
+            let mut node = super_sink;
+            while let Some(arc) = via[node] {
+                bottleneck = bottleneck.min(arcs[arc].capacity);
+                node = arcs[arc ^ 1].to;
+            }
+            // only infinite arcs: a source is also a sink, which was excluded above
+            if bottleneck.is_infinite() {
+                break;
+            }
+            let mut node = super_sink;
+            while let Some(arc) = via[node] {
+                arcs[arc].capacity -= bottleneck;
+                arcs[arc ^ 1].capacity += bottleneck;
+                node = arcs[arc ^ 1].to;
+            }
+            value += bottleneck;
+        }
+
+        // the minimum cut separates what the super source can still reach from the rest
+        let mut reachable = vec![false; adjacency.len()];
+        reachable[super_source] = true;
+        let mut queue = VecDeque::from([super_source]);
+        while let Some(node) = queue.pop_front() {
+            for &arc in &adjacency[node] {
+                let to = arcs[arc].to;
+                if !reachable[to] && arcs[arc].capacity > EPSILON {
+                    reachable[to] = true;
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        let cut_edges: Vec<(NodeIndex, NodeIndex, f64)> = edges.into_iter()
+            .filter(|(s, t, capacity)| *capacity > 0.0 && reachable[position[s]] && !reachable[position[t]])
+            .collect();
+        let mut chokepoints: Vec<NodeIndex> = cut_edges.iter()
+            .flat_map(|(s, t, _)| [*s, *t])
+            .filter(|idx| !source_set.contains(&position[idx]) && !sink_set.contains(&position[idx]))
+            .collect();
+        chokepoints.sort_unstable_by_key(|idx| idx.0);
+        chokepoints.dedup();
+
+        FlowResult { value, cut_edges, chokepoints }
+    }
+}
+
+impl TransactionGraph {
+    // computes how much money could have moved from one group of accounts to another.
+    // the capacity between two accounts is the sum of every transfer between them.
+    // takes in `sources` and `sinks` - the two groups (accounts not in the graph are ignored)
+    // returns the flow value, the transfers of a minimum cut and its chokepoint accounts
+    pub fn max_flow(&self, sources: &[AccountId], sinks: &[AccountId]) -> FlowResult<AccountId> {
+        let to_nodes = |accounts: &[AccountId]| -> Vec<NodeIndex> {
+            accounts.iter().filter_map(|account| self.node_map.get(account).copied()).collect()
+        };
+        let mut totals: HashMap<(NodeIndex, NodeIndex), f64> = HashMap::new();
+        for record in &self.transactions {
+            if let (Some(orig), Some(dest)) = (self.node_map.get(&record.name_orig), self.node_map.get(&record.name_dest)) {
+                *totals.entry((*orig, *dest)).or_default() += record.amount;
+            }
+        }
+        let flow = self.graph.max_flow_by(&to_nodes(sources), &to_nodes(sinks), |source, target, _| {
+            totals.get(&(source, target)).copied().unwrap_or(0.0)
+        });
+
+        let mut chokepoints: Vec<AccountId> = flow.chokepoints.iter().map(|idx| self.graph[*idx]).collect();
+        chokepoints.sort_unstable();
+        let mut cut_edges: Vec<(AccountId, AccountId, f64)> = flow.cut_edges.into_iter()
+            .map(|(s, t, capacity)| (self.graph[s], self.graph[t], capacity))
+            .collect();
+        cut_edges.sort_unstable_by_key(|(s, t, _)| (*s, *t));
+        FlowResult { value: flow.value, cut_edges, chokepoints }
+    }
+}
@@ -2,6 +2,10 @@
 // implements a directed graph model for tracking money flows between accounts.
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use crate::classify::{AccountClass, AccountClassifier, DetectorThresholds};
+use crate::csr::CsrGraph;
+use crate::error::GraphError;
+use crate::intern::{AccountId, AccountTable};
 
 // a unique identifier for a node in a graph
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -50,7 +54,10 @@ impl<'a, N, W> EdgeRef<'a, N, W> {
     }
 }
 
-// a directed graph with weighted edges
+// a directed graph with weighted edges.
+// node indices are stable: an index keeps referring to the same node for the node's whole life,
+// and the index of a removed node is never handed out again, so indices held elsewhere (node
+// maps, snapshots, exported files) can never silently point at a different node.
 #[derive(Debug, Clone)]
 pub struct DiGraph<N, W> {
     // the next available node index
@@ -93,7 +100,29 @@ where
         idx
     }
     
-    // add an edge to the graph with the given weight
+    // add a node under a specific index, used when restoring a saved graph.
+    // later calls to add_node continue after the highest index seen so far
+    pub(crate) fn insert_node_at(&mut self, idx: NodeIndex, node: N) {
+        self.next_node_id = self.next_node_id.max(idx.0 + 1);
+        
+        self.nodes.insert(idx, node);
+        self.outgoing.entry(idx).or_default();
+        self.incoming.entry(idx).or_default();
+    }
+    
+    // get the index the next call to add_node will return
+    pub(crate) fn next_index(&self) -> NodeIndex {
+        NodeIndex(self.next_node_id)
+    }
+    
+    // set the index the next call to add_node will return, used when restoring a saved graph
+    pub(crate) fn set_next_index(&mut self, idx: NodeIndex) {
+        self.next_node_id = self.next_node_id.max(idx.0);
+    }
+    
+    // add an edge to the graph with the given weight.
+    // both nodes must exist: an edge to or from a missing node is stored without adjacency
+    // entries and leaves the graph inconsistent. use try_add_edge when that is not guaranteed
     pub fn add_edge(&mut self, source: NodeIndex, target: NodeIndex, weight: W) {
         self.edges.insert((source, target), weight);
         
@@ -106,6 +135,61 @@ where
         }
     }
     
+    // add an edge to the graph with the given weight, checking that both nodes exist first.
+    // returns the weight the edge had before (adding an existing edge replaces its weight),
+    // or a missing node error, in which case the graph is left unchanged
+    pub fn try_add_edge(&mut self, source: NodeIndex, target: NodeIndex, weight: W) -> Result<Option<W>, GraphError> {
+        for idx in [source, target] {
+            if !self.nodes.contains_key(&idx) {
+                return Err(GraphError::MissingNode(idx));
+            }
+        }
+        
+        let previous = self.edges.insert((source, target), weight);
+        self.outgoing.entry(source).or_default().insert(target);
+        self.incoming.entry(target).or_default().insert(source);
+        Ok(previous)
+    }
+    
+    // removes an edge from the graph.
+    // returns the weight of the removed edge, or none if there was no such edge
+    pub fn remove_edge(&mut self, source: NodeIndex, target: NodeIndex) -> Option<W> {
+        let weight = self.edges.remove(&(source, target))?;
+        
+        if let Some(outgoing) = self.outgoing.get_mut(&source) {
+            outgoing.remove(&target);
+        }
+        
+        if let Some(incoming) = self.incoming.get_mut(&target) {
+            incoming.remove(&source);
+        }
+        
+        Some(weight)
+    }
+    
+    // removes a node and every edge into or out of it.
+    // the other nodes keep their indices and the removed index is never reused.
+    // returns the data of the removed node, or none if there was no such node
+    pub fn remove_node(&mut self, idx: NodeIndex) -> Option<N> {
+        let node = self.nodes.remove(&idx)?;
+        
+        for target in self.outgoing.remove(&idx).unwrap_or_default() {
+            self.edges.remove(&(idx, target));
+            if let Some(incoming) = self.incoming.get_mut(&target) {
+                incoming.remove(&idx);
+            }
+        }
+        
+        for source in self.incoming.remove(&idx).unwrap_or_default() {
+            self.edges.remove(&(source, idx));
+            if let Some(outgoing) = self.outgoing.get_mut(&source) {
+                outgoing.remove(&idx);
+            }
+        }
+        
+        Some(node)
+    }
+    
     // get a reference to a node by index
     #[allow(dead_code)]
     pub fn node_weight(&self, idx: NodeIndex) -> Option<&N> {
@@ -118,20 +202,100 @@ where
         self.edges.get(&(source, target))
     }
     
-    // get all edges as references
-    pub fn edge_references(&self) -> Vec<EdgeRef<N, W>> {
-        let mut result = Vec::new();
+    // get a reference to a node by index, or a missing node error.
+    // the checked counterpart of `graph[idx]`, which panics on a missing node
+    pub fn try_node(&self, idx: NodeIndex) -> Result<&N, GraphError> {
+        self.nodes.get(&idx).ok_or(GraphError::MissingNode(idx))
+    }
+    
+    // get a mutable reference to a node by index, or a missing node error
+    pub fn try_node_mut(&mut self, idx: NodeIndex) -> Result<&mut N, GraphError> {
+        self.nodes.get_mut(&idx).ok_or(GraphError::MissingNode(idx))
+    }
+    
+    // get a reference to an edge's weight, or a missing edge error
+    pub fn try_edge_weight(&self, source: NodeIndex, target: NodeIndex) -> Result<&W, GraphError> {
+        self.edges.get(&(source, target)).ok_or(GraphError::MissingEdge(source, target))
+    }
+    
+    // checks that the graph's internal structures agree with each other:
+    // every node index is below the next index, every node has adjacency sets and every
+    // adjacency set belongs to a node, every edge joins two existing nodes and appears in both
+    // adjacency sets, and every adjacency entry has an edge.
+    // returns an inconsistency error describing the first problem found
+    pub fn validate(&self) -> Result<(), GraphError> {
+        let inconsistent = |msg: String| Err(GraphError::Inconsistent(msg));
         
-        for ((source, target), weight) in &self.edges {
-            result.push(EdgeRef {
-                source_idx: *source,
-                target_idx: *target,
-                weight,
-                graph: self,
-            });
+        for idx in self.nodes.keys() {
+            if idx.0 >= self.next_node_id {
+                return inconsistent(format!("node {} is at or above the next index {}", idx.0, self.next_node_id));
+            }
+            if !self.outgoing.contains_key(idx) || !self.incoming.contains_key(idx) {
+                return inconsistent(format!("node {} has no adjacency sets", idx.0));
+            }
+        }
+        for idx in self.outgoing.keys().chain(self.incoming.keys()) {
+            if !self.nodes.contains_key(idx) {
+                return inconsistent(format!("adjacency sets for missing node {}", idx.0));
+            }
+        }
+        
+        for (source, target) in self.edges.keys() {
+            for idx in [source, target] {
+                if !self.nodes.contains_key(idx) {
+                    return inconsistent(format!("edge {} -> {} refers to missing node {}", source.0, target.0, idx.0));
+                }
+            }
+            if !self.outgoing[source].contains(target) || !self.incoming[target].contains(source) {
+                return inconsistent(format!("edge {} -> {} is missing from the adjacency sets", source.0, target.0));
+            }
         }
         
-        result
+        let adjacency_entries: usize = self.outgoing.values().map(|targets| targets.len()).sum();
+        let reverse_entries: usize = self.incoming.values().map(|sources| sources.len()).sum();
+        if adjacency_entries != self.edges.len() || reverse_entries != self.edges.len() {
+            return inconsistent(format!("{} edges but {} outgoing and {} incoming adjacency entries",
+                self.edges.len(), adjacency_entries, reverse_entries));
+        }
+        
+        Ok(())
+    }
+    
+    // iterate over every edge with its weight, in no particular order.
+    // the iterator borrows the graph and allocates nothing, so it is safe to use on millions of edges
+    pub fn edge_references(&self) -> impl Iterator<Item = EdgeRef<'_, N, W>> + '_ {
+        self.edges.iter().map(move |((source, target), weight)| EdgeRef {
+            source_idx: *source,
+            target_idx: *target,
+            weight,
+            graph: self,
+        })
+    }
+    
+    // iterate over the outgoing edges of a node with their weights, in no particular order.
+    // a missing node has no edges
+    pub fn out_edges(&self, node: NodeIndex) -> impl Iterator<Item = EdgeRef<'_, N, W>> + '_ {
+        self.neighbors(node).filter_map(move |target| {
+            self.edges.get(&(node, target)).map(|weight| EdgeRef {
+                source_idx: node,
+                target_idx: target,
+                weight,
+                graph: self,
+            })
+        })
+    }
+    
+    // iterate over the incoming edges of a node with their weights, in no particular order.
+    // a missing node has no edges
+    pub fn in_edges(&self, node: NodeIndex) -> impl Iterator<Item = EdgeRef<'_, N, W>> + '_ {
+        self.incoming_neighbors(node).filter_map(move |source| {
+            self.edges.get(&(source, node)).map(|weight| EdgeRef {
+                source_idx: source,
+                target_idx: node,
+                weight,
+                graph: self,
+            })
+        })
     }
     
     // get all outgoing neighbors of a node
@@ -152,6 +316,33 @@ where
             .flat_map(|neighbors| neighbors.iter().copied())
     }
     
+    // get the indices of all nodes in the graph (in no particular order)
+    pub fn node_indices(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.nodes.keys().copied()
+    }
+    
+    // collects every node within `hops` edges of the seed nodes, ignoring edge direction.
+    // takes in `seeds` - the starting nodes (missing ones are ignored), and `hops` - the radius
+    // returns the seeds plus every node reachable in at most `hops` steps
+    pub fn k_hop_neighborhood(&self, seeds: &[NodeIndex], hops: usize) -> HashSet<NodeIndex> {
+        let mut visited: HashSet<NodeIndex> = seeds.iter().copied().filter(|s| self.contains_node(*s)).collect();
+        let mut frontier: Vec<NodeIndex> = visited.iter().copied().collect();
+        
+        for _ in 0..hops {
+            let mut next = Vec::new();
+            for node in frontier {
+                for neighbor in self.neighbors(node).chain(self.incoming_neighbors(node)) {
+                    if visited.insert(neighbor) {
+                        next.push(neighbor);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        
+        visited
+    }
+    
     // check if a node exists in the graph
     #[allow(dead_code)]
     pub fn contains_node(&self, idx: NodeIndex) -> bool {
@@ -171,6 +362,16 @@ where
     }
 }
 
+impl<N, W> Default for DiGraph<N, W>
+where
+    N: Clone,
+    W: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // enable indexing into a graph with a NodeIndex to get the node data
 impl<N, W> std::ops::Index<NodeIndex> for DiGraph<N, W> {
     type Output = N;
@@ -189,7 +390,7 @@ impl<N, W> std::ops::IndexMut<NodeIndex> for DiGraph<N, W> {
 
 // represents a single financial transaction between two accounts.
 // contains all transaction details from the original dataset.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
     pub struct Transaction {
     // transaction step/time (sequential identifier)
     #[allow(dead_code)]
@@ -206,91 +407,313 @@ impl<N, W> std::ops::IndexMut<NodeIndex> for DiGraph<N, W> {
     // fraud indicator (1 if fraudulent, 0 if legitimate)
     #[allow(dead_code)]
     pub is_fraud: u8,
+    // account balances before and after the transaction, if the dataset has them
+    pub balances: Option<Balances>,
+}
+
+// the paysim balance columns of a transaction: both accounts' balances before and after it.
+// for a consistent transaction the origin loses the amount and the destination gains it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Balances {
+    // origin balance before the transaction (oldbalanceOrg)
+    pub old_orig: f64,
+    // origin balance after the transaction (newbalanceOrig)
+    pub new_orig: f64,
+    // destination balance before the transaction (oldbalanceDest)
+    pub old_dest: f64,
+    // destination balance after the transaction (newbalanceDest)
+    pub new_dest: f64,
+}
+
+// a transaction as stored inside a transaction graph.
+// identical to transaction except that both accounts are interned handles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionRecord {
+    // transaction step/time (sequential identifier)
+    pub step: u32,
+    // transaction type (payment, transfer, etc.)
+    pub r#type: String,
+    // monetary amount of the transaction
+    pub amount: f64,
+    // source account handle
+    pub name_orig: AccountId,
+    // destination account handle
+    pub name_dest: AccountId,
+    // fraud indicator (1 if fraudulent, 0 if legitimate)
+    pub is_fraud: u8,
+    // account balances before and after the transaction, if the dataset has them
+    pub balances: Option<Balances>,
 }
 
 // models a network of financial transactions as a directed graph.
 // nodes represent accounts and edges represent money transfers.
+// account names are interned once in `accounts`; everything else uses account handles.
+// every account is classified by `classifier` when its node is created.
 pub struct TransactionGraph {
     // directed graph with accounts as nodes and money transfers as weighted edges
-    pub graph: DiGraph<String, f64>,
-    // maps account ids to their corresponding node indices in the graph
-    pub node_map: HashMap<String, NodeIndex>,
+    pub graph: DiGraph<AccountId, f64>,
+    // maps account handles to their corresponding node indices in the graph
+    pub node_map: HashMap<AccountId, NodeIndex>,
     // original transaction records
-    pub transactions: Vec<Transaction>,
+    pub transactions: Vec<TransactionRecord>,
+    // string table holding every account name
+    pub accounts: AccountTable,
+    // the class of every account in the graph
+    pub classes: HashMap<AccountId, AccountClass>,
+    // decides the class of each new account
+    classifier: AccountClassifier,
 }
 
 impl TransactionGraph {
-    // creates a new empty transaction graph.
+    // creates a new empty transaction graph that classifies accounts by the paysim prefixes.
     // returns a TransactionGraph with no nodes or edges
     pub fn new() -> Self {
+        Self::with_classifier(AccountClassifier::default())
+    }
+
+    // creates a new empty transaction graph with custom account classification.
+    // takes in `classifier` - decides the class of each account from its id
+    // returns a TransactionGraph with no nodes or edges
+    pub fn with_classifier(classifier: AccountClassifier) -> Self {
         TransactionGraph {
             graph: DiGraph::new(),
             node_map: HashMap::new(),
             transactions: Vec::new(),
+            accounts: AccountTable::new(),
+            classes: HashMap::new(),
+            classifier,
         }
     }
 
+    // get the classifier new accounts are classified with
+    pub fn classifier(&self) -> &AccountClassifier {
+        &self.classifier
+    }
+
+    // replaces the classifier and reclassifies every account in the graph.
+    // takes in `classifier` - decides the class of each account from its id
+    pub fn set_classifier(&mut self, classifier: AccountClassifier) {
+        self.classifier = classifier;
+        self.reclassify();
+    }
+
+    // recomputes the class of every account in the graph from its id
+    pub(crate) fn reclassify(&mut self) {
+        self.classes = self.node_map.keys()
+            .map(|account| (*account, self.classifier.classify(self.accounts.name(*account))))
+            .collect();
+    }
+
+    // get the class of an account, or Other if it is not in the graph
+    pub fn account_class(&self, account: AccountId) -> AccountClass {
+        self.classes.get(&account).copied().unwrap_or_default()
+    }
+
     // adds a transaction to the graph, creating nodes if needed.
     // takes in  `transaction` - The transaction to add
     pub fn add_transaction(&mut self, transaction: Transaction) {
-        // Add nodes if they don't exist
-        let orig_idx = *self.node_map.entry(transaction.name_orig.clone()).or_insert_with(|| {
-            self.graph.add_node(transaction.name_orig.clone())
+        let name_orig = self.accounts.intern(&transaction.name_orig);
+        let name_dest = self.accounts.intern(&transaction.name_dest);
+        self.add_record(TransactionRecord {
+            step: transaction.step,
+            r#type: transaction.r#type,
+            amount: transaction.amount,
+            name_orig,
+            name_dest,
+            is_fraud: transaction.is_fraud,
+            balances: transaction.balances,
         });
+    }
 
-        let dest_idx = *self.node_map.entry(transaction.name_dest.clone()).or_insert_with(|| {
-            self.graph.add_node(transaction.name_dest.clone())
-        });
+    // adds a transaction whose accounts were already interned in `accounts`.
+    // this is the allocation-free path used by the csv loader.
+    // takes in `record` - The transaction to add
+    pub fn add_record(&mut self, record: TransactionRecord) {
+        // Add nodes if they don't exist
+        let orig_idx = self.node_for(record.name_orig);
+        let dest_idx = self.node_for(record.name_dest);
 
         // Add edge with weight as transaction amount
-        self.graph.add_edge(orig_idx, dest_idx, transaction.amount);
+        self.graph.add_edge(orig_idx, dest_idx, record.amount);
         
         // Store the transaction
-        self.transactions.push(transaction);
+        self.transactions.push(record);
     }
 
-    // Analyzes the transaction graph to calculate metrics for each account.
-    // Computes incoming/outgoing counts, volumes, and retention rates.
-    // returns HashMap mapping account IDs to their calculated metrics
-    pub fn calculate_account_metrics(&self) -> HashMap<String, AccountMetrics> {
-        let mut metrics = HashMap::new();
+    // adds a batch of new transactions to an existing graph.
+    // takes in `batch` - the transactions to add, in order
+    // returns the accounts touched by the batch (sorted, without duplicates). only these accounts'
+    // metrics can change, so they are all FraudAnalysis::apply_batch needs to recompute
+    pub fn add_batch(&mut self, batch: Vec<Transaction>) -> Vec<AccountId> {
+        let mut touched = Vec::with_capacity(batch.len() * 2);
+        for transaction in batch {
+            self.add_transaction(transaction);
+            let record = &self.transactions[self.transactions.len() - 1];
+            touched.push(record.name_orig);
+            touched.push(record.name_dest);
+        }
+        touched.sort_unstable();
+        touched.dedup();
+        touched
+    }
+
+    // removes an account, every transfer into or out of it and its stored transactions.
+    // used to prune known-good hubs before analysis. the account's name stays interned, so
+    // its handle (and every other handle and node index) stays valid; adding a transaction
+    // for the account later creates a new node with a fresh index.
+    // takes in `account` - the account to remove
+    // returns the removed transactions, in their original order (empty if the account was not in the graph)
+    pub fn remove_account(&mut self, account: AccountId) -> Vec<TransactionRecord> {
+        let Some(idx) = self.node_map.remove(&account) else {
+            return Vec::new();
+        };
+        self.classes.remove(&account);
+        self.graph.remove_node(idx);
         
-        // initialize metrics for all nodes
-        for (account, _) in self.node_map.iter() {
-            metrics.insert(account.clone(), AccountMetrics::new());
+        let (removed, kept) = std::mem::take(&mut self.transactions)
+            .into_iter()
+            .partition(|record| record.name_orig == account || record.name_dest == account);
+        self.transactions = kept;
+        removed
+    }
+    
+    // removes the transfer edge between two accounts and every stored transaction behind it.
+    // used to undo erroneous imports. both accounts stay in the graph, even if this was their only transfer.
+    // takes in `orig` and `dest` - the sending and receiving accounts
+    // returns the removed transactions, in their original order (empty if there was no such transfer)
+    pub fn remove_transfer(&mut self, orig: AccountId, dest: AccountId) -> Vec<TransactionRecord> {
+        let (Some(source), Some(target)) = (self.node_map.get(&orig), self.node_map.get(&dest)) else {
+            return Vec::new();
+        };
+        if self.graph.remove_edge(*source, *target).is_none() {
+            return Vec::new();
         }
         
-        // process all transactions to compute metrics
-        for edge in self.graph.edge_references() {
-            let source = self.graph[edge.source()].clone();
-            let target = self.graph[edge.target()].clone();
-            let amount = *edge.weight();
-            
-            // update outgoing metrics for source
-            if let Some(source_metrics) = metrics.get_mut(&source) {
-                source_metrics.outgoing_count += 1;
-                source_metrics.outgoing_volume += amount;
+        let (removed, kept) = std::mem::take(&mut self.transactions)
+            .into_iter()
+            .partition(|record| record.name_orig == orig && record.name_dest == dest);
+        self.transactions = kept;
+        removed
+    }
+    
+    // checks that the graph, the node map and the stored transactions agree with each other:
+    // the graph itself is consistent (see DiGraph::validate), every node is mapped from the
+    // account it holds and every account in the node map has that node, every account handle
+    // is in the string table and has a class, and every stored transaction has an edge between its accounts.
+    // returns an inconsistency error describing the first problem found
+    pub fn validate(&self) -> Result<(), GraphError> {
+        self.validate_structure()?;
+        for account in self.node_map.keys() {
+            if !self.classes.contains_key(account) {
+                return Err(GraphError::Inconsistent(format!("account {} has no class", self.account_name(*account))));
             }
-            
-            // update incoming metrics for target
-            if let Some(target_metrics) = metrics.get_mut(&target) {
-                target_metrics.incoming_count += 1;
-                target_metrics.incoming_volume += amount;
+        }
+        Ok(())
+    }
+
+    // runs every check of validate except the class check, so a graph whose classes have not
+    // been computed yet (such as one read from a snapshot) can be checked before its handles are resolved
+    pub(crate) fn validate_structure(&self) -> Result<(), GraphError> {
+        self.graph.validate()?;
+        let inconsistent = |msg: String| Err(GraphError::Inconsistent(msg));
+        
+        if self.node_map.len() != self.graph.node_count() {
+            return inconsistent(format!("{} mapped accounts but {} nodes", self.node_map.len(), self.graph.node_count()));
+        }
+        for (account, idx) in &self.node_map {
+            if account.0 as usize >= self.accounts.len() {
+                return inconsistent(format!("account handle {} is not in the string table", account.0));
+            }
+            if self.graph.try_node(*idx)? != account {
+                return inconsistent(format!("node {} does not hold account {}", idx.0, self.account_name(*account)));
             }
         }
         
-        // calculate retention rates
-        for (_, metrics) in metrics.iter_mut() {
-            metrics.calculate_retention_rate();
+        for record in &self.transactions {
+            let (Some(source), Some(target)) = (self.node_map.get(&record.name_orig), self.node_map.get(&record.name_dest)) else {
+                return inconsistent(format!("a transaction at step {} refers to an account without a node", record.step));
+            };
+            self.graph.try_edge_weight(*source, *target)?;
         }
         
-        metrics
+        Ok(())
+    }
+    
+    // returns the node of an account, adding it to the graph the first time it is seen
+    pub(crate) fn node_for(&mut self, account: AccountId) -> NodeIndex {
+        if let Some(idx) = self.node_map.get(&account) {
+            return *idx;
+        }
+        let idx = self.graph.add_node(account);
+        self.node_map.insert(account, idx);
+        self.classes.insert(account, self.classifier.classify(self.accounts.name(account)));
+        idx
+    }
+
+    // looks up the handle of an account name.
+    // returns none if the account does not appear in the graph
+    pub fn account_id(&self, name: &str) -> Option<AccountId> {
+        self.accounts.get(name)
+    }
+
+    // get the name of an account handle
+    pub fn account_name(&self, id: AccountId) -> &str {
+        self.accounts.name(id)
+    }
+
+    // freezes the account graph into compressed sparse rows, built from the transaction records.
+    // returns a csr graph with one node per account and one edge per graph edge
+    pub fn to_csr(&self) -> CsrGraph<AccountId, f64> {
+        CsrGraph::from_records(&self.node_map, &self.transactions)
+    }
+
+    // calculates the metrics of a single account straight from the graph.
+    // neighbors are visited in increasing node index order, the same order the csr uses,
+    // so the result is bit-for-bit identical to the full calculate_account_metrics pass.
+    // returns none if the account is not in the graph
+    pub fn account_metrics(&self, account: AccountId) -> Option<AccountMetrics> {
+        let idx = *self.node_map.get(&account)?;
+        let mut metrics = AccountMetrics::new();
+
+        let mut outgoing: Vec<(usize, f64)> = self.graph.out_edges(idx).map(|e| (e.target().0, *e.weight())).collect();
+        outgoing.sort_unstable_by_key(|(target, _)| *target);
+        for (_, amount) in outgoing {
+            metrics.outgoing_count += 1;
+            metrics.outgoing_volume += amount;
+        }
+
+        let mut incoming: Vec<(usize, f64)> = self.graph.in_edges(idx).map(|e| (e.source().0, *e.weight())).collect();
+        incoming.sort_unstable_by_key(|(source, _)| *source);
+        for (_, amount) in incoming {
+            metrics.incoming_count += 1;
+            metrics.incoming_volume += amount;
+        }
+
+        metrics.calculate_retention_rate();
+        Some(metrics)
+    }
+
+    // Analyzes the transaction graph to calculate metrics for each account.
+    // Computes incoming/outgoing counts, volumes, and retention rates.
+    // each account is computed straight from the graph, so no csr copy is built; callers that
+    // already hold a csr graph should use CsrGraph::account_metrics instead.
+    // returns HashMap mapping account handles to their calculated metrics
+    pub fn calculate_account_metrics(&self) -> HashMap<AccountId, AccountMetrics> {
+        self.node_map.keys()
+            .filter_map(|account| Some((*account, self.account_metrics(*account)?)))
+            .collect()
+    }
+}
+
+impl Default for TransactionGraph {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 // holds statistical metrics for an account's transaction behavior.
 // used to identify suspicious activity patterns.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AccountMetrics {
     // number of incoming transactions
     pub incoming_count: u32,
@@ -329,27 +752,27 @@ impl AccountMetrics {
     
     // determines if an account exhibits collector behavior.
     // collectors receive money from many sources but rarely send it out.
-    // returns true if the account matches collector patterns
-    pub fn is_collector(&self) -> bool {
+    // takes in `thresholds` - the thresholds for the account's class (see ClassThresholds::for_class)
+    // returns true if the account matches collector patterns, false if the class has no collector rule
+    pub fn is_collector(&self, thresholds: &DetectorThresholds) -> bool {
         // a collector has high incoming volume, significantly more incoming than outgoing 
         // transactions, and high retention rate
-        self.incoming_count > 5 && 
-        self.incoming_count > self.outgoing_count * 3 && 
-        self.retention_rate > 0.7
+        thresholds.collector.as_ref().is_some_and(|t| t.matches(self))
     }
     
     // determines if an account exhibits money mule behavior.
     // money mules receive and quickly forward large amounts of money. 
-    // returns true if the account matches money mule patterns
-    pub fn is_money_mule(&self) -> bool {
-        // a money mule primarily forwards most incoming funds
-        self.incoming_count >= 1 &&
-        self.outgoing_count >= 1 &&
-        // high volume throughput (most money comes in and goes right back out)
-        self.outgoing_volume > 0.5 * self.incoming_volume &&
-        // low retention rate (they don't keep much of the money)
-        self.retention_rate < 0.4 &&
-        // significant transaction volume
-        self.incoming_volume > 10000.0
+    // takes in `thresholds` - the thresholds for the account's class (see ClassThresholds::for_class)
+    // returns true if the account matches money mule patterns, false if the class has no mule rule
+    pub fn is_money_mule(&self, thresholds: &DetectorThresholds) -> bool {
+        // a money mule primarily forwards most incoming funds, keeps little of it,
+        // and moves a significant volume
+        thresholds.money_mule.as_ref().is_some_and(|t| t.matches(self))
+    }
+}
+
+impl Default for AccountMetrics {
+    fn default() -> Self {
+        Self::new()
     }
 }
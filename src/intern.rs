@@ -0,0 +1,130 @@
+// account id interning.
+// every account name is stored exactly once, in a single string table, and the rest of the
+// crate refers to accounts by a 4-byte handle. this keeps graph nodes, node map keys and
+// transaction records small and avoids cloning a String for every edge during analysis.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// a compact handle for an interned account name.
+// handles are dense: the n-th distinct name interned gets AccountId(n)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AccountId(pub u32);
+
+// marks an unused slot in the lookup table
+const EMPTY_SLOT: u32 = u32::MAX;
+
+// the string table for account names.
+// names are concatenated into one buffer and located through an offset array, and lookups
+// go through an open-addressing table of handles, so no name is ever stored twice.
+#[derive(Debug, Clone)]
+pub struct AccountTable {
+    // all names back to back
+    text: String,
+    // name n occupies text[ends[n - 1]..ends[n]] (with ends[-1] taken as 0)
+    ends: Vec<usize>,
+    // open-addressing hash table of handles; the length is always a power of two
+    slots: Vec<u32>,
+}
+
+impl AccountTable {
+    // creates an empty account table
+    pub fn new() -> Self {
+        AccountTable {
+            text: String::new(),
+            ends: Vec::new(),
+            slots: vec![EMPTY_SLOT; 16],
+        }
+    }
+
+    // get the number of interned names
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    // check if no names have been interned
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    // get the name behind a handle.
+    // panics if the handle was not produced by this table
+    pub fn name(&self, id: AccountId) -> &str {
+        let n = id.0 as usize;
+        let start = if n == 0 { 0 } else { self.ends[n - 1] };
+        &self.text[start..self.ends[n]]
+    }
+
+    // looks up the handle of a name without interning it.
+    // returns none if the name has never been interned
+    pub fn get(&self, name: &str) -> Option<AccountId> {
+        match self.slots[self.find_slot(name)] {
+            EMPTY_SLOT => None,
+            id => Some(AccountId(id)),
+        }
+    }
+
+    // returns the handle for a name, adding it to the table the first time it is seen
+    pub fn intern(&mut self, name: &str) -> AccountId {
+        let slot = self.find_slot(name);
+        if self.slots[slot] != EMPTY_SLOT {
+            return AccountId(self.slots[slot]);
+        }
+
+        let id = self.ends.len() as u32;
+        self.text.push_str(name);
+        self.ends.push(self.text.len());
+        self.slots[slot] = id;
+
+        // keep the load factor at or below one half
+        if self.ends.len() * 2 > self.slots.len() {
+            self.grow();
+        }
+
+        AccountId(id)
+    }
+
+    // iterates over every (handle, name) pair in handle order
+    pub fn iter(&self) -> impl Iterator<Item = (AccountId, &str)> + '_ {
+        (0..self.len() as u32).map(move |n| (AccountId(n), self.name(AccountId(n))))
+    }
+
+    // finds the slot holding `name`, or the empty slot where it would be inserted
+    fn find_slot(&self, name: &str) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut slot = (Self::hash(name) as usize) & mask;
+        loop {
+            let id = self.slots[slot];
+            if id == EMPTY_SLOT || self.name(AccountId(id)) == name {
+                return slot;
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    // doubles the lookup table and re-inserts every handle
+    fn grow(&mut self) {
+        let capacity = self.slots.len() * 2;
+        let mask = capacity - 1;
+        let mut slots = vec![EMPTY_SLOT; capacity];
+        for n in 0..self.len() as u32 {
+            let mut slot = (Self::hash(self.name(AccountId(n))) as usize) & mask;
+            while slots[slot] != EMPTY_SLOT {
+                slot = (slot + 1) & mask;
+            }
+            slots[slot] = n;
+        }
+        self.slots = slots;
+    }
+
+    fn hash(name: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Default for AccountTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,68 @@
+// financial transaction network analysis for fraud detection, as a reusable library.
+// the binary in main.rs is a thin client of this crate; other services can embed
+// the graph model, the loaders and the detectors directly.
+//
+// modules:
+// - graph: the directed graph, the transaction graph built on it, and per-account metrics
+// - intern: the account string table and the compact account handles used everywhere else
+// - classify: customer and merchant classification of account ids, and per-class detector thresholds
+// - csr: a frozen compressed-sparse-row copy of a graph for fast, compact traversal
+// - subgraph: ego networks around seed accounts and filtered views, as standalone transaction graphs
+// - paths: fewest-hop, lowest-cost and widest paths between two accounts, optionally in time order
+// - flow: edmonds-karp max flow and min cut between groups of accounts
+// - community: label propagation community detection on the undirected projection of a graph
+// - balance: transactions whose paysim balance columns do not add up and inconsistent balance histories
+// - reciprocity: account pairs that send money back and forth, with their timing and amount symmetry
+// - stats: degree distributions, power-law fits and other network-level summary statistics
+// - model: account features, step-based train/test splits and an in-crate logistic regression
+// - sweep: detector threshold sweeps with roc and precision-recall curves against the fraud labels
+// - synthetic: a seeded generator of paysim-like transactions with injected, labeled fraud patterns
+// - analysis: rule-based detection of collector and money mule accounts, reciprocal pairs, and community reports
+// - utilities: csv loading and timing helpers
+// - lists: analyst allowlists, denylists and the record of what the previous run reported
+// - streaming: online detection over a transaction feed, alerting as soon as an account matches
+// - export: graphml, dot and csv export of the graph or a neighborhood, for visualization
+// - snapshot: versioned binary snapshots of a built graph, to skip csv parsing on later runs
+// - error: the error type returned by every fallible operation
+pub mod error;
+pub mod graph;
+pub mod csr;
+pub mod intern;
+pub mod classify;
+pub mod subgraph;
+pub mod paths;
+pub mod flow;
+pub mod community;
+pub mod balance;
+pub mod reciprocity;
+pub mod stats;
+pub mod model;
+pub mod sweep;
+pub mod synthetic;
+pub mod analysis;
+pub mod utilities;
+pub mod snapshot;
+pub mod export;
+pub mod streaming;
+pub mod lists;
+
+pub use analysis::{AnalysisConfig, BalanceThresholds, Community, CommunityRanking, Detector, FlagChange, FlaggedAccount, FraudAnalysis, ReciprocityThresholds, Review, SuppressedAccount};
+pub use lists::AccountLists;
+pub use classify::{AccountClass, AccountClassifier, ClassThresholds, CollectorThresholds, DetectorThresholds, MuleThresholds};
+pub use balance::{BalanceMismatch, BalancePath};
+pub use csr::CsrGraph;
+pub use error::{FraudError, GraphError};
+pub use export::GraphExport;
+pub use flow::FlowResult;
+pub use graph::{AccountMetrics, Balances, DiGraph, NodeIndex, Transaction, TransactionGraph, TransactionRecord};
+pub use intern::{AccountId, AccountTable};
+pub use model::{FeatureMatrix, LogisticModel, ModelEvaluation, TrainingOptions};
+pub use paths::{AccountPath, Connection, ConnectionOptions, StatePath};
+pub use reciprocity::ReciprocalPair;
+pub use sweep::{SweepCurve, SweepPoint, ThresholdParameter};
+pub use synthetic::{GeneratorConfig, InjectedPattern, SyntheticDataset, Typology};
+pub use subgraph::{Direction, EgoOptions, TransactionFilter};
+pub use snapshot::{load_snapshot, save_snapshot};
+pub use stats::{DegreeStats, GraphStats, PowerLawFit, TypeFraudRate};
+pub use streaming::{Alert, AlertKind, OnlineDetector, StreamConfig};
+pub use utilities::{append_transaction_dataset, read_transaction_dataset, read_transaction_records};
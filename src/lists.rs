@@ -0,0 +1,100 @@
+// analyst-maintained account lists.
+// analysts review flagged accounts and record verdicts in plain text files:
+// - the allowlist holds known-good accounts (merchants, payroll hubs, ...) whose flags are suppressed
+// - the denylist holds known-bad accounts that are always reported, flagged or not
+// a third file records which accounts the previous run reported, so results can mark new ones.
+//
+// list files have one account per line, optionally followed by a comma and a reason:
+//   C1625226992, payroll hub for a large employer
+//   M1979787155
+// blank lines and lines starting with `#` are ignored.
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use crate::error::FraudError;
+
+// the allowlist, denylist and previous-run state a fraud analysis consults
+#[derive(Debug, Clone, Default)]
+pub struct AccountLists {
+    // known-good accounts mapped to the reason they are suppressed
+    pub allow: HashMap<String, String>,
+    // known-bad accounts mapped to the reason they are always reported
+    pub deny: HashMap<String, String>,
+    // accounts reported by the previous run, or none if there was no previous run
+    pub previous: Option<HashSet<String>>,
+}
+
+impl AccountLists {
+    // creates empty lists: nothing suppressed, nothing forced, no previous run
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // get the reason an account is allowlisted, if it is
+    pub fn allow_reason(&self, account: &str) -> Option<&str> {
+        self.allow.get(account).map(|reason| reason.as_str())
+    }
+
+    // get the reason an account is denylisted, if it is
+    pub fn deny_reason(&self, account: &str) -> Option<&str> {
+        self.deny.get(account).map(|reason| reason.as_str())
+    }
+
+    // checks whether an account is new since the previous run.
+    // every account counts as new when there was no previous run
+    pub fn is_new(&self, account: &str) -> bool {
+        self.previous.as_ref().is_none_or(|previous| !previous.contains(account))
+    }
+}
+
+// parses the text of a list file.
+// takes in `contents` - the file contents, and `default_reason` - the reason used for lines without one
+// returns the accounts mapped to their reasons
+pub fn parse_account_list(contents: &str, default_reason: &str) -> HashMap<String, String> {
+    let mut list = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (account, reason) = match line.split_once(',') {
+            Some((account, reason)) if !reason.trim().is_empty() => (account.trim(), reason.trim()),
+            Some((account, _)) => (account.trim(), default_reason),
+            None => (line, default_reason),
+        };
+        list.insert(account.to_string(), reason.to_string());
+    }
+    list
+}
+
+// loads an allowlist or denylist file.
+// takes in `path` - the list file, and `default_reason` - the reason used for lines without one
+// returns the accounts mapped to their reasons, or an i/o error if the file cannot be read
+pub fn load_account_list(path: &Path, default_reason: &str) -> Result<HashMap<String, String>, FraudError> {
+    Ok(parse_account_list(&fs::read_to_string(path)?, default_reason))
+}
+
+// loads the accounts reported by the previous run.
+// takes in `path` - the state file written by save_reported_accounts
+// returns none if the file does not exist yet (the first run), otherwise the reported accounts
+pub fn load_previous_run(path: &Path) -> Result<Option<HashSet<String>>, FraudError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(parse_account_list(&contents, "").into_keys().collect())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// records the accounts reported by this run, for the next run to compare against.
+// takes in `path` - the state file to write, and `accounts` - the reported account names
+// returns an i/o error if the file cannot be written
+pub fn save_reported_accounts<'a>(path: &Path, accounts: impl Iterator<Item = &'a str>) -> Result<(), FraudError> {
+    let mut contents = String::from("# accounts reported by the last run\n");
+    for account in accounts {
+        contents.push_str(account);
+        contents.push('\n');
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
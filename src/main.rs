@@ -4,55 +4,724 @@
 // it identifies two main types of suspicious accounts:
 // 1. collector accounts - which accumulate money with minimal outflows
 // 2. money mule accounts - which rapidly move money between accounts
-mod graph;
-mod analysis;
-mod utilities;
-
+// the detection logic lives in the fraud_detection library (src/lib.rs); this binary only wires it together.
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
 use std::path::Path;
-use analysis::FraudAnalysis;
-use utilities::{Timer, handle_error, read_transaction_dataset};
+use std::process;
+use fraud_detection::{AccountClass, AccountClassifier, AccountId, AccountLists, AccountPath, AccountTable, AnalysisConfig, CommunityRanking, ConnectionOptions, CsrGraph, FeatureMatrix, FraudAnalysis, FraudError, GeneratorConfig, GraphExport, LogisticModel, SyntheticDataset, TrainingOptions, TransactionFilter, TransactionGraph, TransactionRecord, Typology};
+use fraud_detection::lists::{load_account_list, load_previous_run, save_reported_accounts};
+use fraud_detection::snapshot::load_or_build;
+use fraud_detection::streaming::{OnlineDetector, StreamConfig, run_stream};
+use fraud_detection::sweep::{write_sweep_points, write_sweep_summary};
+use fraud_detection::utilities::{Timer, append_transaction_dataset, handle_error, read_transaction_dataset, read_transaction_records};
 
-// program entry point - loads transaction data, builds a graph representation, and performs fraud analysis to identify suspicious accounts.
-fn main() {
+// options accepted on the command line.
+// usage: cargo run --release -- [--data PATH] [--snapshot PATH] [--append PATH] [--threads N]
+//                               [--allowlist PATH] [--denylist PATH] [--state PATH]
+//                               [--export-graphml PATH] [--export-dot PATH] [--export-csv PREFIX]
+//                               [--around ACCOUNT] [--hops N]
+//                               [--communities N [--community-iterations N]] [--reciprocal N] [--balances N] [--path FROM TO [--time-ordered]]
+//                               [--flow SOURCE,SOURCE,... SINK,SINK,...] [--stats] [--stats-json PATH]
+//                               [--steps FROM-TO] [--min-amount X] [--max-amount X] [--type TYPE]...
+//                               [--customer-prefix P]... [--merchant-prefix P]...
+//                               [--customer-pattern GLOB]... [--merchant-pattern GLOB]...
+//                               [--train-model PATH [--split-step N]] [--model PATH [--scores N]]
+//                               [--sweep PREFIX [--target-precision P]]
+//        cargo run --release -- --generate PATH [--seed N]
+//        cargo run --release -- --stream PATH|- [--follow] [--customer-prefix P]... [--merchant-prefix P]... [--customer-pattern GLOB]... [--merchant-pattern GLOB]...
+struct Options {
     // path to the cleaned dataset
-    let file_path = "data/cleaned_fraud_dataset.csv";
+    data_path: String,
+    // optional graph snapshot to load from (and refresh when stale)
+    snapshot_path: Option<String>,
+    // optional csv of new transactions to append; only flag changes are reported
+    append_path: Option<String>,
+    // worker threads for the analysis (0 for one per core)
+    threads: usize,
+    // known-good accounts whose flags are suppressed
+    allowlist_path: Option<String>,
+    // known-bad accounts that are always reported
+    denylist_path: Option<String>,
+    // file recording the accounts reported by the previous run
+    state_path: Option<String>,
+    // transaction feed for online detection ("-" for stdin)
+    stream_path: Option<String>,
+    // keep waiting for new lines at the end of the feed
+    follow: bool,
+    // graphml file to export the graph to
+    graphml_path: Option<String>,
+    // dot file to export the graph to
+    dot_path: Option<String>,
+    // prefix of the gephi node and edge csv files to export the graph to
+    csv_prefix: Option<String>,
+    // export only the neighborhood of this account instead of the whole graph
+    around: Option<String>,
+    // radius of the exported neighborhood
+    hops: usize,
+    // restricts the analysis to the matching transactions
+    filter: TransactionFilter,
+    // print the top N communities by density of flagged accounts
+    communities: Option<usize>,
+    // the most label propagation passes when finding communities
+    community_iterations: usize,
+    // print the top N account pairs that send money back and forth
+    reciprocal: Option<usize>,
+    // print the top N transactions and accounts whose balances do not add up
+    balances: Option<usize>,
+    // print how money could have moved from the first account to the second
+    path: Option<(String, String)>,
+    // only follow paths whose steps never decrease
+    time_ordered: bool,
+    // print the max flow from the first comma separated group of accounts to the second
+    flow: Option<(String, String)>,
+    // print network-level summary statistics after loading
+    stats: bool,
+    // also write the summary statistics to this json file
+    stats_json_path: Option<String>,
+    // custom customer and merchant id prefixes and patterns, if any were given
+    classifier: Option<AccountClassifier>,
+    // train a model and save it to this file
+    train_model_path: Option<String>,
+    // last step of the training data; later steps are held out for evaluation
+    split_step: Option<u32>,
+    // score accounts with the model saved in this file
+    model_path: Option<String>,
+    // how many of the highest scored accounts to print
+    scores: usize,
+    // sweep the detector thresholds and write the curves to PREFIX_points.csv and PREFIX_auc.csv
+    sweep_prefix: Option<String>,
+    // the precision a suggested threshold has to reach
+    target_precision: f64,
+    // write a synthetic dataset to this file instead of analyzing one
+    generate_path: Option<String>,
+    // seed of the synthetic dataset
+    seed: u64,
+}
+
+// parses the command line arguments (without the program name).
+// returns a config error for unknown flags, bad values or more than one mode
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, FraudError> {
+    let mut options = Options {
+        data_path: "data/cleaned_fraud_dataset.csv".to_string(),
+        snapshot_path: None,
+        append_path: None,
+        threads: 1,
+        allowlist_path: None,
+        denylist_path: None,
+        state_path: None,
+        stream_path: None,
+        follow: false,
+        graphml_path: None,
+        dot_path: None,
+        csv_prefix: None,
+        around: None,
+        hops: 2,
+        filter: TransactionFilter::new(),
+        communities: None,
+        community_iterations: AnalysisConfig::default().community_iterations,
+        reciprocal: None,
+        balances: None,
+        path: None,
+        time_ordered: false,
+        flow: None,
+        stats: false,
+        stats_json_path: None,
+        classifier: None,
+        train_model_path: None,
+        split_step: None,
+        model_path: None,
+        scores: 20,
+        sweep_prefix: None,
+        target_precision: 0.9,
+        generate_path: None,
+        seed: GeneratorConfig::default().seed,
+    };
+    let (mut min_amount, mut max_amount) = (None, None);
+    let (mut customer_prefixes, mut merchant_prefixes) = (Vec::new(), Vec::new());
+    let (mut customer_patterns, mut merchant_patterns) = (Vec::new(), Vec::new());
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next().ok_or_else(|| FraudError::Config(format!("{} needs a value", flag)))
+        };
+        match arg.as_str() {
+            "--data" => options.data_path = value("--data")?,
+            "--snapshot" => options.snapshot_path = Some(value("--snapshot")?),
+            "--append" => options.append_path = Some(value("--append")?),
+            "--allowlist" => options.allowlist_path = Some(value("--allowlist")?),
+            "--denylist" => options.denylist_path = Some(value("--denylist")?),
+            "--state" => options.state_path = Some(value("--state")?),
+            "--stream" => options.stream_path = Some(value("--stream")?),
+            "--follow" => options.follow = true,
+            "--export-graphml" => options.graphml_path = Some(value("--export-graphml")?),
+            "--export-dot" => options.dot_path = Some(value("--export-dot")?),
+            "--export-csv" => options.csv_prefix = Some(value("--export-csv")?),
+            "--around" => options.around = Some(value("--around")?),
+            "--hops" => {
+                let raw = value("--hops")?;
+                options.hops = raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--hops expects a number, got `{}`", raw))
+                })?;
+            }
+            "--threads" => {
+                let raw = value("--threads")?;
+                options.threads = raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--threads expects a number, got `{}`", raw))
+                })?;
+            }
+            "--communities" => {
+                let raw = value("--communities")?;
+                options.communities = Some(raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--communities expects a number, got `{}`", raw))
+                })?);
+            }
+            "--community-iterations" => {
+                let raw = value("--community-iterations")?;
+                options.community_iterations = raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--community-iterations expects a number, got `{}`", raw))
+                })?;
+            }
+            "--reciprocal" => {
+                let raw = value("--reciprocal")?;
+                options.reciprocal = Some(raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--reciprocal expects a number, got `{}`", raw))
+                })?);
+            }
+            "--balances" => {
+                let raw = value("--balances")?;
+                options.balances = Some(raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--balances expects a number, got `{}`", raw))
+                })?);
+            }
+            "--path" => {
+                let from = value("--path")?;
+                options.path = Some((from, value("--path")?));
+            }
+            "--time-ordered" => options.time_ordered = true,
+            "--stats" => options.stats = true,
+            "--stats-json" => options.stats_json_path = Some(value("--stats-json")?),
+            "--flow" => {
+                let sources = value("--flow")?;
+                options.flow = Some((sources, value("--flow")?));
+            }
+            "--steps" => {
+                let raw = value("--steps")?;
+                let range = raw.split_once('-')
+                    .and_then(|(from, to)| Some(from.trim().parse::<u32>().ok()?..=to.trim().parse::<u32>().ok()?))
+                    .ok_or_else(|| FraudError::Config(format!("--steps expects FROM-TO, got `{}`", raw)))?;
+                options.filter.steps = Some(range);
+            }
+            "--min-amount" => min_amount = Some(parse_amount("--min-amount", &value("--min-amount")?)?),
+            "--max-amount" => max_amount = Some(parse_amount("--max-amount", &value("--max-amount")?)?),
+            "--type" => options.filter.types.get_or_insert_with(Vec::new).push(value("--type")?),
+            "--train-model" => options.train_model_path = Some(value("--train-model")?),
+            "--model" => options.model_path = Some(value("--model")?),
+            "--split-step" => {
+                let raw = value("--split-step")?;
+                options.split_step = Some(raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--split-step expects a step number, got `{}`", raw))
+                })?);
+            }
+            "--scores" => {
+                let raw = value("--scores")?;
+                options.scores = raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--scores expects a number, got `{}`", raw))
+                })?;
+            }
+            "--sweep" => options.sweep_prefix = Some(value("--sweep")?),
+            "--target-precision" => {
+                let raw = value("--target-precision")?;
+                options.target_precision = raw.parse().ok().filter(|p: &f64| (0.0..=1.0).contains(p)).ok_or_else(|| {
+                    FraudError::Config(format!("--target-precision expects a number between 0 and 1, got `{}`", raw))
+                })?;
+            }
+            "--generate" => options.generate_path = Some(value("--generate")?),
+            "--seed" => {
+                let raw = value("--seed")?;
+                options.seed = raw.parse().map_err(|_| {
+                    FraudError::Config(format!("--seed expects a number, got `{}`", raw))
+                })?;
+            }
+            "--customer-prefix" => customer_prefixes.push(value("--customer-prefix")?),
+            "--merchant-prefix" => merchant_prefixes.push(value("--merchant-prefix")?),
+            "--customer-pattern" => customer_patterns.push(value("--customer-pattern")?),
+            "--merchant-pattern" => merchant_patterns.push(value("--merchant-pattern")?),
+            other => return Err(FraudError::Config(format!("unknown argument `{}`", other))),
+        }
+    }
+
+    if min_amount.is_some() || max_amount.is_some() {
+        options.filter.amounts = Some(min_amount.unwrap_or(f64::NEG_INFINITY)..=max_amount.unwrap_or(f64::INFINITY));
+    }
+    // given prefixes replace the default ones of their class; patterns are added to them
+    if !customer_prefixes.is_empty() || !merchant_prefixes.is_empty() || !customer_patterns.is_empty() || !merchant_patterns.is_empty() {
+        let mut classifier = AccountClassifier { customer_patterns, merchant_patterns, ..AccountClassifier::default() };
+        if !customer_prefixes.is_empty() {
+            classifier.customer_prefixes = customer_prefixes;
+        }
+        if !merchant_prefixes.is_empty() {
+            classifier.merchant_prefixes = merchant_prefixes;
+        }
+        options.classifier = Some(classifier);
+    }
+
+    // each mode replaces the default analysis, so at most one may be given.
+    // the export flags form one mode and may be combined with each other
+    let export = [
+        ("--export-graphml", options.graphml_path.is_some()),
+        ("--export-dot", options.dot_path.is_some()),
+        ("--export-csv", options.csv_prefix.is_some()),
+    ];
+    let export_flag = export.iter().find(|(_, given)| *given).map(|(flag, _)| *flag);
+    let modes = [
+        ("--stream", options.stream_path.is_some()),
+        ("--generate", options.generate_path.is_some()),
+        ("--append", options.append_path.is_some()),
+        ("--path", options.path.is_some()),
+        ("--train-model", options.train_model_path.is_some()),
+        ("--sweep", options.sweep_prefix.is_some()),
+        ("--flow", options.flow.is_some()),
+        (export_flag.unwrap_or("--export-csv"), export_flag.is_some()),
+    ];
+    let active: Vec<&str> = modes.iter().filter(|(_, given)| *given).map(|(flag, _)| *flag).collect();
+    if let [first, second, ..] = active.as_slice() {
+        return Err(FraudError::Config(format!("{} and {} cannot be used together", first, second)));
+    }
+
+    Ok(options)
+}
+
+// parses the value of an amount flag
+fn parse_amount(flag: &str, raw: &str) -> Result<f64, FraudError> {
+    raw.parse().map_err(|_| FraudError::Config(format!("{} expects an amount, got `{}`", flag, raw)))
+}
+
+// online mode - consumes a transaction feed in step order and prints alerts as they are raised.
+// takes in `feed` - a csv file path, or "-" for stdin, `follow` - wait for more input at the end,
+// and `classifier` - custom id prefixes and patterns, if any were given
+// returns an error if the feed cannot be opened or read
+fn run_online(feed: &str, follow: bool, classifier: Option<AccountClassifier>) -> Result<(), FraudError> {
+    println!("Online Money Laundering Detection");
+    println!("=================================");
+    
+    let mut config = StreamConfig::default();
+    if let Some(classifier) = classifier {
+        config.classifier = classifier;
+    }
+    let mut detector = OnlineDetector::new(config);
+    let print_alert = |detector: &OnlineDetector, alert: &fraud_detection::Alert| {
+        println!("ALERT step {:<6} {:<15} {:<17} in {:.2} / out {:.2}",
+            alert.step,
+            detector.accounts().name(alert.account),
+            alert.kind.name(),
+            alert.metrics.incoming_volume,
+            alert.metrics.outgoing_volume);
+    };
+    
+    let consumed = if feed == "-" {
+        run_stream(io::stdin().lock(), follow, &mut detector, print_alert)?
+    } else {
+        run_stream(BufReader::new(File::open(feed)?), follow, &mut detector, print_alert)?
+    };
+    println!("\nConsumed {} transactions.", consumed);
+    Ok(())
+}
+
+// loads the analyst lists, prints the deduplicated review and records what was reported.
+// takes in `analysis` - the finished analysis, `accounts` - the account names, and `options` - the list paths
+// returns an error if a list or the state file cannot be read or written
+fn run_review(analysis: &mut FraudAnalysis, accounts: &AccountTable, options: &Options) -> Result<(), FraudError> {
+    let mut lists = AccountLists::new();
+    if let Some(path) = &options.allowlist_path {
+        lists.allow = load_account_list(Path::new(path), "allowlisted")?;
+    }
+    if let Some(path) = &options.denylist_path {
+        lists.deny = load_account_list(Path::new(path), "denylisted")?;
+    }
+    if let Some(path) = &options.state_path {
+        lists.previous = load_previous_run(Path::new(path))?;
+    }
+    analysis.set_lists(lists);
+    
+    let review = analysis.review(accounts);
+    FraudAnalysis::print_review(&review, accounts);
+    
+    if let Some(path) = &options.state_path {
+        let reported = review.reported.iter().map(|flagged| accounts.name(flagged.account));
+        save_reported_accounts(Path::new(path), reported)?;
+    }
+    Ok(())
+}
+
+// prints the fewest-hop, cheapest and widest paths between two accounts.
+// takes in `graph` - the loaded graph, `from` and `to` - the account names, and `time_ordered` - only follow non-decreasing steps
+// returns a config error if either account is not in the graph
+fn run_connection(graph: &TransactionGraph, from: &str, to: &str, time_ordered: bool) -> Result<(), FraudError> {
+    let lookup = |name: &str| graph.account_id(name)
+        .ok_or_else(|| FraudError::Config(format!("account `{}` is not in the graph", name)));
+    let options = ConnectionOptions { time_ordered, ..ConnectionOptions::default() };
+    
+    let Some(connection) = graph.connection_with(lookup(from)?, lookup(to)?, &options) else {
+        println!("\nNo {}path from {} to {}", if time_ordered { "time-ordered " } else { "" }, from, to);
+        return Ok(());
+    };
+    
+    let print_path = |label: &str, path: &AccountPath| {
+        let names: Vec<&str> = path.accounts.iter().map(|a| graph.account_name(*a)).collect();
+        let steps: Vec<String> = path.steps.iter().map(|s| s.to_string()).collect();
+        println!("{:<12} {:<14.4} {} (steps {})", label, path.value, names.join(" -> "), steps.join(", "));
+    };
+    println!("\n=== Paths from {} to {} ===", from, to);
+    print_path("fewest hops", &connection.fewest_hops);
+    print_path("cheapest", &connection.cheapest);
+    print_path("widest", &connection.widest);
+    Ok(())
+}
+
+// prints the max flow between two groups of accounts and the chokepoints of its minimum cut.
+// takes in `graph` - the loaded graph, and `sources`, `sinks` - comma separated account names
+// returns a config error if an account is not in the graph
+fn run_flow(graph: &TransactionGraph, sources: &str, sinks: &str) -> Result<(), FraudError> {
+    let lookup = |names: &str| names.split(',')
+        .map(|name| graph.account_id(name.trim())
+            .ok_or_else(|| FraudError::Config(format!("account `{}` is not in the graph", name.trim()))))
+        .collect::<Result<Vec<_>, _>>();
+    let flow = graph.max_flow(&lookup(sources)?, &lookup(sinks)?);
+    
+    println!("\n=== Max flow from {} to {}: {:.2} ===", sources, sinks, flow.value);
+    println!("{:<15} {:<15} {:<15}", "From", "To", "Capacity");
+    for (from, to, capacity) in &flow.cut_edges {
+        println!("{:<15} {:<15} {:<15.2}", graph.account_name(*from), graph.account_name(*to), capacity);
+    }
+    let chokepoints: Vec<&str> = flow.chokepoints.iter().map(|a| graph.account_name(*a)).collect();
+    println!("Chokepoint accounts: {}", chokepoints.join(", "));
+    Ok(())
+}
+
+// trains a fraud model on the earlier part of the graph, evaluates it on the later part and saves it.
+// takes in `graph` - the loaded graph, `path` - where to save the model, and `split_step` - the
+// last training step (by default the step that puts about 80% of the transactions in training)
+// returns an error if the split leaves no training data or the model cannot be written
+fn run_training(graph: &TransactionGraph, path: &str, split_step: Option<u32>) -> Result<(), FraudError> {
+    let split = match split_step {
+        Some(step) => step,
+        None => {
+            let mut steps: Vec<u32> = graph.transactions.iter().map(|r| r.step).collect();
+            steps.sort_unstable();
+            steps.get(steps.len() * 4 / 5).copied().unwrap_or(0)
+        }
+    };
+    let (train, test) = graph.split_at_step(split);
+    let (train, test) = (FeatureMatrix::from_graph(&train), FeatureMatrix::from_graph(&test));
+    if train.is_empty() {
+        return Err(FraudError::Config(format!("no transactions at or before step {} to train on", split)));
+    }
+    
+    let training_timer = Timer::new("Model training");
+    let model = LogisticModel::train(&train, &TrainingOptions::default());
+    drop(training_timer);
+    
+    println!("\n=== Model trained on steps up to {} ({} accounts), evaluated on later steps ({} accounts) ===",
+        split, train.len(), test.len());
+    if test.is_empty() {
+        println!("No transactions after step {}; nothing to evaluate on", split);
+    } else {
+        let evaluation = model.evaluate(&test, 0.5);
+        println!("{:<12} {:<10} {:<12} {:<10} {:<10}", "Positives", "AUC", "Precision", "Recall", "F1");
+        println!("{:<12} {:<10.4} {:<12.4} {:<10.4} {:<10.4}",
+            evaluation.positives, evaluation.auc, evaluation.precision, evaluation.recall, evaluation.f1);
+    }
+    
+    model.save(Path::new(path))?;
+    println!("Saved model to {}", path);
+    Ok(())
+}
+
+// generates a synthetic dataset with the default pool sizes and pattern counts and writes it as csv.
+// takes in `path` - where to write the dataset, and `seed` - the generator seed
+// returns an error if the file cannot be written
+fn run_generate(path: &str, seed: u64) -> Result<(), FraudError> {
+    let config = GeneratorConfig { seed, ..GeneratorConfig::default() };
+    let dataset = SyntheticDataset::generate(&config);
+    dataset.write_csv(BufWriter::new(File::create(path)?))?;
+    
+    println!("Wrote {} transactions to {}", dataset.transactions.len(), path);
+    println!("{:<14} {:<10} {:<30}", "Pattern", "Injected", "Central Accounts");
+    for typology in Typology::ALL {
+        let central = dataset.central_accounts(typology);
+        println!("{:<14} {:<10} {:<30}", typology.name(), central.len(), central.join(", "));
+    }
+    Ok(())
+}
+
+// sweeps every detector threshold against the fraud labels, writes the curves and prints the suggested values.
+// takes in `graph` - the loaded graph, `config` - the analysis settings whose thresholds are the
+// starting point, `prefix` - the output file prefix, and `target_precision` - the precision to meet
+// returns an error if a file cannot be written
+fn run_sweep(graph: &TransactionGraph, config: AnalysisConfig, prefix: &str, target_precision: f64) -> Result<(), FraudError> {
+    let sweep_timer = Timer::new("Threshold sweep");
+    let curves = FraudAnalysis::with_config(graph, config).sweep_all_thresholds(graph);
+    drop(sweep_timer);
+    
+    let points_path = format!("{}_points.csv", prefix);
+    let auc_path = format!("{}_auc.csv", prefix);
+    write_sweep_points(&curves, BufWriter::new(File::create(&points_path)?))?;
+    write_sweep_summary(&curves, target_precision, BufWriter::new(File::create(&auc_path)?))?;
+    
+    println!("\n=== Suggested Detector Thresholds ===");
+    println!("{:<12} {:<20} {:<10} {:<10} {:<14} {:<10} {:<20}",
+        "Detector", "Threshold", "ROC AUC", "PR AUC", "Best F1 At", "F1", format!("Precision >= {}", target_precision));
+    for curve in &curves {
+        let best = curve.best_f1().map_or("-".to_string(), |p| p.value.to_string());
+        let f1 = curve.best_f1().map_or(0.0, |p| p.f1);
+        let target = curve.for_precision(target_precision)
+            .map_or("-".to_string(), |p| format!("{} (recall {:.4})", p.value, p.recall));
+        println!("{:<12} {:<20} {:<10.4} {:<10.4} {:<14} {:<10.4} {:<20}",
+            curve.parameter.detector().name(), curve.parameter.name(), curve.roc_auc, curve.pr_auc, best, f1, target);
+    }
+    println!("Wrote sweep curves to {} and {}", points_path, auc_path);
+    Ok(())
+}
+
+// writes the requested graph exports, either of the whole graph or of the neighborhood of one account.
+// takes in `graph` - the loaded graph, `analysis` - its analysis, and `options` - the export paths
+// returns an error if the account is unknown or a file cannot be written
+fn run_export(graph: &TransactionGraph, analysis: &FraudAnalysis, options: &Options) -> Result<(), FraudError> {
+    let mut export = GraphExport::new(graph, analysis);
+    if let Some(name) = &options.around {
+        let idx = graph.account_id(name)
+            .and_then(|account| graph.node_map.get(&account).copied())
+            .ok_or_else(|| FraudError::Config(format!("account `{}` is not in the graph", name)))?;
+        export = export.with_nodes(&graph.graph.k_hop_neighborhood(&[idx], options.hops));
+    }
+    
+    if let Some(path) = &options.graphml_path {
+        export.write_graphml(BufWriter::new(File::create(path)?))?;
+        println!("Wrote {} accounts to {}", export.node_count(), path);
+    }
+    if let Some(path) = &options.dot_path {
+        export.write_dot(BufWriter::new(File::create(path)?))?;
+        println!("Wrote {} accounts to {}", export.node_count(), path);
+    }
+    if let Some(prefix) = &options.csv_prefix {
+        let nodes_path = format!("{}_nodes.csv", prefix);
+        let edges_path = format!("{}_edges.csv", prefix);
+        export.write_csv(BufWriter::new(File::create(&nodes_path)?), BufWriter::new(File::create(&edges_path)?))?;
+        println!("Wrote {} accounts to {} and {}", export.node_count(), nodes_path, edges_path);
+    }
+    Ok(())
+}
+
+// program entry point - parses the command line and runs the requested mode, exiting with
+// status 2 for bad arguments and 1 for any other error.
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            handle_error(e);
+            process::exit(2);
+        }
+    };
+    
+    if let Err(e) = run(options) {
+        handle_error(e);
+        process::exit(1);
+    }
+}
+
+// runs the mode selected by the options: online detection, dataset generation, or loading
+// transaction data into a graph and analyzing it to identify suspicious accounts.
+// takes in `options` - the parsed command line
+// returns the first error any step runs into
+fn run(options: Options) -> Result<(), FraudError> {
+    if let Some(feed) = &options.stream_path {
+        return run_online(feed, options.follow, options.classifier.clone());
+    }
+    
+    if let Some(path) = &options.generate_path {
+        return run_generate(path, options.seed);
+    }
+    
+    let file_path = options.data_path.as_str();
     
     // verify the data file exists before proceeding
     if !Path::new(file_path).exists() {
-        handle_error(format!("File not found: {}", file_path));
-        return;
+        return Err(FraudError::Config(format!("File not found: {}", file_path)));
     }
     
     println!("Money Laundering Detection Analysis");
     println!("===================================");
     
+    let filtered = options.filter.steps.is_some() || options.filter.amounts.is_some() || options.filter.types.is_some();
+    let config = AnalysisConfig {
+        threads: options.threads,
+        community_iterations: options.community_iterations,
+        ..AnalysisConfig::default()
+    };
+    
+    // the default analysis only needs the csr and the records, so without a snapshot, filter,
+    // statistics or another mode the file is read into records and the hash-map graph is never built
+    let other_mode = options.append_path.is_some() || options.path.is_some() || options.train_model_path.is_some()
+        || options.sweep_prefix.is_some() || options.flow.is_some()
+        || options.graphml_path.is_some() || options.dot_path.is_some() || options.csv_prefix.is_some();
+    let needs_graph = options.snapshot_path.is_some() || filtered || options.stats || options.stats_json_path.is_some();
+    if !other_mode && !needs_graph {
+        let load_timer = Timer::new("Data loading and csr construction");
+        let (accounts, transactions) = read_transaction_records(file_path)?;
+        let csr = CsrGraph::from_account_records(&accounts, &transactions);
+        let classifier = options.classifier.clone().unwrap_or_default();
+        let classes = accounts.iter().map(|(account, name)| (account, classifier.classify(name))).collect();
+        drop(load_timer);
+        println!("Loaded {} transactions, {} unique accounts", transactions.len(), accounts.len());
+        return run_analysis(csr, accounts, classes, transactions, config, &options);
+    }
+    
     // load data and build the transaction graph
     let load_timer = Timer::new("Data loading and graph construction");
-    let graph = match read_transaction_dataset(file_path) {
-        Ok(g) => g,
-        Err(e) => {
-            handle_error(format!("Failed to load data: {}", e));
-            return;
-        }
+    let classifier = options.classifier.clone().unwrap_or_default();
+    let mut graph = match &options.snapshot_path {
+        Some(snapshot_path) => load_or_build(Path::new(file_path), Path::new(snapshot_path), &classifier)?,
+        None => read_transaction_dataset(file_path)?,
     };
     drop(load_timer);
+    
+    // with custom id prefixes or patterns, classify the accounts again (load_or_build already did for snapshots)
+    if graph.classifier() != &classifier {
+        graph.set_classifier(classifier);
+    }
+    
+    // with filter flags, analyze only the matching transactions
+    if filtered {
+        graph = graph.filter(&options.filter);
+    }
 
     // output summary statistics about the loaded data
-    println!("Loaded {} transactions, {} unique accounts", 
+    println!("{} {} transactions, {} unique accounts", 
+        if filtered { "Kept" } else { "Loaded" },
         graph.transactions.len(),
         graph.node_map.len());
     
-    // create the fraud analysis module and run analysis
+    // with --stats or --stats-json, summarize the network before analyzing it
+    if options.stats || options.stats_json_path.is_some() {
+        let stats = graph.stats();
+        stats.print();
+        if let Some(path) = &options.stats_json_path {
+            std::fs::write(path, stats.to_json())?;
+        }
+    }
+    
+    // with --append, analyze the existing graph, add the new batch and report only what changed
+    if let Some(batch_path) = &options.append_path {
+        let mut fraud_analysis = FraudAnalysis::with_config(&graph, config);
+        let batch_timer = Timer::new("Applying transaction batch");
+        let touched = append_transaction_dataset(&mut graph, batch_path)?;
+        let changes = fraud_analysis.apply_batch(&graph, &touched);
+        drop(batch_timer);
+        
+        println!("Batch touched {} accounts", touched.len());
+        FraudAnalysis::print_flag_changes(&changes, &graph.accounts);
+        println!("\nAnalysis complete.");
+        return Ok(());
+    }
+    
+    // with --path, print how the two accounts are connected instead of running the detectors
+    if let Some((from, to)) = &options.path {
+        return run_connection(&graph, from, to, options.time_ordered);
+    }
+    
+    // with --train-model, train, evaluate and save a model instead of running the detectors
+    if let Some(path) = &options.train_model_path {
+        return run_training(&graph, path, options.split_step);
+    }
+    
+    // with --sweep, write threshold curves against the fraud labels instead of running the detectors
+    if let Some(prefix) = &options.sweep_prefix {
+        return run_sweep(&graph, config, prefix, options.target_precision);
+    }
+    
+    // with --flow, print the max flow and min cut between the two groups
+    if let Some((sources, sinks)) = &options.flow {
+        return run_flow(&graph, sources, sinks);
+    }
+    
+    // with an export flag, analyze the graph and write it out for visualization instead of printing tables
+    if options.graphml_path.is_some() || options.dot_path.is_some() || options.csv_prefix.is_some() {
+        let fraud_analysis = FraudAnalysis::with_config(&graph, config);
+        run_export(&graph, &fraud_analysis, &options)?;
+        println!("\nExport complete.");
+        return Ok(());
+    }
+    
+    // the graph is frozen into csr form and only the account names, classes and transactions are kept
+    let csr = graph.to_csr();
+    let accounts = std::mem::take(&mut graph.accounts);
+    let classes = std::mem::take(&mut graph.classes);
+    let transactions = std::mem::take(&mut graph.transactions);
+    drop(graph);
+    run_analysis(csr, accounts, classes, transactions, config, &options)
+}
+
+// runs the detectors and the optional reports on a frozen graph and prints the results.
+// takes in `csr` - the account graph, `accounts` - the account names, `classes` - the class of
+// every account, `transactions` - the transaction records, `config` - the analysis settings,
+// and `options` - the parsed command line
+// returns an error if a model, list or state file cannot be read or written
+fn run_analysis(csr: CsrGraph<AccountId, f64>, accounts: AccountTable, classes: HashMap<AccountId, AccountClass>,
+                transactions: Vec<TransactionRecord>, config: AnalysisConfig, options: &Options) -> Result<(), FraudError> {
     let analysis_timer = Timer::new("Fraud analysis");
-    let fraud_analysis = FraudAnalysis::new(&graph);
+    let mut fraud_analysis = FraudAnalysis::from_csr(&csr, config);
+    fraud_analysis.set_classes(classes);
+    // community detection, reciprocal pairs, balance checks and model scores work from the csr and the transactions
+    let communities = options.communities
+        .map(|_| fraud_analysis.community_report(&csr, &transactions, 3, CommunityRanking::FlaggedDensity));
+    let reciprocal = options.reciprocal
+        .map(|_| fraud_analysis.identify_reciprocal_pairs(&transactions));
+    let balances = options.balances
+        .map(|_| (fraud_analysis.identify_balance_mismatches(&transactions), fraud_analysis.identify_inconsistent_balance_paths(&transactions)));
+    let scores = options.model_path.as_deref()
+        .map(|path| LogisticModel::load(Path::new(path)))
+        .transpose()?
+        .map(|model| fraud_analysis.score_accounts(&csr, &transactions, &model));
+    drop(transactions);
+    drop(csr);
     
     // identify and print collector accounts (accounts that accumulate funds)
-    fraud_analysis.print_collector_accounts();
+    fraud_analysis.print_collector_accounts(&accounts);
     
     // identify and print money mule accounts (accounts that rapidly forward funds)
-    fraud_analysis.print_money_mule_accounts();
+    fraud_analysis.print_money_mule_accounts(&accounts);
+    
+    // with --communities, print the communities with the most flagged members
+    if let (Some(limit), Some(report)) = (options.communities, &communities) {
+        FraudAnalysis::print_community_report(report, limit, &accounts);
+    }
+    
+    // with --reciprocal, print the pairs that send the most money back and forth
+    if let (Some(limit), Some(pairs)) = (options.reciprocal, &reciprocal) {
+        FraudAnalysis::print_reciprocal_pairs(pairs, limit, &accounts);
+    }
+    
+    // with --model, print the accounts the model scores highest
+    if let Some(scores) = &scores {
+        fraud_analysis.print_account_scores(scores, options.scores, &accounts);
+    }
+    
+    // with --balances, print the transactions and accounts whose balances do not add up
+    if let (Some(limit), Some((mismatches, paths))) = (options.balances, &balances) {
+        FraudAnalysis::print_balance_report(mismatches, paths, limit, &accounts);
+    }
+    
+    // with analyst lists or a state file, print the deduplicated review as well
+    let wants_review = options.allowlist_path.is_some() || options.denylist_path.is_some() || options.state_path.is_some();
+    if wants_review {
+        run_review(&mut fraud_analysis, &accounts, options)?;
+    }
     
     drop(analysis_timer);
     
     println!("\nAnalysis complete.");
+    Ok(())
 }
@@ -0,0 +1,414 @@
+// supervised fraud scoring with an in-crate logistic regression.
+// every account is described by a fixed feature vector (its AccountMetrics plus a few graph
+// features) and labeled fraudulent if it sent or received an `is_fraud` transaction. the model
+// is trained with full-batch gradient descent on standardized features, optionally weighting
+// the rare fraud class up, and is saved as a small text file that scores later graphs.
+//
+// to keep the future out of the past, training and evaluation graphs are cut by step:
+// TransactionGraph::split_at_step puts everything up to a step in one graph and the rest in
+// another, and features and labels of each side only see that side's transactions.
+//
+// model file layout (one record per line, values separated by spaces):
+//   fraud-model 1
+//   features <feature names>
+//   mean <per-feature mean>
+//   scale <per-feature standard deviation>
+//   weights <per-feature weight>
+//   bias <bias>
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use crate::classify::AccountClass;
+use crate::error::FraudError;
+use crate::csr::CsrGraph;
+use crate::graph::{AccountMetrics, TransactionGraph, TransactionRecord};
+use crate::intern::AccountId;
+use crate::subgraph::TransactionFilter;
+
+// names of the model features, in feature vector order
+pub const FEATURE_NAMES: [&str; 9] = [
+    "log_incoming_count",
+    "log_outgoing_count",
+    "log_incoming_volume",
+    "log_outgoing_volume",
+    "retention_rate",
+    "reciprocated_share",
+    "log_mean_neighbor_degree",
+    "log_transactions",
+    "is_merchant",
+];
+
+// first line of a model file
+const MODEL_HEADER: &str = "fraud-model 1";
+
+// the feature vectors and labels of every account in a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureMatrix {
+    // the accounts, sorted by handle
+    pub accounts: Vec<AccountId>,
+    // one feature vector per account, in FEATURE_NAMES order
+    pub rows: Vec<Vec<f64>>,
+    // true if the account sent or received a transaction labeled as fraud
+    pub labels: Vec<bool>,
+}
+
+impl FeatureMatrix {
+    // computes the features and labels of every account in a graph. this freezes the graph into
+    // csr form first, so callers that already hold a csr should use from_csr.
+    // takes in `graph` - the graph to describe
+    // returns one row per account, sorted by account handle
+    pub fn from_graph(graph: &TransactionGraph) -> Self {
+        Self::from_csr(&graph.to_csr(), &graph.transactions, |account| graph.account_class(account))
+    }
+
+    // computes the features and labels of every account of a frozen graph.
+    // takes in `csr` - the frozen graph, `transactions` - the transactions it was built from,
+    // and `class` - the class of an account
+    // returns one row per account, sorted by account handle
+    pub fn from_csr<C: Fn(AccountId) -> AccountClass>(csr: &CsrGraph<AccountId, f64>, transactions: &[TransactionRecord], class: C) -> Self {
+        let metrics = csr.account_metrics();
+        Self::from_parts(csr, transactions, |pos| &metrics[pos], class)
+    }
+
+    // computes the features and labels of every account of a frozen graph.
+    // training and scoring both go through here, so a model always sees the same features.
+    // takes in `csr` - the frozen graph, `transactions` - the transactions it was built from,
+    // `metrics` - the metrics of the account at a csr position, and `class` - the class of an account
+    // returns one row per account, sorted by account handle
+    pub(crate) fn from_parts<'m, M, C>(csr: &CsrGraph<AccountId, f64>, transactions: &[TransactionRecord], metrics: M, class: C) -> Self
+    where
+        M: Fn(usize) -> &'m AccountMetrics,
+        C: Fn(AccountId) -> AccountClass,
+    {
+        let mut counts: HashMap<AccountId, usize> = HashMap::new();
+        for record in transactions {
+            *counts.entry(record.name_orig).or_default() += 1;
+            *counts.entry(record.name_dest).or_default() += 1;
+        }
+        let fraud = fraud_accounts(transactions);
+
+        let mut rows: Vec<(AccountId, Vec<f64>, bool)> = (0..csr.node_count())
+            .map(|pos| {
+                let account = *csr.node(pos);
+                let m = metrics(pos);
+
+                // a neighbor in both lists is reciprocated
+                let mut neighbors: Vec<u32> = csr.out_neighbors(pos).iter().chain(csr.in_neighbors(pos)).copied().collect();
+                neighbors.sort_unstable();
+                let listed = neighbors.len();
+                neighbors.dedup();
+                let reciprocated = listed - neighbors.len();
+                let degrees: usize = neighbors.iter().map(|n| csr.out_degree(*n as usize) + csr.in_degree(*n as usize)).sum();
+
+                let count = counts.get(&account).copied().unwrap_or_default();
+                let row = vec![
+                    (m.incoming_count as f64).ln_1p(),
+                    (m.outgoing_count as f64).ln_1p(),
+                    m.incoming_volume.max(0.0).ln_1p(),
+                    m.outgoing_volume.max(0.0).ln_1p(),
+                    m.retention_rate.clamp(-1.0, 1.0),
+                    if neighbors.is_empty() { 0.0 } else { reciprocated as f64 / neighbors.len() as f64 },
+                    if neighbors.is_empty() { 0.0 } else { (degrees as f64 / neighbors.len() as f64).ln_1p() },
+                    (count as f64).ln_1p(),
+                    if class(account) == AccountClass::Merchant { 1.0 } else { 0.0 },
+                ];
+                (account, row, fraud.contains(&account))
+            })
+            .collect();
+        rows.sort_unstable_by_key(|(account, _, _)| *account);
+
+        FeatureMatrix {
+            accounts: rows.iter().map(|(account, _, _)| *account).collect(),
+            labels: rows.iter().map(|(_, _, label)| *label).collect(),
+            rows: rows.into_iter().map(|(_, row, _)| row).collect(),
+        }
+    }
+
+    // get the number of accounts
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    // checks whether the matrix has no accounts
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+impl TransactionGraph {
+    // splits the graph in time: transactions up to and including `step` go in the first graph
+    // and later ones in the second, so a model trained on the first never sees the second.
+    // returns (earlier, later)
+    pub fn split_at_step(&self, step: u32) -> (TransactionGraph, TransactionGraph) {
+        let earlier = self.filter(&TransactionFilter { steps: Some(0..=step), ..TransactionFilter::new() });
+        let later = match step.checked_add(1) {
+            Some(next) => self.filter(&TransactionFilter { steps: Some(next..=u32::MAX), ..TransactionFilter::new() }),
+            None => TransactionGraph::with_classifier(self.classifier().clone()),
+        };
+        (earlier, later)
+    }
+}
+
+// settings of logistic regression training
+#[derive(Debug, Clone)]
+pub struct TrainingOptions {
+    // number of full passes of gradient descent
+    pub epochs: usize,
+    // gradient descent step size
+    pub learning_rate: f64,
+    // l2 regularization strength on the weights
+    pub l2: f64,
+    // weight both classes equally in the loss, so rare fraud labels are not ignored
+    pub balance_classes: bool,
+}
+
+impl Default for TrainingOptions {
+    fn default() -> Self {
+        TrainingOptions {
+            epochs: 300,
+            learning_rate: 0.5,
+            l2: 1e-4,
+            balance_classes: true,
+        }
+    }
+}
+
+// a trained logistic regression model over FEATURE_NAMES
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogisticModel {
+    // per-feature mean of the training data, subtracted before scoring
+    pub mean: Vec<f64>,
+    // per-feature standard deviation of the training data (1 for constant features)
+    pub scale: Vec<f64>,
+    // per-feature weight on the standardized features
+    pub weights: Vec<f64>,
+    // the intercept
+    pub bias: f64,
+}
+
+// how well a model separates fraud from non-fraud accounts on held-out data
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelEvaluation {
+    // accounts evaluated
+    pub accounts: usize,
+    // accounts labeled as fraud
+    pub positives: usize,
+    // area under the roc curve (0.5 is chance, 1 is perfect)
+    pub auc: f64,
+    // fraction of accounts scored at or above the threshold that are fraud
+    pub precision: f64,
+    // fraction of fraud accounts scored at or above the threshold
+    pub recall: f64,
+    // harmonic mean of precision and recall
+    pub f1: f64,
+    // the score threshold precision, recall and f1 were measured at
+    pub threshold: f64,
+}
+
+impl LogisticModel {
+    // trains a model on labeled feature vectors.
+    // takes in `data` - the training accounts, and `options` - the training settings
+    // returns the trained model; the result depends only on the data and the options
+    pub fn train(data: &FeatureMatrix, options: &TrainingOptions) -> Self {
+        let features = FEATURE_NAMES.len();
+        let n = data.len().max(1) as f64;
+
+        let mut mean = vec![0.0; features];
+        for row in &data.rows {
+            for (m, x) in mean.iter_mut().zip(row) {
+                *m += x / n;
+            }
+        }
+        let mut scale = vec![0.0; features];
+        for row in &data.rows {
+            for ((s, x), m) in scale.iter_mut().zip(row).zip(&mean) {
+                *s += (x - m) * (x - m) / n;
+            }
+        }
+        for s in &mut scale {
+            *s = if *s > 0.0 { s.sqrt() } else { 1.0 };
+        }
+
+        let standardized: Vec<Vec<f64>> = data.rows.iter()
+            .map(|row| row.iter().zip(&mean).zip(&scale).map(|((x, m), s)| (x - m) / s).collect())
+            .collect();
+
+        // each class gets half of the total weight when balancing
+        let positives = data.labels.iter().filter(|label| **label).count();
+        let negatives = data.len() - positives;
+        let (positive_weight, negative_weight) = if options.balance_classes && positives > 0 && negatives > 0 {
+            (n / (2.0 * positives as f64), n / (2.0 * negatives as f64))
+        } else {
+            (1.0, 1.0)
+        };
+
+        let mut model = LogisticModel { mean, scale, weights: vec![0.0; features], bias: 0.0 };
+        for _ in 0..options.epochs {
+            let mut gradient = vec![0.0; features];
+            let mut bias_gradient = 0.0;
+            for (x, label) in standardized.iter().zip(&data.labels) {
+                let predicted = sigmoid(model.bias + dot(&model.weights, x));
+                let (target, weight) = if *label { (1.0, positive_weight) } else { (0.0, negative_weight) };
+                let error = weight * (predicted - target);
+                for (g, xi) in gradient.iter_mut().zip(x) {
+                    *g += error * xi;
+                }
+                bias_gradient += error;
+            }
+            for (w, g) in model.weights.iter_mut().zip(&gradient) {
+                *w -= options.learning_rate * (g / n + options.l2 * *w);
+            }
+            model.bias -= options.learning_rate * bias_gradient / n;
+        }
+        model
+    }
+
+    // scores one feature vector.
+    // takes in `features` - a row of a FeatureMatrix
+    // returns the predicted probability that the account is fraudulent
+    pub fn predict(&self, features: &[f64]) -> f64 {
+        let z: f64 = features.iter()
+            .zip(&self.mean)
+            .zip(&self.scale)
+            .zip(&self.weights)
+            .map(|(((x, m), s), w)| w * (x - m) / s)
+            .sum();
+        sigmoid(self.bias + z)
+    }
+
+    // scores every account of a feature matrix.
+    // returns (account, probability) pairs, highest first, ties broken by account handle
+    pub fn score(&self, data: &FeatureMatrix) -> Vec<(AccountId, f64)> {
+        let mut scores: Vec<(AccountId, f64)> = data.accounts.iter()
+            .zip(&data.rows)
+            .map(|(account, row)| (*account, self.predict(row)))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores
+    }
+
+    // measures the model on labeled accounts it was not trained on.
+    // takes in `data` - the held-out accounts, and `threshold` - the score at or above which an account counts as flagged
+    // returns the auc and the precision, recall and f1 at the threshold
+    pub fn evaluate(&self, data: &FeatureMatrix, threshold: f64) -> ModelEvaluation {
+        let scores: Vec<f64> = data.rows.iter().map(|row| self.predict(row)).collect();
+        let positives = data.labels.iter().filter(|label| **label).count();
+        let flagged = scores.iter().filter(|score| **score >= threshold).count();
+        let hits = scores.iter().zip(&data.labels).filter(|(score, label)| **score >= threshold && **label).count();
+
+        let precision = if flagged > 0 { hits as f64 / flagged as f64 } else { 0.0 };
+        let recall = if positives > 0 { hits as f64 / positives as f64 } else { 0.0 };
+        ModelEvaluation {
+            accounts: data.len(),
+            positives,
+            auc: roc_auc(&scores, &data.labels),
+            precision,
+            recall,
+            f1: if precision + recall > 0.0 { 2.0 * precision * recall / (precision + recall) } else { 0.0 },
+            threshold,
+        }
+    }
+
+    // writes the model as text.
+    // takes in `path` - the file to write
+    // returns an i/o error if the file cannot be written
+    pub fn save(&self, path: &Path) -> Result<(), FraudError> {
+        let join = |values: &[f64]| values.iter().map(|v| format!("{:?}", v)).collect::<Vec<String>>().join(" ");
+        let text = format!("{}\nfeatures {}\nmean {}\nscale {}\nweights {}\nbias {:?}\n",
+            MODEL_HEADER,
+            FEATURE_NAMES.join(" "),
+            join(&self.mean),
+            join(&self.scale),
+            join(&self.weights),
+            self.bias);
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    // reads a model written by save.
+    // takes in `path` - the model file
+    // returns the model, an i/o error if the file cannot be read, or a model error if it is
+    // malformed or was trained on different features
+    pub fn load(path: &Path) -> Result<Self, FraudError> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        if lines.next() != Some(MODEL_HEADER) {
+            return Err(FraudError::Model(format!("not a model file (expected `{}` on the first line)", MODEL_HEADER)));
+        }
+
+        let mut record = |key: &str| -> Result<Vec<&str>, FraudError> {
+            let line = lines.next().ok_or_else(|| FraudError::Model(format!("missing `{}` line", key)))?;
+            let mut fields = line.split_whitespace();
+            if fields.next() != Some(key) {
+                return Err(FraudError::Model(format!("expected a `{}` line, found `{}`", key, line)));
+            }
+            Ok(fields.collect())
+        };
+        let numbers = |key: &str, fields: Vec<&str>, expected: usize| -> Result<Vec<f64>, FraudError> {
+            if fields.len() != expected {
+                return Err(FraudError::Model(format!("`{}` has {} values, expected {}", key, fields.len(), expected)));
+            }
+            fields.iter()
+                .map(|field| field.parse::<f64>().map_err(|e| FraudError::Model(format!("bad `{}` value `{}`: {}", key, field, e))))
+                .collect()
+        };
+
+        let features = record("features")?;
+        if features != FEATURE_NAMES {
+            return Err(FraudError::Model(format!("the model was trained on different features: {}", features.join(" "))));
+        }
+        let count = FEATURE_NAMES.len();
+        let mean = numbers("mean", record("mean")?, count)?;
+        let scale = numbers("scale", record("scale")?, count)?;
+        let weights = numbers("weights", record("weights")?, count)?;
+        let bias = numbers("bias", record("bias")?, 1)?[0];
+        Ok(LogisticModel { mean, scale, weights, bias })
+    }
+}
+
+// the accounts labeled as fraud: every account that sent or received an `is_fraud` transaction
+pub fn fraud_accounts(transactions: &[TransactionRecord]) -> HashSet<AccountId> {
+    transactions.iter()
+        .filter(|record| record.is_fraud == 1)
+        .flat_map(|record| [record.name_orig, record.name_dest])
+        .collect()
+}
+
+// the area under the roc curve, as the probability that a random positive scores above a
+// random negative (ties count half).
+// takes in `scores` and `labels` - one score and label per example
+// returns the auc, or 0.5 if there are no positives or no negatives
+pub fn roc_auc(scores: &[f64], labels: &[bool]) -> f64 {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| scores[*a].total_cmp(&scores[*b]));
+
+    // average ranks over groups of tied scores
+    let mut positive_rank_sum = 0.0;
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end < order.len() && scores[order[end]] == scores[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        positive_rank_sum += rank * order[start..end].iter().filter(|i| labels[**i]).count() as f64;
+        start = end;
+    }
+
+    let positives = labels.iter().filter(|label| **label).count() as f64;
+    let negatives = labels.len() as f64 - positives;
+    if positives == 0.0 || negatives == 0.0 {
+        return 0.5;
+    }
+    (positive_rank_sum - positives * (positives + 1.0) / 2.0) / (positives * negatives)
+}
+
+// the logistic function
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+// the dot product of two vectors
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
@@ -0,0 +1,310 @@
+// path queries between two accounts.
+// answers "are these two accounts connected, and how?" three ways: the path with the fewest
+// hops (breadth first search), the path with the lowest total cost (dijkstra) and the path
+// whose smallest transfer is largest (widest / maximum bottleneck path).
+//
+// an edge may stand for several transfers (every transaction between two accounts), and each
+// search treats them as parallel edges: the caller lists the transfers an edge may be taken as,
+// and the path records the transfer used on every hop, so its step and amount always belong
+// together. every search can also restrict which transfer may follow which, e.g. to require
+// that the steps along the path never decrease so the money could actually have flowed that way.
+// to keep such restrictions exact, the searches run over (node, label) states instead of nodes:
+// the label carries whatever the restriction needs to know about how the node was reached, such
+// as the step the money arrived at, and every transfer taken computes the label of the next state.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
+use crate::graph::{DiGraph, NodeIndex, TransactionGraph};
+use crate::intern::AccountId;
+
+// an edge as (source, target)
+type EdgeKey = (NodeIndex, NodeIndex);
+
+// the parent links of a search: every reached state, the state it was reached from and the
+// transfer taken
+type Parents<T, X> = HashMap<(NodeIndex, T), ((NodeIndex, T), X)>;
+
+// a path found by a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatePath<T, X> {
+    // the (node, label) states from source to target
+    pub states: Vec<(NodeIndex, T)>,
+    // the transfer taken on every hop, so transfers[i] leads from states[i] to states[i + 1]
+    pub transfers: Vec<X>,
+}
+
+// an entry of the priority queue used by dijkstra and the widest path search.
+// the entry with the greatest `priority` is popped first; ties go to fewer hops, then lower indices and labels
+struct QueueEntry<T> {
+    priority: f64,
+    hops: usize,
+    state: (NodeIndex, T),
+}
+
+impl<T: Ord> PartialEq for QueueEntry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for QueueEntry<T> {}
+
+impl<T: Ord> PartialOrd for QueueEntry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for QueueEntry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.total_cmp(&other.priority).then_with(|| {
+            (other.hops, other.state.0.0, &other.state.1).cmp(&(self.hops, self.state.0.0, &self.state.1))
+        })
+    }
+}
+
+impl<N, W> DiGraph<N, W>
+where
+    N: Clone,
+    W: Clone,
+{
+    // the outgoing neighbors of a node in increasing index order, so searches are deterministic
+    fn sorted_neighbors(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut neighbors: Vec<NodeIndex> = self.neighbors(node).collect();
+        neighbors.sort_unstable_by_key(|n| n.0);
+        neighbors
+    }
+
+    // rebuilds the path ending in `state` from the parent links of a search
+    fn trace<T: Copy + Eq + Hash, X: Clone>(parents: &Parents<T, X>, mut state: (NodeIndex, T)) -> StatePath<T, X> {
+        let mut states = vec![state];
+        let mut transfers = Vec::new();
+        while let Some((parent, transfer)) = parents.get(&state) {
+            states.push(*parent);
+            transfers.push(transfer.clone());
+            state = *parent;
+        }
+        states.reverse();
+        transfers.reverse();
+        StatePath { states, transfers }
+    }
+
+    // finds a path with the fewest edges using breadth first search.
+    // takes in `source` and `target` - the end nodes, `start` - the label of the source, and
+    // `advance` - called with the label of the current state and the next edge; returns every
+    // transfer the edge may be taken as from that state, each with the label after taking it
+    // (nothing if the edge may not be taken at all)
+    // returns the path from source to target, or none if there is no allowed path
+    pub fn shortest_hop_path<T, X, A, I>(&self, source: NodeIndex, target: NodeIndex, start: T, advance: A) -> Option<StatePath<T, X>>
+    where
+        T: Copy + Eq + Hash,
+        X: Clone,
+        A: Fn(T, EdgeKey) -> I,
+        I: IntoIterator<Item = (T, X)>,
+    {
+        if !self.contains_node(source) || !self.contains_node(target) {
+            return None;
+        }
+        let start = (source, start);
+        if source == target {
+            return Some(StatePath { states: vec![start], transfers: Vec::new() });
+        }
+
+        let mut parents: Parents<T, X> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(state) = queue.pop_front() {
+            for next in self.sorted_neighbors(state.0) {
+                for (label, transfer) in advance(state.1, (state.0, next)) {
+                    let next_state = (next, label);
+                    if next_state == start || parents.contains_key(&next_state) {
+                        continue;
+                    }
+                    parents.insert(next_state, (state, transfer));
+                    if next == target {
+                        return Some(Self::trace(&parents, next_state));
+                    }
+                    queue.push_back(next_state);
+                }
+            }
+        }
+        None
+    }
+
+    // finds the path with the lowest total transfer cost using dijkstra's algorithm.
+    // takes in `source` and `target` - the end nodes, `cost` - the cost of a transfer (must not be
+    // negative), and `start` and `advance` - as for shortest_hop_path
+    // returns the total cost and the path, or none if there is no allowed path
+    pub fn dijkstra_path<T, X, C, A, I>(&self, source: NodeIndex, target: NodeIndex, cost: C, start: T, advance: A) -> Option<(f64, StatePath<T, X>)>
+    where
+        T: Copy + Eq + Hash + Ord,
+        X: Clone,
+        C: Fn(&X) -> f64,
+        A: Fn(T, EdgeKey) -> I,
+        I: IntoIterator<Item = (T, X)>,
+    {
+        // the queue pops the greatest priority, so costs are stored negated
+        self.best_first(source, target, 0.0, |total, transfer| total - cost(transfer), start, advance)
+            .map(|(total, path)| (0.0 - total, path))
+    }
+
+    // finds the path whose smallest transfer capacity is as large as possible.
+    // takes in `source` and `target` - the end nodes, `capacity` - the capacity of a transfer,
+    // and `start` and `advance` - as for shortest_hop_path
+    // returns the bottleneck capacity and the path, or none if there is no allowed path.
+    // a path from a node to itself has no edges and an infinite bottleneck
+    pub fn widest_path<T, X, C, A, I>(&self, source: NodeIndex, target: NodeIndex, capacity: C, start: T, advance: A) -> Option<(f64, StatePath<T, X>)>
+    where
+        T: Copy + Eq + Hash + Ord,
+        X: Clone,
+        C: Fn(&X) -> f64,
+        A: Fn(T, EdgeKey) -> I,
+        I: IntoIterator<Item = (T, X)>,
+    {
+        self.best_first(source, target, f64::INFINITY, |bottleneck, transfer| bottleneck.min(capacity(transfer)), start, advance)
+    }
+
+    // label-setting search shared by dijkstra and the widest path: the state with the greatest
+    // priority is settled first, and `extend` must never increase the priority along a path
+    fn best_first<T, X, E, A, I>(&self, source: NodeIndex, target: NodeIndex, initial: f64, extend: E, start: T, advance: A) -> Option<(f64, StatePath<T, X>)>
+    where
+        T: Copy + Eq + Hash + Ord,
+        X: Clone,
+        E: Fn(f64, &X) -> f64,
+        A: Fn(T, EdgeKey) -> I,
+        I: IntoIterator<Item = (T, X)>,
+    {
+        if !self.contains_node(source) || !self.contains_node(target) {
+            return None;
+        }
+        let start = (source, start);
+        if source == target {
+            return Some((initial, StatePath { states: vec![start], transfers: Vec::new() }));
+        }
+
+        let mut best: HashMap<(NodeIndex, T), f64> = HashMap::from([(start, initial)]);
+        let mut parents: Parents<T, X> = HashMap::new();
+        let mut queue = BinaryHeap::from([QueueEntry { priority: initial, hops: 0, state: start }]);
+
+        while let Some(QueueEntry { priority, hops, state }) = queue.pop() {
+            if best.get(&state).is_some_and(|b| *b > priority) {
+                continue;
+            }
+            if state.0 == target {
+                return Some((priority, Self::trace(&parents, state)));
+            }
+
+            for next in self.sorted_neighbors(state.0) {
+                for (label, transfer) in advance(state.1, (state.0, next)) {
+                    let next_state = (next, label);
+                    let candidate = extend(priority, &transfer);
+                    if next_state != start && best.get(&next_state).is_none_or(|b| candidate > *b) {
+                        best.insert(next_state, candidate);
+                        parents.insert(next_state, (state, transfer));
+                        queue.push(QueueEntry { priority: candidate, hops: hops + 1, state: next_state });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// settings for TransactionGraph::connection_with
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    // require the steps along every path to never decrease, so money could have flowed along it
+    pub time_ordered: bool,
+    // the cost of a transfer for the lowest-cost path, given its amount. the default,
+    // 1 / amount, makes paths through large transfers cheap
+    pub cost: fn(f64) -> f64,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            time_ordered: false,
+            cost: |amount| 1.0 / amount.max(f64::MIN_POSITIVE),
+        }
+    }
+}
+
+// a path between two accounts
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountPath {
+    // the accounts along the path, from the first account to the second
+    pub accounts: Vec<AccountId>,
+    // the amount of the transfer the path uses on every hop
+    pub amounts: Vec<f64>,
+    // the step of the transfer the path uses on every hop
+    pub steps: Vec<u32>,
+    // hop count, total cost or bottleneck amount, depending on the search
+    pub value: f64,
+}
+
+// how two accounts are connected
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    // the path with the fewest transfers
+    pub fewest_hops: AccountPath,
+    // the path with the lowest total cost
+    pub cheapest: AccountPath,
+    // the path whose smallest transfer is largest
+    pub widest: AccountPath,
+}
+
+impl TransactionGraph {
+    // finds how money could have moved from one account to another, with default options.
+    // takes in `from` and `to` - the two accounts
+    // returns the fewest-hop, cheapest and widest paths, or none if `to` cannot be reached
+    pub fn connection(&self, from: AccountId, to: AccountId) -> Option<Connection> {
+        self.connection_with(from, to, &ConnectionOptions::default())
+    }
+
+    // finds how money could have moved from one account to another.
+    // every transaction between two accounts is a separate transfer the path may use, and the
+    // costs and bottlenecks are those of the transfers used. with time ordering, each hop may
+    // only use a transfer at or after the step the money arrived at.
+    // takes in `from` and `to` - the two accounts, and `options` - time ordering and path cost
+    // returns the fewest-hop, cheapest and widest paths, or none if `to` cannot be reached
+    pub fn connection_with(&self, from: AccountId, to: AccountId, options: &ConnectionOptions) -> Option<Connection> {
+        let source = *self.node_map.get(&from)?;
+        let target = *self.node_map.get(&to)?;
+
+        // the (step, amount) of every transfer along each edge, sorted by step
+        let mut transfers: HashMap<EdgeKey, Vec<(u32, f64)>> = HashMap::new();
+        for record in &self.transactions {
+            if let (Some(orig), Some(dest)) = (self.node_map.get(&record.name_orig), self.node_map.get(&record.name_dest)) {
+                transfers.entry((*orig, *dest)).or_default().push((record.step, record.amount));
+            }
+        }
+        for list in transfers.values_mut() {
+            list.sort_by_key(|(step, _)| *step);
+        }
+
+        // the label is the step the money arrived at; without time ordering it is always 0 and
+        // every transfer on the edge may be taken
+        let advance = |arrived: u32, edge: EdgeKey| {
+            let list = transfers.get(&edge).map_or(&[][..], |list| list.as_slice());
+            let first = if options.time_ordered { list.partition_point(|(step, _)| *step < arrived) } else { 0 };
+            list[first..].iter().map(|transfer| (if options.time_ordered { transfer.0 } else { 0 }, *transfer))
+        };
+
+        let to_path = |path: StatePath<u32, (u32, f64)>, value: f64| AccountPath {
+            accounts: path.states.iter().map(|(idx, _)| self.graph[*idx]).collect(),
+            amounts: path.transfers.iter().map(|(_, amount)| *amount).collect(),
+            steps: path.transfers.iter().map(|(step, _)| *step).collect(),
+            value,
+        };
+
+        let hops = self.graph.shortest_hop_path(source, target, 0, advance)?;
+        let (cost, cheapest) = self.graph.dijkstra_path(source, target, |(_, amount)| (options.cost)(*amount), 0, advance)?;
+        let (bottleneck, widest) = self.graph.widest_path(source, target, |(_, amount)| *amount, 0, advance)?;
+        let hop_count = hops.transfers.len() as f64;
+
+        Some(Connection {
+            fewest_hops: to_path(hops, hop_count),
+            cheapest: to_path(cheapest, cost),
+            widest: to_path(widest, bottleneck),
+        })
+    }
+}
@@ -0,0 +1,116 @@
+// back-and-forth transfers between pairs of accounts.
+// two accounts that keep sending money to each other (A -> B, then B -> A, then A -> B ...)
+// inflate their transaction volume without moving money anywhere, a common way to make
+// accounts look active or to layer funds. the graph keeps only one edge per direction, so the
+// pass works on the stored transactions, which keep every transfer and its step.
+use std::collections::HashMap;
+use crate::graph::{TransactionGraph, TransactionRecord};
+use crate::intern::AccountId;
+
+// an account pair with transfers in both directions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReciprocalPair {
+    // the account with the smaller handle
+    pub a: AccountId,
+    // the account with the larger handle
+    pub b: AccountId,
+    // number of transfers from a to b
+    pub a_to_b: usize,
+    // number of transfers from b to a
+    pub b_to_a: usize,
+    // total amount sent from a to b
+    pub volume_a_to_b: f64,
+    // total amount sent from b to a
+    pub volume_b_to_a: f64,
+    // the smaller volume divided by the larger one: 1 when both directions carry the same amount
+    pub amount_symmetry: f64,
+    // how often the direction flips when the pair's transfers are ordered by step
+    // (A -> B, B -> A, A -> B is two reversals)
+    pub reversals: usize,
+    // the smallest step gap between a transfer and the reply that reversed it
+    pub min_gap: u32,
+    // the mean step gap between a transfer and the reply that reversed it
+    pub mean_gap: f64,
+}
+
+impl ReciprocalPair {
+    // the total amount moved between the two accounts in both directions
+    pub fn volume(&self) -> f64 {
+        self.volume_a_to_b + self.volume_b_to_a
+    }
+}
+
+// one transfer within a pair
+struct Transfer {
+    step: u32,
+    amount: f64,
+    // true if sent from a to b
+    forward: bool,
+}
+
+impl TransactionGraph {
+    // finds every account pair with at least one transfer in each direction (see reciprocal_pairs).
+    // returns the pairs sorted by (a, b)
+    pub fn reciprocal_pairs(&self) -> Vec<ReciprocalPair> {
+        reciprocal_pairs(&self.transactions)
+    }
+}
+
+// finds every account pair with at least one transfer in each direction.
+// a pair's transfers are ordered by step (ties keep the order they were added in) to count
+// reversals and measure how quickly each reply followed. self-transfers are ignored.
+// takes in `transactions` - the transactions to search
+// returns the pairs sorted by (a, b)
+pub fn reciprocal_pairs(transactions: &[TransactionRecord]) -> Vec<ReciprocalPair> {
+    let mut transfers: HashMap<(AccountId, AccountId), Vec<Transfer>> = HashMap::new();
+    for record in transactions {
+        let (orig, dest) = (record.name_orig, record.name_dest);
+        if orig == dest {
+            continue;
+        }
+        let key = (orig.min(dest), orig.max(dest));
+        transfers.entry(key).or_default().push(Transfer {
+            step: record.step,
+            amount: record.amount,
+            forward: orig < dest,
+        });
+    }
+
+    let mut pairs: Vec<ReciprocalPair> = transfers.into_iter()
+        .filter(|(_, list)| list.iter().any(|t| t.forward) && list.iter().any(|t| !t.forward))
+        .map(|((a, b), mut list)| {
+            list.sort_by_key(|t| t.step);
+            let forward = list.iter().filter(|t| t.forward);
+            let backward = list.iter().filter(|t| !t.forward);
+            let volume_a_to_b: f64 = forward.clone().map(|t| t.amount).sum();
+            let volume_b_to_a: f64 = backward.clone().map(|t| t.amount).sum();
+
+            let gaps: Vec<u32> = list.windows(2)
+                .filter(|pair| pair[0].forward != pair[1].forward)
+                .map(|pair| pair[1].step - pair[0].step)
+                .collect();
+
+            ReciprocalPair {
+                a,
+                b,
+                a_to_b: forward.count(),
+                b_to_a: backward.count(),
+                volume_a_to_b,
+                volume_b_to_a,
+                amount_symmetry: symmetry(volume_a_to_b, volume_b_to_a),
+                reversals: gaps.len(),
+                min_gap: gaps.iter().copied().min().unwrap_or(0),
+                mean_gap: gaps.iter().map(|g| *g as f64).sum::<f64>() / gaps.len() as f64,
+            }
+        })
+        .collect();
+
+    pairs.sort_unstable_by_key(|pair| (pair.a, pair.b));
+    pairs
+}
+
+// the smaller of two volumes divided by the larger, or 1 if both are zero
+fn symmetry(x: f64, y: f64) -> f64 {
+    let larger = x.max(y);
+    if larger > 0.0 { x.min(y) / larger } else { 1.0 }
+}
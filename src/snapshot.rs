@@ -0,0 +1,399 @@
+// binary snapshots of a built transaction graph.
+// parsing the cleaned csv takes several seconds on every run. a snapshot stores the finished
+// graph (account classifier, account names, nodes, edges, node map and transactions) in a
+// compact little-endian binary file that loads back with a single read and no text parsing.
+//
+// layout:
+//   header  - magic (8 bytes), format version (u32), source file size (u64),
+//             source modification time in nanoseconds (u64), payload length (u64),
+//             fnv-1a checksum (u64) of every header byte before it followed by the payload
+//   payload - classifier, accounts, nodes, edges, node map, transactions (see write_payload)
+//
+// a snapshot is rejected if the magic, version, length or checksum do not match, or if the
+// source csv it was built from has changed since it was written.
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use crate::classify::AccountClassifier;
+use crate::error::FraudError;
+use crate::graph::{Balances, NodeIndex, TransactionGraph, TransactionRecord};
+use crate::intern::AccountId;
+use crate::utilities::read_transaction_dataset;
+
+// identifies a file as a transaction graph snapshot
+const MAGIC: &[u8; 8] = b"TXGRAPH\0";
+// bumped whenever the layout changes; older snapshots are rejected
+pub const SNAPSHOT_VERSION: u32 = 5;
+// size of the fixed header in bytes
+const HEADER_LEN: usize = 8 + 4 + 8 + 8 + 8 + 8;
+// size of the header bytes covered by the checksum (everything but the checksum itself)
+const CHECKED_HEADER_LEN: usize = HEADER_LEN - 8;
+
+// identifies the version of the source csv a snapshot was built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SourceStamp {
+    // file size in bytes
+    size: u64,
+    // modification time in nanoseconds since the unix epoch, so a rewrite of the same size
+    // within the same second is still noticed
+    modified: u64,
+}
+
+impl SourceStamp {
+    // reads the stamp of a source file, or the zero stamp when there is no source
+    fn of(source: Option<&Path>) -> Result<Self, FraudError> {
+        let Some(path) = source else {
+            return Ok(SourceStamp { size: 0, modified: 0 });
+        };
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Ok(SourceStamp { size: metadata.len(), modified })
+    }
+}
+
+// computes the 64-bit fnv-1a hash of byte slices read one after the other
+fn checksum(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// saves a transaction graph to a snapshot file.
+// takes in `graph` - the graph to save, `path` - where to write the snapshot,
+// and `source` - the csv the graph was built from, used to detect stale snapshots later
+// returns an error if the source cannot be inspected or the file cannot be written
+pub fn save_snapshot(graph: &TransactionGraph, path: &Path, source: Option<&Path>) -> Result<(), FraudError> {
+    let stamp = SourceStamp::of(source)?;
+    let mut payload = Vec::new();
+    write_payload(graph, &mut payload);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    header.extend_from_slice(&stamp.size.to_le_bytes());
+    header.extend_from_slice(&stamp.modified.to_le_bytes());
+    header.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    header.extend_from_slice(&checksum(&[&header, &payload]).to_le_bytes());
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&header)?;
+    out.write_all(&payload)?;
+    out.flush()?;
+    Ok(())
+}
+
+// loads a transaction graph from a snapshot file.
+// takes in `path` - the snapshot to read, and `source` - the csv it must have been built from
+// returns a snapshot error if the file is not a valid snapshot of this version,
+// is corrupt, or is older than the current contents of `source`
+pub fn load_snapshot(path: &Path, source: Option<&Path>) -> Result<TransactionGraph, FraudError> {
+    let bytes = fs::read(path)?;
+    if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+        return Err(FraudError::Snapshot(format!("{} is not a graph snapshot", path.display())));
+    }
+
+    let mut header = Reader::new(&bytes[8..HEADER_LEN]);
+    let version = header.u32()?;
+    if version != SNAPSHOT_VERSION {
+        return Err(FraudError::Snapshot(format!(
+            "snapshot version {} is not supported (expected {})", version, SNAPSHOT_VERSION)));
+    }
+    let stamp = SourceStamp { size: header.u64()?, modified: header.u64()? };
+    let payload_len = header.u64()? as usize;
+    let expected_checksum = header.u64()?;
+
+    if stamp != SourceStamp::of(source)? {
+        return Err(FraudError::Snapshot("snapshot is stale: the source file has changed".to_string()));
+    }
+
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() != payload_len {
+        return Err(FraudError::Snapshot(format!(
+            "snapshot is truncated: expected {} payload bytes, found {}", payload_len, payload.len())));
+    }
+    if checksum(&[&bytes[..CHECKED_HEADER_LEN], payload]) != expected_checksum {
+        return Err(FraudError::Snapshot("snapshot checksum does not match".to_string()));
+    }
+
+    read_payload(&mut Reader::new(payload))
+}
+
+// loads the graph for a csv file, going through a snapshot when possible.
+// a valid snapshot is loaded directly; otherwise the csv is parsed and a fresh snapshot written.
+// a snapshot saved with a different classifier is loaded and its accounts classified again.
+// takes in `csv_path` - the cleaned dataset, `snapshot_path` - where the snapshot lives,
+// and `classifier` - decides the class of each account from its id
+// returns the graph, or an error if the csv cannot be loaded or the snapshot cannot be written
+pub fn load_or_build(csv_path: &Path, snapshot_path: &Path, classifier: &AccountClassifier) -> Result<TransactionGraph, FraudError> {
+    match load_snapshot(snapshot_path, Some(csv_path)) {
+        Ok(mut graph) => {
+            if graph.classifier() != classifier {
+                graph.set_classifier(classifier.clone());
+            }
+            return Ok(graph);
+        }
+        Err(FraudError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => eprintln!("Warning: rebuilding snapshot: {}", e),
+    }
+
+    let mut graph = read_transaction_dataset(&csv_path.to_string_lossy())?;
+    if graph.classifier() != classifier {
+        graph.set_classifier(classifier.clone());
+    }
+    save_snapshot(&graph, snapshot_path, Some(csv_path))?;
+    Ok(graph)
+}
+
+// serializes everything but the header.
+// payload layout (counts are u64, account handles u32, node indices u64):
+//   classifier   - customer prefix count, prefixes, merchant prefix count, prefixes
+//                  (each as length u32 and bytes), digits only u8, then the customer
+//                  and merchant patterns laid out like the prefixes
+//   accounts     - count, then (name length u32, name bytes) in handle order
+//   nodes        - next node index, count, then (node index, account handle)
+//   edges        - count, then (source index, target index, weight f64)
+//   node map     - count, then (account handle, node index)
+//   transactions - count, then (step u32, type length u32, type bytes, amount f64,
+//                  origin handle, destination handle, is_fraud u8, has balances u8,
+//                  then if it has them the four balances as f64 in paysim column order)
+fn write_payload(graph: &TransactionGraph, out: &mut Vec<u8>) {
+    let classifier = graph.classifier();
+    for prefixes in [&classifier.customer_prefixes, &classifier.merchant_prefixes] {
+        out.extend_from_slice(&(prefixes.len() as u64).to_le_bytes());
+        for prefix in prefixes {
+            write_str(out, prefix);
+        }
+    }
+    out.push(classifier.digits_only as u8);
+    for patterns in [&classifier.customer_patterns, &classifier.merchant_patterns] {
+        out.extend_from_slice(&(patterns.len() as u64).to_le_bytes());
+        for pattern in patterns {
+            write_str(out, pattern);
+        }
+    }
+
+    out.extend_from_slice(&(graph.accounts.len() as u64).to_le_bytes());
+    for (_, name) in graph.accounts.iter() {
+        write_str(out, name);
+    }
+
+    let mut nodes: Vec<NodeIndex> = graph.graph.node_indices().collect();
+    nodes.sort_unstable_by_key(|idx| idx.0);
+    out.extend_from_slice(&(graph.graph.next_index().0 as u64).to_le_bytes());
+    out.extend_from_slice(&(nodes.len() as u64).to_le_bytes());
+    for idx in nodes {
+        out.extend_from_slice(&(idx.0 as u64).to_le_bytes());
+        out.extend_from_slice(&graph.graph[idx].0.to_le_bytes());
+    }
+
+    out.extend_from_slice(&(graph.graph.edge_count() as u64).to_le_bytes());
+    for edge in graph.graph.edge_references() {
+        out.extend_from_slice(&(edge.source().0 as u64).to_le_bytes());
+        out.extend_from_slice(&(edge.target().0 as u64).to_le_bytes());
+        out.extend_from_slice(&edge.weight().to_le_bytes());
+    }
+
+    out.extend_from_slice(&(graph.node_map.len() as u64).to_le_bytes());
+    for (account, idx) in &graph.node_map {
+        out.extend_from_slice(&account.0.to_le_bytes());
+        out.extend_from_slice(&(idx.0 as u64).to_le_bytes());
+    }
+
+    out.extend_from_slice(&(graph.transactions.len() as u64).to_le_bytes());
+    for record in &graph.transactions {
+        out.extend_from_slice(&record.step.to_le_bytes());
+        write_str(out, &record.r#type);
+        out.extend_from_slice(&record.amount.to_le_bytes());
+        out.extend_from_slice(&record.name_orig.0.to_le_bytes());
+        out.extend_from_slice(&record.name_dest.0.to_le_bytes());
+        out.push(record.is_fraud);
+        match record.balances {
+            Some(balances) => {
+                out.push(1);
+                for value in [balances.old_orig, balances.new_orig, balances.old_dest, balances.new_dest] {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            None => out.push(0),
+        }
+    }
+}
+
+// rebuilds a graph from a payload written by write_payload
+fn read_payload(input: &mut Reader) -> Result<TransactionGraph, FraudError> {
+    let [customer_prefixes, merchant_prefixes] = read_str_lists(input)?;
+    let digits_only = match input.u8()? {
+        0 => false,
+        1 => true,
+        flag => return Err(FraudError::Snapshot(format!("invalid digits only flag {}", flag))),
+    };
+    let [customer_patterns, merchant_patterns] = read_str_lists(input)?;
+    let mut graph = TransactionGraph::with_classifier(AccountClassifier {
+        customer_prefixes,
+        merchant_prefixes,
+        digits_only,
+        customer_patterns,
+        merchant_patterns,
+    });
+
+    let account_count = input.u64()?;
+    for _ in 0..account_count {
+        let name = input.str()?;
+        graph.accounts.intern(name);
+    }
+
+    let next_index = NodeIndex(input.u64()? as usize);
+    let node_count = input.u64()?;
+    for _ in 0..node_count {
+        let idx = NodeIndex(input.u64()? as usize);
+        let account = input.account(account_count)?;
+        graph.graph.insert_node_at(idx, account);
+    }
+    graph.graph.set_next_index(next_index);
+
+    let edge_count = input.u64()?;
+    for _ in 0..edge_count {
+        let source = input.node(&graph)?;
+        let target = input.node(&graph)?;
+        let weight = input.f64()?;
+        graph.graph.add_edge(source, target, weight);
+    }
+
+    let map_count = input.u64()?;
+    for _ in 0..map_count {
+        let account = input.account(account_count)?;
+        let idx = input.node(&graph)?;
+        graph.node_map.insert(account, idx);
+    }
+
+    let transaction_count = input.u64()?;
+    graph.transactions.reserve(transaction_count as usize);
+    for _ in 0..transaction_count {
+        graph.transactions.push(TransactionRecord {
+            step: input.u32()?,
+            r#type: input.str()?.to_string(),
+            amount: input.f64()?,
+            name_orig: input.account(account_count)?,
+            name_dest: input.account(account_count)?,
+            is_fraud: input.u8()?,
+            balances: match input.u8()? {
+                0 => None,
+                1 => Some(Balances {
+                    old_orig: input.f64()?,
+                    new_orig: input.f64()?,
+                    old_dest: input.f64()?,
+                    new_dest: input.f64()?,
+                }),
+                flag => return Err(FraudError::Snapshot(format!("invalid balance flag {}", flag))),
+            },
+        });
+    }
+
+    if !input.is_empty() {
+        return Err(FraudError::Snapshot("unexpected data after the last transaction".to_string()));
+    }
+
+    // a well-formed payload can still describe an inconsistent graph, such as an account handle
+    // outside the string table, so it is checked before any handle is resolved
+    graph.validate_structure().map_err(|e| FraudError::Snapshot(e.to_string()))?;
+
+    // classes are derived from the account ids and the saved classifier, so they are recomputed instead of stored
+    graph.reclassify();
+    Ok(graph)
+}
+
+// reads two lists of strings, each as a count followed by the strings
+fn read_str_lists(input: &mut Reader) -> Result<[Vec<String>; 2], FraudError> {
+    let mut lists = [Vec::new(), Vec::new()];
+    for list in &mut lists {
+        let count = input.u64()?;
+        for _ in 0..count {
+            list.push(input.str()?.to_string());
+        }
+    }
+    Ok(lists)
+}
+
+// writes a length-prefixed utf-8 string
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+// a cursor over snapshot bytes that turns running out of input into a snapshot error
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FraudError> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| FraudError::Snapshot("snapshot ends unexpectedly".to_string()))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const LEN: usize>(&mut self) -> Result<[u8; LEN], FraudError> {
+        let mut array = [0u8; LEN];
+        array.copy_from_slice(self.take(LEN)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, FraudError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, FraudError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, FraudError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, FraudError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn str(&mut self) -> Result<&'a str, FraudError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?)
+            .map_err(|_| FraudError::Snapshot("snapshot contains invalid utf-8".to_string()))
+    }
+
+    // reads an account handle and checks it refers to a saved account
+    fn account(&mut self, account_count: u64) -> Result<AccountId, FraudError> {
+        let id = self.u32()?;
+        if id as u64 >= account_count {
+            return Err(FraudError::Snapshot(format!("account handle {} is out of range", id)));
+        }
+        Ok(AccountId(id))
+    }
+
+    // reads a node index and checks it refers to a saved node
+    fn node(&mut self, graph: &TransactionGraph) -> Result<NodeIndex, FraudError> {
+        let idx = NodeIndex(self.u64()? as usize);
+        if !graph.graph.contains_node(idx) {
+            return Err(FraudError::Snapshot(format!("node index {} is not in the snapshot", idx.0)));
+        }
+        Ok(idx)
+    }
+}
@@ -0,0 +1,334 @@
+// network-level summary statistics of a transaction graph.
+// goes beyond "loaded N transactions, M unique accounts": degree distributions with a
+// power-law fit, reciprocity, density, self-loops, amount quantiles, the fraud rate of every
+// transaction type and the size of the largest connected component.
+// the distribution statistics are computed with statrs; the report is written as json by hand,
+// like the csv loader, so no serialization library is needed.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use statrs::distribution::{ContinuousCDF, Pareto};
+use statrs::statistics::{Data, OrderStatistics, Statistics};
+use crate::graph::{NodeIndex, TransactionGraph};
+
+// the amount quantiles included in the report
+pub const AMOUNT_QUANTILES: [f64; 7] = [0.0, 0.25, 0.5, 0.75, 0.9, 0.99, 1.0];
+
+// the smallest tail a power law is fitted to; smaller tails give meaningless exponents
+const MIN_TAIL: usize = 10;
+
+// a power law p(k) ~ k^-alpha fitted to the tail k >= x_min of a degree distribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerLawFit {
+    // the fitted exponent
+    pub alpha: f64,
+    // the smallest degree in the fitted tail
+    pub x_min: usize,
+    // how many accounts have a degree of at least x_min
+    pub tail_size: usize,
+    // kolmogorov-smirnov distance between the tail and the fitted law (smaller is better)
+    pub ks_distance: f64,
+}
+
+// summary of an in- or out-degree distribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct DegreeStats {
+    // mean degree over all accounts
+    pub mean: f64,
+    // standard deviation of the degree
+    pub std_dev: f64,
+    // median degree
+    pub median: f64,
+    // highest degree
+    pub max: usize,
+    // (degree, number of accounts with that degree), by increasing degree
+    pub histogram: Vec<(usize, usize)>,
+    // the power law fitted to the tail, or none if fewer than 10 accounts have a positive degree
+    pub power_law: Option<PowerLawFit>,
+}
+
+// the fraud rate of one transaction type
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeFraudRate {
+    // the transaction type
+    pub r#type: String,
+    // how many transactions have this type
+    pub transactions: usize,
+    // how many of them are labeled as fraud
+    pub fraud: usize,
+    // fraud / transactions
+    pub rate: f64,
+}
+
+// network-level summary statistics of a transaction graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphStats {
+    // number of accounts
+    pub accounts: usize,
+    // number of distinct (origin, destination) edges
+    pub edges: usize,
+    // number of stored transactions
+    pub transactions: usize,
+    // edges / (accounts * (accounts - 1)), the fraction of possible edges present
+    pub density: f64,
+    // the fraction of edges between two different accounts whose reverse edge also exists
+    pub reciprocity: f64,
+    // edges from an account to itself
+    pub self_loops: usize,
+    // the in-degree distribution
+    pub in_degree: DegreeStats,
+    // the out-degree distribution
+    pub out_degree: DegreeStats,
+    // (quantile, amount) for every entry of AMOUNT_QUANTILES
+    pub amount_quantiles: Vec<(f64, f64)>,
+    // the fraud rate of every transaction type, by type name
+    pub fraud_rate_by_type: Vec<TypeFraudRate>,
+    // number of weakly connected components
+    pub components: usize,
+    // accounts in the largest weakly connected component
+    pub largest_component: usize,
+}
+
+impl TransactionGraph {
+    // computes the network-level summary statistics of the graph.
+    // returns the statistics; an empty graph gives zeros and empty distributions
+    pub fn stats(&self) -> GraphStats {
+        let mut nodes: Vec<NodeIndex> = self.graph.node_indices().collect();
+        nodes.sort_unstable_by_key(|idx| idx.0);
+        let account_count = nodes.len();
+        let edge_count = self.graph.edge_count();
+
+        let mut self_loops = 0;
+        let mut reciprocated = 0;
+        for edge in self.graph.edge_references() {
+            if edge.source() == edge.target() {
+                self_loops += 1;
+            } else if self.graph.edge_weight(edge.target(), edge.source()).is_some() {
+                reciprocated += 1;
+            }
+        }
+
+        let in_degrees: Vec<usize> = nodes.iter().map(|idx| self.graph.incoming_neighbors(*idx).count()).collect();
+        let out_degrees: Vec<usize> = nodes.iter().map(|idx| self.graph.neighbors(*idx).count()).collect();
+
+        let mut amounts = Data::new(self.transactions.iter().map(|r| r.amount).collect::<Vec<f64>>());
+        let amount_quantiles = if amounts.is_empty() {
+            Vec::new()
+        } else {
+            AMOUNT_QUANTILES.iter().map(|q| (*q, amounts.quantile(*q))).collect()
+        };
+
+        let mut by_type: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for record in &self.transactions {
+            let entry = by_type.entry(record.r#type.as_str()).or_default();
+            entry.0 += 1;
+            entry.1 += record.is_fraud as usize;
+        }
+        let fraud_rate_by_type = by_type.into_iter()
+            .map(|(r#type, (transactions, fraud))| TypeFraudRate {
+                r#type: r#type.to_string(),
+                transactions,
+                fraud,
+                rate: fraud as f64 / transactions as f64,
+            })
+            .collect();
+
+        let (components, largest_component) = self.weak_components(&nodes);
+        let possible = account_count as f64 * account_count.saturating_sub(1) as f64;
+
+        GraphStats {
+            accounts: account_count,
+            edges: edge_count,
+            transactions: self.transactions.len(),
+            density: if possible > 0.0 { edge_count as f64 / possible } else { 0.0 },
+            reciprocity: if edge_count > self_loops { reciprocated as f64 / (edge_count - self_loops) as f64 } else { 0.0 },
+            self_loops,
+            in_degree: degree_stats(&in_degrees),
+            out_degree: degree_stats(&out_degrees),
+            amount_quantiles,
+            fraud_rate_by_type,
+            components,
+            largest_component,
+        }
+    }
+
+    // counts the weakly connected components with union-find.
+    // returns the number of components and the size of the largest one
+    fn weak_components(&self, nodes: &[NodeIndex]) -> (usize, usize) {
+        let position: HashMap<NodeIndex, usize> = nodes.iter().enumerate().map(|(pos, idx)| (*idx, pos)).collect();
+        let mut parent: Vec<usize> = (0..nodes.len()).collect();
+
+        fn find(parent: &mut [usize], mut x: usize) -> usize {
+            while parent[x] != x {
+                parent[x] = parent[parent[x]];
+                x = parent[x];
+            }
+            x
+        }
+
+        for edge in self.graph.edge_references() {
+            let a = find(&mut parent, position[&edge.source()]);
+            let b = find(&mut parent, position[&edge.target()]);
+            if a != b {
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+
+        let mut sizes: HashMap<usize, usize> = HashMap::new();
+        for pos in 0..nodes.len() {
+            *sizes.entry(find(&mut parent, pos)).or_default() += 1;
+        }
+        (sizes.len(), sizes.values().copied().max().unwrap_or(0))
+    }
+}
+
+// summarizes a degree sequence and fits a power law to its tail
+fn degree_stats(degrees: &[usize]) -> DegreeStats {
+    let values: Vec<f64> = degrees.iter().map(|d| *d as f64).collect();
+    let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+    for degree in degrees {
+        *histogram.entry(*degree).or_default() += 1;
+    }
+    let histogram: Vec<(usize, usize)> = histogram.into_iter().collect();
+
+    DegreeStats {
+        mean: if values.is_empty() { 0.0 } else { (&values).mean() },
+        std_dev: if values.len() < 2 { 0.0 } else { (&values).std_dev() },
+        median: if values.is_empty() { 0.0 } else { Data::new(values.clone()).median() },
+        max: degrees.iter().copied().max().unwrap_or(0),
+        power_law: fit_power_law(&histogram),
+        histogram,
+    }
+}
+
+// fits a discrete power law to a degree histogram the way clauset, shalizi and newman do:
+// for every candidate x_min the exponent is the maximum likelihood estimate
+// alpha = 1 + n / sum(ln(k / (x_min - 0.5))), and the x_min whose tail is closest to its fitted
+// law (smallest kolmogorov-smirnov distance against the continuous pareto approximation) wins.
+// zero degrees are never part of the tail.
+fn fit_power_law(histogram: &[(usize, usize)]) -> Option<PowerLawFit> {
+    let positive: Vec<(usize, usize)> = histogram.iter().copied().filter(|(degree, _)| *degree > 0).collect();
+    let mut best: Option<PowerLawFit> = None;
+
+    for start in 0..positive.len() {
+        let tail = &positive[start..];
+        let x_min = tail[0].0;
+        let tail_size: usize = tail.iter().map(|(_, count)| count).sum();
+        if tail_size < MIN_TAIL {
+            break;
+        }
+
+        let scale = x_min as f64 - 0.5;
+        let log_sum: f64 = tail.iter().map(|(degree, count)| *count as f64 * (*degree as f64 / scale).ln()).sum();
+        if log_sum <= 0.0 {
+            continue;
+        }
+        let alpha = 1.0 + tail_size as f64 / log_sum;
+        let Ok(model) = Pareto::new(scale, alpha - 1.0) else {
+            continue;
+        };
+
+        let mut seen = 0;
+        let mut ks_distance: f64 = 0.0;
+        for (degree, count) in tail {
+            seen += count;
+            let empirical = seen as f64 / tail_size as f64;
+            ks_distance = ks_distance.max((empirical - model.cdf(*degree as f64 + 0.5)).abs());
+        }
+
+        if best.as_ref().is_none_or(|b| ks_distance < b.ks_distance) {
+            best = Some(PowerLawFit { alpha, x_min, tail_size, ks_distance });
+        }
+    }
+
+    best
+}
+
+impl GraphStats {
+    // renders the statistics as a json object
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+        let _ = writeln!(json, "  \"accounts\": {},", self.accounts);
+        let _ = writeln!(json, "  \"edges\": {},", self.edges);
+        let _ = writeln!(json, "  \"transactions\": {},", self.transactions);
+        let _ = writeln!(json, "  \"density\": {},", json_number(self.density));
+        let _ = writeln!(json, "  \"reciprocity\": {},", json_number(self.reciprocity));
+        let _ = writeln!(json, "  \"self_loops\": {},", self.self_loops);
+        let _ = writeln!(json, "  \"in_degree\": {},", degree_json(&self.in_degree));
+        let _ = writeln!(json, "  \"out_degree\": {},", degree_json(&self.out_degree));
+
+        let quantiles: Vec<String> = self.amount_quantiles.iter()
+            .map(|(q, amount)| format!("{{\"quantile\": {}, \"amount\": {}}}", json_number(*q), json_number(*amount)))
+            .collect();
+        let _ = writeln!(json, "  \"amount_quantiles\": [{}],", quantiles.join(", "));
+
+        let rates: Vec<String> = self.fraud_rate_by_type.iter()
+            .map(|t| format!("{{\"type\": {}, \"transactions\": {}, \"fraud\": {}, \"rate\": {}}}",
+                json_string(&t.r#type), t.transactions, t.fraud, json_number(t.rate)))
+            .collect();
+        let _ = writeln!(json, "  \"fraud_rate_by_type\": [{}],", rates.join(", "));
+
+        let _ = writeln!(json, "  \"components\": {},", self.components);
+        let _ = writeln!(json, "  \"largest_component\": {}", self.largest_component);
+        json.push('}');
+        json.push('\n');
+        json
+    }
+
+    // prints a short human readable summary (the json export has the full histograms)
+    pub fn print(&self) {
+        println!("\n=== Network statistics ===");
+        println!("{:<22} {}", "Accounts", self.accounts);
+        println!("{:<22} {}", "Edges", self.edges);
+        println!("{:<22} {}", "Transactions", self.transactions);
+        println!("{:<22} {:.3e}", "Density", self.density);
+        println!("{:<22} {:.4}", "Reciprocity", self.reciprocity);
+        println!("{:<22} {}", "Self-loops", self.self_loops);
+        println!("{:<22} {} ({} components)", "Largest component", self.largest_component, self.components);
+        for (label, degrees) in [("In-degree", &self.in_degree), ("Out-degree", &self.out_degree)] {
+            let fit = match &degrees.power_law {
+                Some(fit) => format!("power law alpha {:.2} for k >= {} (KS {:.3})", fit.alpha, fit.x_min, fit.ks_distance),
+                None => "no power law fit".to_string(),
+            };
+            println!("{:<22} mean {:.2}, max {}, {}", label, degrees.mean, degrees.max, fit);
+        }
+        for (q, amount) in &self.amount_quantiles {
+            println!("{:<22} {:.2}", format!("Amount p{}", q * 100.0), amount);
+        }
+        for rate in &self.fraud_rate_by_type {
+            println!("{:<22} {:.4} ({} of {})", format!("Fraud rate {}", rate.r#type), rate.rate, rate.fraud, rate.transactions);
+        }
+    }
+}
+
+// renders a degree summary as a json object
+fn degree_json(degrees: &DegreeStats) -> String {
+    let histogram: Vec<String> = degrees.histogram.iter().map(|(degree, count)| format!("[{}, {}]", degree, count)).collect();
+    let power_law = match &degrees.power_law {
+        Some(fit) => format!("{{\"alpha\": {}, \"x_min\": {}, \"tail_size\": {}, \"ks_distance\": {}}}",
+            json_number(fit.alpha), fit.x_min, fit.tail_size, json_number(fit.ks_distance)),
+        None => "null".to_string(),
+    };
+    format!("{{\"mean\": {}, \"std_dev\": {}, \"median\": {}, \"max\": {}, \"histogram\": [{}], \"power_law\": {}}}",
+        json_number(degrees.mean), json_number(degrees.std_dev), json_number(degrees.median), degrees.max,
+        histogram.join(", "), power_law)
+}
+
+// formats a number for json, which has no nan or infinity
+fn json_number(value: f64) -> String {
+    if value.is_finite() { format!("{}", value) } else { "null".to_string() }
+}
+
+// quotes and escapes a string for json
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => { let _ = write!(quoted, "\\u{:04x}", c as u32); }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,310 @@
+// online detection over a live transaction feed.
+// instead of loading a full dataset and analyzing it in one batch, the online detector consumes
+// transactions one at a time in step order, keeps rolling per-account state, and raises an
+// alert the moment an account first meets a detector's criteria.
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+use crate::analysis::Detector;
+use crate::classify::{AccountClassifier, ClassThresholds};
+use crate::error::FraudError;
+use crate::graph::{AccountMetrics, Transaction, TransactionRecord};
+use crate::intern::{AccountId, AccountTable};
+use crate::utilities::{self, record_from_csv, step_from_csv, validate_headers};
+
+// the patterns the online detector can alert on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    // the account now meets the collector criteria
+    Collector,
+    // the account now meets the money mule criteria
+    MoneyMule,
+    // the account received a TRANSFER and cashed most of it out shortly after
+    TransferCashout,
+}
+
+impl AlertKind {
+    // a short human readable name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertKind::Collector => "collector",
+            AlertKind::MoneyMule => "money mule",
+            AlertKind::TransferCashout => "transfer-cashout",
+        }
+    }
+}
+
+// an alert raised by the online detector
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    // step of the transaction that triggered the alert
+    pub step: u32,
+    // the account the alert is about
+    pub account: AccountId,
+    // which pattern the account matched
+    pub kind: AlertKind,
+    // the account's rolling metrics when the alert was raised
+    pub metrics: AccountMetrics,
+}
+
+// settings for the online detector
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    // how many steps after a TRANSFER a CASH_OUT still counts as cashing it out
+    pub cashout_window: u32,
+    // the CASH_OUT must be at least this fraction of the TRANSFER amount
+    pub cashout_ratio: f64,
+    // decides whether an account is a customer or a merchant
+    pub classifier: AccountClassifier,
+    // the collector and money mule thresholds for each class of account
+    pub thresholds: ClassThresholds,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            cashout_window: 24,
+            cashout_ratio: 0.9,
+            classifier: AccountClassifier::default(),
+            thresholds: ClassThresholds::default(),
+        }
+    }
+}
+
+// keeps rolling per-account state over a transaction feed and raises alerts as they happen.
+// edges follow the same rule as the batch graph: a later transfer between the same pair of
+// accounts replaces the earlier one, so the rolling counts and volumes describe the same
+// edges the batch analysis would see for the transactions consumed so far.
+pub struct OnlineDetector {
+    // settings the detector was created with
+    config: StreamConfig,
+    // string table for every account seen so far
+    accounts: AccountTable,
+    // current weight of every (origin, destination) edge
+    edges: HashMap<(AccountId, AccountId), f64>,
+    // rolling metrics of every account seen so far
+    metrics: HashMap<AccountId, AccountMetrics>,
+    // most recent incoming TRANSFER per account, as (step, amount)
+    pending_transfers: HashMap<AccountId, (u32, f64)>,
+    // alerts already raised, so each account alerts at most once per kind
+    alerted: HashSet<(AccountId, AlertKind)>,
+    // step of the last transaction consumed
+    last_step: Option<u32>,
+}
+
+impl OnlineDetector {
+    // creates an online detector with no state.
+    // takes in `config` - the transfer-cashout settings, classification and detector thresholds
+    pub fn new(config: StreamConfig) -> Self {
+        OnlineDetector {
+            config,
+            accounts: AccountTable::new(),
+            edges: HashMap::new(),
+            metrics: HashMap::new(),
+            pending_transfers: HashMap::new(),
+            alerted: HashSet::new(),
+            last_step: None,
+        }
+    }
+
+    // get the string table of every account seen so far
+    pub fn accounts(&self) -> &AccountTable {
+        &self.accounts
+    }
+
+    // get the rolling metrics of an account
+    pub fn metrics(&self, account: AccountId) -> Option<&AccountMetrics> {
+        self.metrics.get(&account)
+    }
+
+    // consumes the next transaction of the feed.
+    // takes in `transaction` - the next transaction; steps must never decrease
+    // returns the alerts raised by this transaction (possibly none), or a schema error if
+    // the transaction is older than the previous one
+    pub fn process(&mut self, transaction: Transaction) -> Result<Vec<Alert>, FraudError> {
+        // a rejected transaction must not leave its account names behind in the table
+        self.check_step(transaction.step)?;
+        let record = TransactionRecord {
+            step: transaction.step,
+            name_orig: self.accounts.intern(&transaction.name_orig),
+            name_dest: self.accounts.intern(&transaction.name_dest),
+            r#type: transaction.r#type,
+            amount: transaction.amount,
+            is_fraud: transaction.is_fraud,
+            balances: transaction.balances,
+        };
+        self.process_record(&record)
+    }
+
+    // checks that a transaction at `step` may follow the previous one.
+    // returns a schema error if it is older than the previous transaction
+    fn check_step(&self, step: u32) -> Result<(), FraudError> {
+        match self.last_step.filter(|last| step < *last) {
+            Some(last) => Err(FraudError::Schema(format!(
+                "transactions must arrive in step order: step {} after step {}", step, last))),
+            None => Ok(()),
+        }
+    }
+
+    // consumes the next transaction of the feed, with its account names already interned
+    // into this detector's account table (see process)
+    fn process_record(&mut self, transaction: &TransactionRecord) -> Result<Vec<Alert>, FraudError> {
+        let step = transaction.step;
+        self.check_step(step)?;
+        self.last_step = Some(step);
+
+        let (orig, dest) = (transaction.name_orig, transaction.name_dest);
+        self.update_metrics(orig, dest, transaction.amount);
+
+        let mut alerts = Vec::new();
+
+        // transfer-cashout: remember incoming transfers, match them against later cash outs
+        match transaction.r#type.as_str() {
+            "TRANSFER" => {
+                self.pending_transfers.insert(dest, (step, transaction.amount));
+            }
+            "CASH_OUT" => {
+                if let Some((transfer_step, transfer_amount)) = self.pending_transfers.get(&orig).copied() {
+                    if step - transfer_step > self.config.cashout_window {
+                        self.pending_transfers.remove(&orig);
+                    } else if transaction.amount >= self.config.cashout_ratio * transfer_amount {
+                        self.pending_transfers.remove(&orig);
+                        self.raise(&mut alerts, step, orig, AlertKind::TransferCashout);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        // rule-based detectors on the two accounts whose metrics just changed,
+        // with the thresholds of each account's class
+        for account in [orig, dest] {
+            let thresholds = self.config.thresholds.for_class(self.config.classifier.classify(self.accounts.name(account)));
+            let metrics = &self.metrics[&account];
+            let collector = Detector::Collector.matches(metrics, thresholds);
+            let money_mule = Detector::MoneyMule.matches(metrics, thresholds);
+            if collector {
+                self.raise(&mut alerts, step, account, AlertKind::Collector);
+            }
+            if money_mule {
+                self.raise(&mut alerts, step, account, AlertKind::MoneyMule);
+            }
+        }
+
+        Ok(alerts)
+    }
+
+    // applies one transfer to the rolling edge weights and both accounts' metrics
+    fn update_metrics(&mut self, orig: AccountId, dest: AccountId, amount: f64) {
+        let previous = self.edges.insert((orig, dest), amount);
+
+        let source = self.metrics.entry(orig).or_default();
+        match previous {
+            Some(old) => source.outgoing_volume += amount - old,
+            None => {
+                source.outgoing_count += 1;
+                source.outgoing_volume += amount;
+            }
+        }
+        source.calculate_retention_rate();
+
+        let target = self.metrics.entry(dest).or_default();
+        match previous {
+            Some(old) => target.incoming_volume += amount - old,
+            None => {
+                target.incoming_count += 1;
+                target.incoming_volume += amount;
+            }
+        }
+        target.calculate_retention_rate();
+    }
+
+    // records an alert unless the account already alerted for this kind
+    fn raise(&mut self, alerts: &mut Vec<Alert>, step: u32, account: AccountId, kind: AlertKind) {
+        if self.alerted.insert((account, kind)) {
+            alerts.push(Alert {
+                step,
+                account,
+                kind,
+                metrics: self.metrics[&account].clone(),
+            });
+        }
+    }
+}
+
+// runs the online detector over a csv feed such as stdin or a growing file.
+// the feed is parsed with the same csv reader and record parser as the batch loader, so quoted
+// fields are handled the same way. a header line is accepted (and checked) as the first line,
+// and if it names the balance columns every transaction carries its balances. malformed lines
+// and lines that arrive out of step order are reported as warnings and skipped.
+// takes in `input` - the feed, `follow` - keep waiting for new lines at end of input
+// (like `tail -f`) instead of stopping, `detector` - the detector to feed, and
+// `on_alert` - called for every alert as soon as it is raised
+// returns the number of transactions consumed, or an error if the feed cannot be read
+pub fn run_stream<R, F>(input: R, follow: bool, detector: &mut OnlineDetector, mut on_alert: F) -> Result<u64, FraudError>
+where
+    R: Read,
+    F: FnMut(&OnlineDetector, &Alert),
+{
+    let input: Box<dyn Read + '_> = if follow { Box::new(Follow(input)) } else { Box::new(input) };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(input);
+    let mut record = csv::StringRecord::new();
+    let mut balance_columns = None;
+    let mut consumed: u64 = 0;
+
+    loop {
+        match reader.read_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => return Ok(consumed),
+            Err(e) if e.is_io_error() => return Err(e.into()),
+            Err(e) => {
+                eprintln!("Warning: {}", FraudError::from(e));
+                continue;
+            }
+        }
+
+        let row = record.position().map(|p| p.line()).unwrap_or(0);
+        if row == 1 && record.get(0).is_some_and(|field| field.trim().to_lowercase().starts_with("step")) {
+            validate_headers(&record)?;
+            balance_columns = utilities::balance_columns(&record);
+            continue;
+        }
+
+        // the step is checked before record_from_csv interns the account names of the row
+        let parsed = step_from_csv(&record)
+            .and_then(|step| detector.check_step(step))
+            .and_then(|_| record_from_csv(&record, balance_columns, &mut detector.accounts));
+        let alerts = match parsed.and_then(|t| detector.process_record(&t)) {
+            Ok(alerts) => alerts,
+            Err(e) => {
+                eprintln!("Warning: skipping record: {}", e);
+                continue;
+            }
+        };
+        consumed += 1;
+
+        for alert in &alerts {
+            on_alert(detector, alert);
+        }
+    }
+}
+
+// a reader over a growing file that never reports the end of input: when the file has no
+// new data it waits and reads again, so a line that is only partly written is completed
+// by a later read instead of being parsed early
+struct Follow<R>(R);
+
+impl<R: Read> Read for Follow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.0.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
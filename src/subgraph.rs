@@ -0,0 +1,202 @@
+// extraction of smaller transaction graphs from a larger one.
+// an investigation usually starts from a handful of accounts ("everything within 2 hops of
+// C1625226992"), or from a slice of the data ("only TRANSFERs between steps 100 and 200 over
+// $50k"). both the ego network and the filtered view are self-contained TransactionGraphs
+// that can be analyzed, exported or snapshotted like the full graph.
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use crate::graph::{TransactionGraph, TransactionRecord};
+use crate::intern::AccountId;
+
+// which edges a neighborhood search follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // only from sender to receiver: where the money went
+    Outgoing,
+    // only from receiver to sender: where the money came from
+    Incoming,
+    // both ways
+    Both,
+}
+
+// settings for ego network extraction
+#[derive(Debug, Clone)]
+pub struct EgoOptions {
+    // which edges to follow away from the seeds
+    pub direction: Direction,
+    // how many edges away from the seeds to go
+    pub hops: usize,
+    // only transactions in this step range are followed and kept, or all of them if none
+    pub steps: Option<RangeInclusive<u32>>,
+    // stop adding accounts once this many are included, or never stop if none.
+    // accounts closer to the seeds are always added first
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for EgoOptions {
+    fn default() -> Self {
+        EgoOptions {
+            direction: Direction::Both,
+            hops: 2,
+            steps: None,
+            max_nodes: None,
+        }
+    }
+}
+
+// a custom condition on a single transaction
+pub type TransactionPredicate = Box<dyn Fn(&TransactionRecord) -> bool + Send + Sync>;
+// a custom condition on an edge, given the origin, the destination and the edge weight
+pub type EdgePredicate = Box<dyn Fn(AccountId, AccountId, f64) -> bool + Send + Sync>;
+
+// a reusable set of conditions on transactions and on the edges they form.
+// a transaction is kept when it passes every transaction condition; afterwards the edges of
+// the kept transactions are checked against every edge condition, and the transactions behind
+// a rejected edge are dropped too. conditions left unset accept everything.
+#[derive(Default)]
+pub struct TransactionFilter {
+    // only transactions in this step range
+    pub steps: Option<RangeInclusive<u32>>,
+    // only transactions whose amount is in this range
+    pub amounts: Option<RangeInclusive<f64>>,
+    // only transactions of these types (TRANSFER, CASH_OUT, ...)
+    pub types: Option<Vec<String>>,
+    // extra conditions on single transactions
+    pub predicates: Vec<TransactionPredicate>,
+    // conditions on edges, given the origin, the destination and the edge weight
+    pub edge_predicates: Vec<EdgePredicate>,
+}
+
+impl TransactionFilter {
+    // creates a filter that accepts every transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // adds a custom condition on single transactions.
+    // takes in `predicate` - returns true for transactions to keep
+    pub fn matching<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&TransactionRecord) -> bool + Send + Sync + 'static,
+    {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    // adds a custom condition on the edges formed by the kept transactions.
+    // takes in `predicate` - called with the origin, destination and edge weight; returns true for edges to keep
+    pub fn matching_edges<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(AccountId, AccountId, f64) -> bool + Send + Sync + 'static,
+    {
+        self.edge_predicates.push(Box::new(predicate));
+        self
+    }
+
+    // checks whether a transaction passes every transaction condition
+    pub fn accepts(&self, record: &TransactionRecord) -> bool {
+        self.steps.as_ref().is_none_or(|steps| steps.contains(&record.step))
+            && self.amounts.as_ref().is_none_or(|amounts| amounts.contains(&record.amount))
+            && self.types.as_ref().is_none_or(|types| types.contains(&record.r#type))
+            && self.predicates.iter().all(|predicate| predicate(record))
+    }
+
+    // checks whether an edge passes every edge condition
+    pub fn accepts_edge(&self, orig: AccountId, dest: AccountId, weight: f64) -> bool {
+        self.edge_predicates.iter().all(|predicate| predicate(orig, dest, weight))
+    }
+}
+
+impl TransactionGraph {
+    // builds a filtered view of the graph.
+    // takes in `filter` - the transaction and edge conditions
+    // returns a new transaction graph (with its own account table) holding the accepted
+    // transactions in their original order; accounts left without transactions are not included
+    pub fn filter(&self, filter: &TransactionFilter) -> TransactionGraph {
+        let mut kept: Vec<&TransactionRecord> = self.transactions.iter().filter(|r| filter.accepts(r)).collect();
+
+        if !filter.edge_predicates.is_empty() {
+            // an edge carries the amount of the last kept transaction between its two accounts
+            let mut weights: HashMap<(AccountId, AccountId), f64> = HashMap::new();
+            for record in &kept {
+                weights.insert((record.name_orig, record.name_dest), record.amount);
+            }
+            kept.retain(|r| filter.accepts_edge(r.name_orig, r.name_dest, weights[&(r.name_orig, r.name_dest)]));
+        }
+
+        let mut view = TransactionGraph::with_classifier(self.classifier().clone());
+        self.copy_records(&mut view, kept);
+        view
+    }
+
+    // copies records into another graph, re-interning their account names in its table
+    fn copy_records<'a>(&self, target: &mut TransactionGraph, records: impl IntoIterator<Item = &'a TransactionRecord>) {
+        for record in records {
+            let mut copy = record.clone();
+            copy.name_orig = target.accounts.intern(self.account_name(record.name_orig));
+            copy.name_dest = target.accounts.intern(self.account_name(record.name_dest));
+            target.add_record(copy);
+        }
+    }
+
+    // extracts the subgraph induced by the accounts near a seed set.
+    // accounts are discovered breadth first, one hop at a time, visiting each hop in account
+    // handle order so the node budget always cuts off the same accounts. the result holds every
+    // (in-range) transaction between two included accounts, in the original order, so its edges
+    // and metrics are exactly those of the induced subgraph.
+    // takes in `seeds` - the accounts to start from (accounts not in the graph are ignored),
+    // and `options` - direction, hop limit, step range and node budget
+    // returns a new transaction graph with its own account table
+    pub fn ego_network(&self, seeds: &[AccountId], options: &EgoOptions) -> TransactionGraph {
+        let in_range = |step: u32| options.steps.as_ref().is_none_or(|steps| steps.contains(&step));
+        let budget = options.max_nodes.unwrap_or(usize::MAX);
+
+        // adjacency restricted to the transactions in the step range
+        let mut adjacent: HashMap<AccountId, Vec<AccountId>> = HashMap::new();
+        for record in self.transactions.iter().filter(|r| in_range(r.step)) {
+            if options.direction != Direction::Incoming {
+                adjacent.entry(record.name_orig).or_default().push(record.name_dest);
+            }
+            if options.direction != Direction::Outgoing {
+                adjacent.entry(record.name_dest).or_default().push(record.name_orig);
+            }
+        }
+
+        let mut frontier: Vec<AccountId> = seeds.iter().copied().filter(|s| self.node_map.contains_key(s)).collect();
+        frontier.sort_unstable();
+        frontier.dedup();
+        frontier.truncate(budget);
+        let mut included: HashSet<AccountId> = frontier.iter().copied().collect();
+
+        for _ in 0..options.hops {
+            let mut next: Vec<AccountId> = frontier.iter()
+                .filter_map(|account| adjacent.get(account))
+                .flatten()
+                .copied()
+                .filter(|account| !included.contains(account))
+                .collect();
+            next.sort_unstable();
+            next.dedup();
+            next.truncate(budget - included.len());
+            if next.is_empty() {
+                break;
+            }
+            included.extend(next.iter().copied());
+            frontier = next;
+        }
+
+        let mut subgraph = TransactionGraph::with_classifier(self.classifier().clone());
+        // seeds come first so they keep their place even when they have no transactions in range
+        let mut seeds: Vec<AccountId> = seeds.iter().copied().filter(|s| included.contains(s)).collect();
+        seeds.sort_unstable();
+        seeds.dedup();
+        for seed in seeds {
+            let id = subgraph.accounts.intern(self.account_name(seed));
+            subgraph.node_for(id);
+        }
+        self.copy_records(&mut subgraph, self.transactions.iter().filter(|r| {
+            in_range(r.step) && included.contains(&r.name_orig) && included.contains(&r.name_dest)
+        }));
+        subgraph
+    }
+}
@@ -0,0 +1,312 @@
+// threshold sweeps for the rule-based detectors.
+// the default detector thresholds (a retention rate above 0.7, more than 10000 received, ...)
+// were picked by hand. a sweep varies one threshold over a grid of values while the others keep
+// their configured values, and measures at every value how well the detector's flags match the
+// `is_fraud`-derived account labels (an account is fraud if it sent or received a fraud
+// transaction). the resulting roc and pr curves show the trade-off, and the best value for f1
+// or for a target precision is a data-driven replacement for the hand-picked one.
+use std::io::Write;
+use crate::analysis::{Detector, FraudAnalysis};
+use crate::classify::DetectorThresholds;
+use crate::error::FraudError;
+use crate::graph::{AccountMetrics, TransactionGraph};
+use crate::intern::AccountId;
+use crate::model::fraud_accounts;
+use crate::utilities::{map_ranges, resolve_threads};
+
+// a single detector threshold that can be swept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThresholdParameter {
+    // CollectorThresholds::min_incoming
+    CollectorMinIncoming,
+    // CollectorThresholds::in_out_ratio
+    CollectorInOutRatio,
+    // CollectorThresholds::min_retention
+    CollectorMinRetention,
+    // MuleThresholds::min_incoming
+    MuleMinIncoming,
+    // MuleThresholds::min_outgoing
+    MuleMinOutgoing,
+    // MuleThresholds::min_forwarded
+    MuleMinForwarded,
+    // MuleThresholds::max_retention
+    MuleMaxRetention,
+    // MuleThresholds::min_incoming_volume
+    MuleMinIncomingVolume,
+}
+
+impl ThresholdParameter {
+    // every parameter, in report order
+    pub const ALL: [ThresholdParameter; 8] = [
+        ThresholdParameter::CollectorMinIncoming,
+        ThresholdParameter::CollectorInOutRatio,
+        ThresholdParameter::CollectorMinRetention,
+        ThresholdParameter::MuleMinIncoming,
+        ThresholdParameter::MuleMinOutgoing,
+        ThresholdParameter::MuleMinForwarded,
+        ThresholdParameter::MuleMaxRetention,
+        ThresholdParameter::MuleMinIncomingVolume,
+    ];
+
+    // the detector this threshold belongs to
+    pub fn detector(&self) -> Detector {
+        match self {
+            ThresholdParameter::CollectorMinIncoming
+            | ThresholdParameter::CollectorInOutRatio
+            | ThresholdParameter::CollectorMinRetention => Detector::Collector,
+            _ => Detector::MoneyMule,
+        }
+    }
+
+    // the name of the threshold field
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThresholdParameter::CollectorMinIncoming | ThresholdParameter::MuleMinIncoming => "min_incoming",
+            ThresholdParameter::CollectorInOutRatio => "in_out_ratio",
+            ThresholdParameter::CollectorMinRetention => "min_retention",
+            ThresholdParameter::MuleMinOutgoing => "min_outgoing",
+            ThresholdParameter::MuleMinForwarded => "min_forwarded",
+            ThresholdParameter::MuleMaxRetention => "max_retention",
+            ThresholdParameter::MuleMinIncomingVolume => "min_incoming_volume",
+        }
+    }
+
+    // a grid of values covering the useful range of the threshold, in increasing order
+    pub fn default_grid(&self) -> Vec<f64> {
+        let fractions = || (0..=20).map(|i| i as f64 * 0.05);
+        match self {
+            ThresholdParameter::CollectorMinIncoming => (1..=20).map(f64::from).collect(),
+            ThresholdParameter::CollectorInOutRatio => (0..=10).map(f64::from).collect(),
+            ThresholdParameter::MuleMinIncoming | ThresholdParameter::MuleMinOutgoing => (1..=10).map(f64::from).collect(),
+            ThresholdParameter::CollectorMinRetention
+            | ThresholdParameter::MuleMinForwarded
+            | ThresholdParameter::MuleMaxRetention => fractions().collect(),
+            // 0, then 1, 2 and 5 times every power of ten up to 10 million
+            ThresholdParameter::MuleMinIncomingVolume => std::iter::once(0.0)
+                .chain((0..=7).flat_map(|e| [1.0, 2.0, 5.0].map(|m| m * 10f64.powi(e))))
+                .filter(|v| *v <= 1e7)
+                .collect(),
+        }
+    }
+
+    // sets this threshold in a set of detector thresholds.
+    // a detector the thresholds disable stays disabled
+    pub fn apply(&self, thresholds: &mut DetectorThresholds, value: f64) {
+        if let Some(collector) = &mut thresholds.collector {
+            match self {
+                ThresholdParameter::CollectorMinIncoming => collector.min_incoming = value as u32,
+                ThresholdParameter::CollectorInOutRatio => collector.in_out_ratio = value as u32,
+                ThresholdParameter::CollectorMinRetention => collector.min_retention = value,
+                _ => {}
+            }
+        }
+        if let Some(mule) = &mut thresholds.money_mule {
+            match self {
+                ThresholdParameter::MuleMinIncoming => mule.min_incoming = value as u32,
+                ThresholdParameter::MuleMinOutgoing => mule.min_outgoing = value as u32,
+                ThresholdParameter::MuleMinForwarded => mule.min_forwarded = value,
+                ThresholdParameter::MuleMaxRetention => mule.max_retention = value,
+                ThresholdParameter::MuleMinIncomingVolume => mule.min_incoming_volume = value,
+                _ => {}
+            }
+        }
+    }
+}
+
+// how a detector did at one threshold value
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepPoint {
+    // the threshold value
+    pub value: f64,
+    // flagged accounts labeled fraud
+    pub true_positives: usize,
+    // flagged accounts not labeled fraud
+    pub false_positives: usize,
+    // fraud accounts not flagged
+    pub false_negatives: usize,
+    // accounts neither flagged nor labeled fraud
+    pub true_negatives: usize,
+    // true_positives / flagged (1 if nothing is flagged)
+    pub precision: f64,
+    // true_positives / fraud accounts (the true positive rate)
+    pub recall: f64,
+    // false_positives / non-fraud accounts
+    pub false_positive_rate: f64,
+    // harmonic mean of precision and recall
+    pub f1: f64,
+}
+
+// the result of sweeping one threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepCurve {
+    // the swept threshold
+    pub parameter: ThresholdParameter,
+    // one point per grid value, in grid order
+    pub points: Vec<SweepPoint>,
+    // area under the roc curve through the points, closed with (0, 0) and (1, 1)
+    pub roc_auc: f64,
+    // area under the precision-recall curve through the points, from recall 0 to the highest recall reached
+    pub pr_auc: f64,
+}
+
+impl SweepCurve {
+    // the point with the highest f1, the smallest value among ties
+    pub fn best_f1(&self) -> Option<&SweepPoint> {
+        self.points.iter().fold(None, |best: Option<&SweepPoint>, point| match best {
+            Some(b) if b.f1 >= point.f1 => Some(b),
+            _ => Some(point),
+        })
+    }
+
+    // the point with the highest recall whose precision is at least `target`
+    // (the smallest value among ties), or None if no value reaches the target
+    pub fn for_precision(&self, target: f64) -> Option<&SweepPoint> {
+        self.points.iter()
+            .filter(|point| point.true_positives > 0 && point.precision >= target)
+            .fold(None, |best: Option<&SweepPoint>, point| match best {
+                Some(b) if b.recall >= point.recall => Some(b),
+                _ => Some(point),
+            })
+    }
+}
+
+impl FraudAnalysis {
+    // sweeps one detector threshold over a grid of values, with every other threshold at its
+    // configured value. the value is applied to every account class whose thresholds enable
+    // the detector. grid values are spread over the configured number of threads.
+    // takes in `graph` - the graph this analysis was built from (for the fraud labels),
+    // `parameter` - the threshold to vary, and `values` - the grid
+    // returns the confusion counts, precision, recall and f1 at every value, and both auc values
+    pub fn threshold_sweep(&self, graph: &TransactionGraph, parameter: ThresholdParameter, values: &[f64]) -> SweepCurve {
+        let fraud = fraud_accounts(&graph.transactions);
+        let accounts: Vec<(AccountId, &AccountMetrics, bool)> = self.accounts()
+            .map(|(account, metrics)| (account, metrics, fraud.contains(&account)))
+            .collect();
+        let positives = accounts.iter().filter(|(_, _, label)| *label).count();
+        let negatives = accounts.len() - positives;
+        let detector = parameter.detector();
+
+        let points = map_ranges(values.len(), resolve_threads(self.config().threads), |range| {
+            values[range].iter().map(|value| {
+                let mut thresholds = self.config().thresholds.clone();
+                for class in [&mut thresholds.customer, &mut thresholds.merchant, &mut thresholds.other] {
+                    parameter.apply(class, *value);
+                }
+
+                let (mut true_positives, mut false_positives) = (0, 0);
+                for (account, metrics, label) in &accounts {
+                    if detector.matches(metrics, thresholds.for_class(self.account_class(*account))) {
+                        if *label { true_positives += 1 } else { false_positives += 1 }
+                    }
+                }
+                let flagged = true_positives + false_positives;
+                let precision = if flagged > 0 { true_positives as f64 / flagged as f64 } else { 1.0 };
+                let recall = if positives > 0 { true_positives as f64 / positives as f64 } else { 0.0 };
+                SweepPoint {
+                    value: *value,
+                    true_positives,
+                    false_positives,
+                    false_negatives: positives - true_positives,
+                    true_negatives: negatives - false_positives,
+                    precision,
+                    recall,
+                    false_positive_rate: if negatives > 0 { false_positives as f64 / negatives as f64 } else { 0.0 },
+                    f1: if true_positives > 0 { 2.0 * precision * recall / (precision + recall) } else { 0.0 },
+                }
+            }).collect()
+        });
+
+        SweepCurve {
+            parameter,
+            roc_auc: roc_area(&points),
+            pr_auc: pr_area(&points),
+            points,
+        }
+    }
+
+    // sweeps every detector threshold over its default grid.
+    // returns one curve per parameter, in ThresholdParameter::ALL order
+    pub fn sweep_all_thresholds(&self, graph: &TransactionGraph) -> Vec<SweepCurve> {
+        ThresholdParameter::ALL.iter()
+            .map(|parameter| self.threshold_sweep(graph, *parameter, &parameter.default_grid()))
+            .collect()
+    }
+}
+
+// the area under the roc curve: points sorted by false positive rate, joined by straight
+// lines and closed with (0, 0) and (1, 1)
+fn roc_area(points: &[SweepPoint]) -> f64 {
+    let mut curve: Vec<(f64, f64)> = points.iter().map(|p| (p.false_positive_rate, p.recall)).collect();
+    curve.push((0.0, 0.0));
+    curve.push((1.0, 1.0));
+    curve.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    trapezoid(&curve)
+}
+
+// the area under the precision-recall curve: points sorted by recall, joined by straight
+// lines, starting at recall 0 with the precision of the lowest-recall point
+fn pr_area(points: &[SweepPoint]) -> f64 {
+    let mut curve: Vec<(f64, f64)> = points.iter().map(|p| (p.recall, p.precision)).collect();
+    curve.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.1.total_cmp(&a.1)));
+    if let Some(first) = curve.first().copied() {
+        curve.insert(0, (0.0, first.1));
+    }
+    trapezoid(&curve)
+}
+
+// the area under a piecewise linear curve given as (x, y) points sorted by x
+fn trapezoid(curve: &[(f64, f64)]) -> f64 {
+    curve.windows(2).map(|pair| (pair[1].0 - pair[0].0) * (pair[0].1 + pair[1].1) / 2.0).sum()
+}
+
+// writes the points of every curve as csv, one row per threshold value.
+// the false_positive_rate and recall columns are the roc curve, recall and precision the pr curve.
+// takes in `curves` - the sweep results, and `out` - where to write the table
+// returns an i/o error if writing fails
+pub fn write_sweep_points<W: Write>(curves: &[SweepCurve], mut out: W) -> Result<(), FraudError> {
+    writeln!(out, "detector,parameter,value,true_positives,false_positives,false_negatives,true_negatives,precision,recall,false_positive_rate,f1")?;
+    for curve in curves {
+        for point in &curve.points {
+            writeln!(out, "{},{},{},{},{},{},{},{},{},{},{}",
+                curve.parameter.detector().name(),
+                curve.parameter.name(),
+                point.value,
+                point.true_positives,
+                point.false_positives,
+                point.false_negatives,
+                point.true_negatives,
+                point.precision,
+                point.recall,
+                point.false_positive_rate,
+                point.f1)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// writes one summary row per curve as csv: both auc values, the value with the best f1, and
+// the value with the best recall at `target_precision` (empty if no value reaches it).
+// takes in `curves` - the sweep results, `target_precision` - the precision to meet,
+// and `out` - where to write the table
+// returns an i/o error if writing fails
+pub fn write_sweep_summary<W: Write>(curves: &[SweepCurve], target_precision: f64, mut out: W) -> Result<(), FraudError> {
+    writeln!(out, "detector,parameter,roc_auc,pr_auc,best_f1_value,best_f1,target_precision,target_precision_value,target_precision_recall")?;
+    for curve in curves {
+        let (best_value, best_f1) = curve.best_f1().map_or((String::new(), String::new()), |p| (p.value.to_string(), p.f1.to_string()));
+        let (target_value, target_recall) = curve.for_precision(target_precision)
+            .map_or((String::new(), String::new()), |p| (p.value.to_string(), p.recall.to_string()));
+        writeln!(out, "{},{},{},{},{},{},{},{},{}",
+            curve.parameter.detector().name(),
+            curve.parameter.name(),
+            curve.roc_auc,
+            curve.pr_auc,
+            best_value,
+            best_f1,
+            target_precision,
+            target_value,
+            target_recall)?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,464 @@
+// seeded synthetic transaction data with injected, labeled fraud patterns.
+// the kaggle paysim file is 471MB, too large for tests and slow for benchmarks. the generator
+// builds a paysim-like stream instead: customers (`C...`) pay merchants (`M...`), cash in and out
+// through them and transfer to each other, and on top of that background it injects the fraud
+// typologies the detectors look for. every injected transaction has is_fraud set, and every
+// injected pattern is listed with its accounts, so detector output can be checked against it.
+// the same config and seed always give the same transactions, on every platform.
+use std::collections::HashMap;
+use std::io::Write;
+use crate::error::FraudError;
+use crate::graph::{Balances, Transaction, TransactionGraph};
+
+// settings of the generator
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    // seed of the random number generator
+    pub seed: u64,
+    // number of ordinary customer accounts
+    pub customers: usize,
+    // number of merchant accounts
+    pub merchants: usize,
+    // number of steps (hours in paysim); transactions fall in 1..=steps
+    pub steps: u32,
+    // number of ordinary background transactions
+    pub transactions: usize,
+    // number of money mules to inject
+    pub mules: usize,
+    // number of collectors to inject
+    pub collectors: usize,
+    // number of cycles to inject
+    pub cycles: usize,
+    // number of structuring patterns to inject
+    pub structuring: usize,
+    // number of layering chains to inject
+    pub layering: usize,
+    // the reporting limit structuring deposits stay just under
+    pub structuring_limit: f64,
+    // number of intermediate accounts in a layering chain
+    pub chain_length: usize,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            seed: 42,
+            customers: 2000,
+            merchants: 200,
+            steps: 100,
+            transactions: 20000,
+            mules: 10,
+            collectors: 10,
+            cycles: 5,
+            structuring: 5,
+            layering: 5,
+            structuring_limit: 10000.0,
+            chain_length: 4,
+        }
+    }
+}
+
+// the kinds of fraud pattern the generator injects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Typology {
+    // victims send large transfers to one account that forwards nearly all of it to a cash-out account
+    MoneyMule,
+    // many customers send to one account that keeps nearly everything
+    Collector,
+    // a sum travels around a ring of accounts and returns to where it started, minus small fees
+    Cycle,
+    // one account moves a large sum as many transfers just under the reporting limit
+    Structuring,
+    // a sum hops through a chain of fresh accounts, one hop per step, and is cashed out at the end
+    Layering,
+}
+
+impl Typology {
+    // every typology, in report order
+    pub const ALL: [Typology; 5] = [Typology::MoneyMule, Typology::Collector, Typology::Cycle, Typology::Structuring, Typology::Layering];
+
+    // a short human readable name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Typology::MoneyMule => "money mule",
+            Typology::Collector => "collector",
+            Typology::Cycle => "cycle",
+            Typology::Structuring => "structuring",
+            Typology::Layering => "layering",
+        }
+    }
+}
+
+// one injected fraud pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct InjectedPattern {
+    // what kind of pattern it is
+    pub typology: Typology,
+    // the accounts created for the pattern, the central one first: the mule, the collector, the
+    // first account of the cycle, the structuring source or the start of the layering chain.
+    // victims and senders drawn from the ordinary customers are not included
+    pub accounts: Vec<String>,
+    // the first and last step of the pattern's transactions
+    pub steps: (u32, u32),
+    // how many transactions the pattern added
+    pub transactions: usize,
+}
+
+// a generated transaction stream and the patterns injected into it
+#[derive(Debug, Clone)]
+pub struct SyntheticDataset {
+    // every transaction in step order, with consistent balances
+    pub transactions: Vec<Transaction>,
+    // the injected patterns, in the order they were generated
+    pub patterns: Vec<InjectedPattern>,
+}
+
+// splitmix64: a small, fast generator with good statistical quality, so the crate needs no
+// dependency for random numbers and the output never changes under a dependency update
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // a uniform number in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // a uniform index in 0..n (n must be positive)
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    // a uniform integer in lo..=hi
+    fn between(&mut self, lo: usize, hi: usize) -> usize {
+        lo + self.below(hi - lo + 1)
+    }
+
+    // a uniform amount in [lo, hi), rounded to cents
+    fn uniform(&mut self, lo: f64, hi: f64) -> f64 {
+        cents(lo + self.next_f64() * (hi - lo))
+    }
+
+    // an amount whose logarithm is uniform in [ln lo, ln hi), rounded to cents.
+    // small amounts are common and large ones rare, as in real payment data
+    fn log_uniform(&mut self, lo: f64, hi: f64) -> f64 {
+        cents((lo.ln() + self.next_f64() * (hi.ln() - lo.ln())).exp())
+    }
+
+    // `count` distinct indices in 0..n, in random order (count must not exceed n)
+    fn sample(&mut self, n: usize, count: usize) -> Vec<usize> {
+        let mut picked: Vec<usize> = Vec::with_capacity(count);
+        while picked.len() < count {
+            let i = self.below(n);
+            if !picked.contains(&i) {
+                picked.push(i);
+            }
+        }
+        picked
+    }
+}
+
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+// builds the transactions while handing out fresh account ids
+struct Generator<'a> {
+    config: &'a GeneratorConfig,
+    rng: SplitMix64,
+    transactions: Vec<Transaction>,
+    // the number part of the last customer id handed out
+    next_account: usize,
+}
+
+impl Generator<'_> {
+    fn customer(&self, i: usize) -> String {
+        format!("C{}", i + 1)
+    }
+
+    fn merchant(&self, i: usize) -> String {
+        format!("M{}", i + 1)
+    }
+
+    // a customer id no other account uses
+    fn fresh(&mut self) -> String {
+        self.next_account += 1;
+        format!("C{}", self.next_account)
+    }
+
+    fn random_customer(&mut self) -> String {
+        let i = self.rng.below(self.config.customers);
+        self.customer(i)
+    }
+
+    fn random_merchant(&mut self) -> String {
+        let i = self.rng.below(self.config.merchants);
+        self.merchant(i)
+    }
+
+    // a step at which a pattern lasting `span` more steps still ends by the last step
+    fn start_step(&mut self, span: u32) -> u32 {
+        let latest = self.config.steps.saturating_sub(span).max(1);
+        1 + self.rng.below(latest as usize) as u32
+    }
+
+    // keeps a step within 1..=steps, for configs with fewer steps than a pattern spans
+    fn clamp(&self, step: u32) -> u32 {
+        step.clamp(1, self.config.steps.max(1))
+    }
+
+    fn push(&mut self, step: u32, kind: &str, amount: f64, from: &str, to: &str, is_fraud: u8) {
+        self.transactions.push(Transaction {
+            step: self.clamp(step),
+            r#type: kind.to_string(),
+            amount,
+            name_orig: from.to_string(),
+            name_dest: to.to_string(),
+            is_fraud,
+            balances: None,
+        });
+    }
+
+    // ordinary activity between the customer and merchant pools
+    fn background(&mut self) {
+        if self.config.customers < 2 || self.config.merchants == 0 {
+            return;
+        }
+        for _ in 0..self.config.transactions {
+            let step = 1 + self.rng.below(self.config.steps.max(1) as usize) as u32;
+            let roll = self.rng.next_f64();
+            let customer = self.random_customer();
+            if roll < 0.36 {
+                let (merchant, amount) = (self.random_merchant(), self.rng.log_uniform(10.0, 2000.0));
+                self.push(step, "PAYMENT", amount, &customer, &merchant, 0);
+            } else if roll < 0.70 {
+                let (merchant, amount) = (self.random_merchant(), self.rng.log_uniform(500.0, 100000.0));
+                self.push(step, "CASH_OUT", amount, &customer, &merchant, 0);
+            } else if roll < 0.82 {
+                let (merchant, amount) = (self.random_merchant(), self.rng.log_uniform(500.0, 100000.0));
+                self.push(step, "CASH_IN", amount, &merchant, &customer, 0);
+            } else if roll < 0.99 {
+                let mut other = self.random_customer();
+                while other == customer {
+                    other = self.random_customer();
+                }
+                let amount = self.rng.log_uniform(100.0, 50000.0);
+                self.push(step, "TRANSFER", amount, &customer, &other, 0);
+            } else {
+                let (merchant, amount) = (self.random_merchant(), self.rng.log_uniform(10.0, 5000.0));
+                self.push(step, "DEBIT", amount, &customer, &merchant, 0);
+            }
+        }
+    }
+
+    // 2 to 4 victims send 8000 to 60000 each to a mule, which forwards 90-97% of it a step or two later
+    fn money_mule(&mut self) -> InjectedPattern {
+        let (mule, cash_out) = (self.fresh(), self.fresh());
+        let victims = self.rng.between(2, 4).min(self.config.customers);
+        let start = self.start_step(2);
+        let mut received = 0.0;
+        for i in self.rng.sample(self.config.customers, victims) {
+            let amount = self.rng.uniform(8000.0, 60000.0);
+            received += amount;
+            let victim = self.customer(i);
+            self.push(start, "TRANSFER", amount, &victim, &mule, 1);
+        }
+        let end = start + self.rng.between(1, 2) as u32;
+        let forwarded = cents(received * self.rng.uniform(0.90, 0.97));
+        self.push(end, "CASH_OUT", forwarded, &mule, &cash_out, 1);
+        InjectedPattern { typology: Typology::MoneyMule, accounts: vec![mule, cash_out], steps: (self.clamp(start), self.clamp(end)), transactions: victims + 1 }
+    }
+
+    // 8 to 14 customers each send 500 to 9000 to a collector over a few steps,
+    // and half of the collectors pass 10-20% of it on once
+    fn collector(&mut self) -> InjectedPattern {
+        let collector = self.fresh();
+        let mut accounts = vec![collector.clone()];
+        let senders = self.rng.between(8, 14).min(self.config.customers);
+        let start = self.start_step(5);
+        let mut received = 0.0;
+        let mut end = start;
+        for i in self.rng.sample(self.config.customers, senders) {
+            let step = start + self.rng.below(5) as u32;
+            end = end.max(step);
+            let amount = self.rng.uniform(500.0, 9000.0);
+            received += amount;
+            let sender = self.customer(i);
+            self.push(step, "TRANSFER", amount, &sender, &collector, 1);
+        }
+        let mut transactions = senders;
+        if self.rng.next_f64() < 0.5 {
+            let sink = self.fresh();
+            let amount = cents(received * self.rng.uniform(0.10, 0.20));
+            self.push(end, "CASH_OUT", amount, &collector, &sink, 1);
+            accounts.push(sink);
+            transactions += 1;
+        }
+        InjectedPattern { typology: Typology::Collector, accounts, steps: (self.clamp(start), self.clamp(end)), transactions }
+    }
+
+    // 10000 to 100000 goes around a ring of 3 to 5 accounts, one hop per step, losing 1-2% per hop
+    fn cycle(&mut self) -> InjectedPattern {
+        let size = self.rng.between(3, 5);
+        let accounts: Vec<String> = (0..size).map(|_| self.fresh()).collect();
+        let start = self.start_step(size as u32 - 1);
+        let mut amount = self.rng.uniform(10000.0, 100000.0);
+        for hop in 0..size {
+            let (from, to) = (accounts[hop].clone(), accounts[(hop + 1) % size].clone());
+            self.push(start + hop as u32, "TRANSFER", amount, &from, &to, 1);
+            amount = cents(amount * self.rng.uniform(0.98, 0.99));
+        }
+        InjectedPattern { typology: Typology::Cycle, accounts, steps: (self.clamp(start), self.clamp(start + size as u32 - 1)), transactions: size }
+    }
+
+    // 6 to 12 transfers of 90-99.9% of the reporting limit from one account to 2 to 4 others over a few steps
+    fn structuring(&mut self) -> InjectedPattern {
+        let source = self.fresh();
+        let targets = self.rng.between(2, 4);
+        let mut accounts = vec![source.clone()];
+        accounts.extend((0..targets).map(|_| self.fresh()));
+        let deposits = self.rng.between(6, 12);
+        let start = self.start_step(3);
+        let mut end = start;
+        let limit = self.config.structuring_limit;
+        for _ in 0..deposits {
+            let step = start + self.rng.below(4) as u32;
+            end = end.max(step);
+            let target = accounts[1 + self.rng.below(targets)].clone();
+            let amount = self.rng.uniform(limit * 0.9, limit * 0.999);
+            self.push(step, "TRANSFER", amount, &source, &target, 1);
+        }
+        InjectedPattern { typology: Typology::Structuring, accounts, steps: (self.clamp(start), self.clamp(end)), transactions: deposits }
+    }
+
+    // 50000 to 500000 hops from a source through `chain_length` accounts, one hop per step,
+    // losing 1-3% per hop, and is cashed out at a sink
+    fn layering(&mut self) -> InjectedPattern {
+        let hops = self.config.chain_length + 1;
+        let accounts: Vec<String> = (0..=hops).map(|_| self.fresh()).collect();
+        let start = self.start_step(hops as u32 - 1);
+        let mut amount = self.rng.uniform(50000.0, 500000.0);
+        for hop in 0..hops {
+            let kind = if hop + 1 == hops { "CASH_OUT" } else { "TRANSFER" };
+            let (from, to) = (accounts[hop].clone(), accounts[hop + 1].clone());
+            self.push(start + hop as u32, kind, amount, &from, &to, 1);
+            amount = cents(amount * self.rng.uniform(0.97, 0.99));
+        }
+        InjectedPattern { typology: Typology::Layering, accounts, steps: (self.clamp(start), self.clamp(start + hops as u32 - 1)), transactions: hops }
+    }
+
+    // fills in the balance columns. every account starts with a random balance plus whatever it
+    // needs to never go below zero, so every transaction adds up on both sides
+    fn settle_balances(&mut self) {
+        let mut lowest: HashMap<&str, (f64, f64)> = HashMap::new();
+        for transaction in &self.transactions {
+            let entry = lowest.entry(transaction.name_orig.as_str()).or_insert((0.0, 0.0));
+            entry.0 -= transaction.amount;
+            entry.1 = entry.1.min(entry.0);
+            lowest.entry(transaction.name_dest.as_str()).or_insert((0.0, 0.0)).0 += transaction.amount;
+        }
+        let deficit: HashMap<String, f64> = lowest.into_iter().map(|(name, (_, low))| (name.to_string(), -low)).collect();
+
+        let mut balances: HashMap<String, f64> = HashMap::new();
+        for transaction in &mut self.transactions {
+            for name in [&transaction.name_orig, &transaction.name_dest] {
+                if !balances.contains_key(name) {
+                    let opening = cents(self.rng.log_uniform(100.0, 50000.0) + deficit[name]);
+                    balances.insert(name.clone(), opening);
+                }
+            }
+            let old_orig = balances[&transaction.name_orig];
+            let new_orig = cents(old_orig - transaction.amount);
+            balances.insert(transaction.name_orig.clone(), new_orig);
+            let old_dest = balances[&transaction.name_dest];
+            let new_dest = cents(old_dest + transaction.amount);
+            balances.insert(transaction.name_dest.clone(), new_dest);
+            transaction.balances = Some(Balances { old_orig, new_orig, old_dest, new_dest });
+        }
+    }
+}
+
+impl SyntheticDataset {
+    // generates a transaction stream: the background activity, then every injected pattern,
+    // sorted by step (a stable sort, so the order within a step is the generation order)
+    // takes in `config` - the pool sizes, the number of each pattern and the seed
+    // returns the transactions and the injected patterns
+    pub fn generate(config: &GeneratorConfig) -> Self {
+        let mut generator = Generator {
+            config,
+            rng: SplitMix64::new(config.seed),
+            transactions: Vec::new(),
+            next_account: config.customers,
+        };
+        generator.background();
+
+        let mut patterns = Vec::new();
+        if config.customers > 0 {
+            for _ in 0..config.mules {
+                patterns.push(generator.money_mule());
+            }
+            for _ in 0..config.collectors {
+                patterns.push(generator.collector());
+            }
+        }
+        for _ in 0..config.cycles {
+            patterns.push(generator.cycle());
+        }
+        for _ in 0..config.structuring {
+            patterns.push(generator.structuring());
+        }
+        for _ in 0..config.layering {
+            patterns.push(generator.layering());
+        }
+
+        generator.transactions.sort_by_key(|transaction| transaction.step);
+        generator.settle_balances();
+        SyntheticDataset { transactions: generator.transactions, patterns }
+    }
+
+    // builds a transaction graph from the generated transactions
+    pub fn to_graph(&self) -> TransactionGraph {
+        let mut graph = TransactionGraph::new();
+        for transaction in &self.transactions {
+            graph.add_transaction(transaction.clone());
+        }
+        graph
+    }
+
+    // get the central account of every injected pattern of one typology (the mule, the collector, ...)
+    pub fn central_accounts(&self, typology: Typology) -> Vec<&str> {
+        self.patterns.iter()
+            .filter(|pattern| pattern.typology == typology)
+            .map(|pattern| pattern.accounts[0].as_str())
+            .collect()
+    }
+
+    // writes the transactions as csv in the layout of the cleaned dataset, with the balance
+    // columns after isFraud, so the file can be read back with read_transaction_dataset.
+    // takes in `out` - where to write the table
+    // returns an i/o error if writing fails
+    pub fn write_csv<W: Write>(&self, mut out: W) -> Result<(), FraudError> {
+        writeln!(out, "step,type,amount,nameOrig,nameDest,isFraud,oldbalanceOrg,newbalanceOrig,oldbalanceDest,newbalanceDest")?;
+        for t in &self.transactions {
+            let b = t.balances.unwrap_or_default();
+            writeln!(out, "{},{},{:.2},{},{},{},{:.2},{:.2},{:.2},{:.2}",
+                t.step, t.r#type, t.amount, t.name_orig, t.name_dest, t.is_fraud,
+                b.old_orig, b.new_orig, b.old_dest, b.new_dest)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
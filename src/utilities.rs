@@ -1,10 +1,13 @@
 // utility functions and structs for the financial fraud detection system.
 // provides timing, data loading, and error handling functionality.
 use std::time::Instant;
-use std::error::Error;
 use std::fs::File;
+use std::ops::Range;
+use std::thread;
 use csv::ReaderBuilder;
-use crate::graph::{Transaction, TransactionGraph};
+use crate::error::FraudError;
+use crate::graph::{Balances, TransactionGraph, TransactionRecord};
+use crate::intern::{AccountId, AccountTable};
 
 // raii timer for measuring and reporting execution duration of code sections.
 // automatically reports elapsed time when the timer goes out of scope.
@@ -36,65 +39,255 @@ impl Drop for Timer {
     }
 }
 
-// creates a transaction from a csv record without using serde.
-// takes in `record` as an argument- csv record containing transaction data
-// returns a result containing either a transaction or an error message
-fn transaction_from_record(record: &csv::StringRecord) -> Result<Transaction, Box<dyn Error>> {
-    if record.len() < 6 {
-        return Err(format!("Not enough fields in record: expected 6, got {}", record.len()).into());
+// resolves a requested thread count.
+// takes in `threads` as an argument- the requested count, where 0 means one thread per available core
+// returns the number of threads to actually use (always at least 1)
+pub fn resolve_threads(threads: usize) -> usize {
+    if threads == 0 {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        threads
     }
-    
-    let step = record[0].parse::<u32>()
-        .map_err(|e| format!("Failed to parse step: {}", e))?;
-        
+}
+
+// splits 0..len into one contiguous range per thread and runs `f` on each range in a scoped thread.
+// the results are concatenated in range order, so the output is identical to calling `f(0..len)`
+// whenever `f` handles each index independently. with one thread no thread is spawned.
+// takes in `len` - the number of items, `threads` - the resolved thread count,
+// and `f` - the work to run on each range
+// returns the concatenated results of every range
+pub fn map_ranges<R, F>(len: usize, threads: usize, f: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(Range<usize>) -> Vec<R> + Sync,
+{
+    let threads = threads.clamp(1, len.max(1));
+    if threads == 1 {
+        return f(0..len);
+    }
+
+    let chunk = len.div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let range = (i * chunk).min(len)..((i + 1) * chunk).min(len);
+                scope.spawn(move || f(range))
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(len);
+        for handle in handles {
+            results.extend(handle.join().expect("worker thread panicked"));
+        }
+        results
+    })
+}
+
+// the columns expected in the cleaned dataset, in order
+const EXPECTED_COLUMNS: [&str; 6] = ["step", "type", "amount", "nameOrig", "nameDest", "isFraud"];
+
+// the optional paysim balance columns, found by name anywhere after the expected columns
+const BALANCE_COLUMNS: [&str; 4] = ["oldbalanceOrg", "newbalanceOrig", "oldbalanceDest", "newbalanceDest"];
+
+// normalizes a column name so `nameOrig`, `name_orig` and `NAMEORIG` all compare equal
+fn normalize_column(name: &str) -> String {
+    name.trim().replace('_', "").to_lowercase()
+}
+
+// checks that the csv header matches the layout of the cleaned dataset.
+// takes in `headers` as an argument- the header record of the csv file
+// returns a schema error naming the first column that does not match
+pub(crate) fn validate_headers(headers: &csv::StringRecord) -> Result<(), FraudError> {
+    if headers.len() < EXPECTED_COLUMNS.len() {
+        return Err(FraudError::Schema(format!(
+            "expected {} columns, found {}", EXPECTED_COLUMNS.len(), headers.len())));
+    }
+
+    for (i, expected) in EXPECTED_COLUMNS.iter().enumerate() {
+        if normalize_column(&headers[i]) != normalize_column(expected) {
+            return Err(FraudError::Schema(format!(
+                "column {} should be `{}`, found `{}`", i + 1, expected, &headers[i])));
+        }
+    }
+
+    Ok(())
+}
+
+// finds the positions of the four balance columns in the csv header.
+// takes in `headers` as an argument- the header record of the csv file
+// returns the positions in BALANCE_COLUMNS order, or None unless all four are present
+pub(crate) fn balance_columns(headers: &csv::StringRecord) -> Option<[usize; 4]> {
+    let mut positions = [0; 4];
+    for (position, expected) in positions.iter_mut().zip(BALANCE_COLUMNS) {
+        *position = (EXPECTED_COLUMNS.len()..headers.len())
+            .find(|i| normalize_column(&headers[*i]) == normalize_column(expected))?;
+    }
+    Some(positions)
+}
+
+// parses a single field, reporting the column and row on failure
+fn parse_field<T>(record: &csv::StringRecord, index: usize, row: u64) -> Result<T, FraudError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    parse_column(record, index, EXPECTED_COLUMNS[index], row)
+}
+
+// parses the field at `index`, reporting it as `column` on failure
+fn parse_column<T>(record: &csv::StringRecord, index: usize, column: &str, row: u64) -> Result<T, FraudError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    record[index].parse::<T>().map_err(|e| FraudError::Parse {
+        column: column.to_string(),
+        row,
+        message: e.to_string(),
+    })
+}
+
+// parses the balance fields of a record.
+// takes in `record` - the csv record, `columns` - the positions found by balance_columns, and `row` - its line number
+// returns None if the row leaves every balance field empty, or a parse error if one is malformed
+fn parse_balances(record: &csv::StringRecord, columns: [usize; 4], row: u64) -> Result<Option<Balances>, FraudError> {
+    if columns.iter().all(|i| record.get(*i).is_none_or(|field| field.trim().is_empty())) {
+        return Ok(None);
+    }
+    if let Some(missing) = columns.iter().position(|i| *i >= record.len()) {
+        return Err(FraudError::Schema(format!(
+            "row {} has no `{}` field", row, BALANCE_COLUMNS[missing])));
+    }
+    Ok(Some(Balances {
+        old_orig: parse_column(record, columns[0], BALANCE_COLUMNS[0], row)?,
+        new_orig: parse_column(record, columns[1], BALANCE_COLUMNS[1], row)?,
+        old_dest: parse_column(record, columns[2], BALANCE_COLUMNS[2], row)?,
+        new_dest: parse_column(record, columns[3], BALANCE_COLUMNS[3], row)?,
+    }))
+}
+
+// reads only the step of a csv record, so a caller can reject the row before record_from_csv
+// interns its account names.
+// takes in `record` - the csv record
+// returns the step, or a schema error if the row is too short or a parse error if the step is malformed
+pub(crate) fn step_from_csv(record: &csv::StringRecord) -> Result<u32, FraudError> {
+    let row = record.position().map(|p| p.line()).unwrap_or(0);
+    if record.len() < EXPECTED_COLUMNS.len() {
+        return Err(FraudError::Schema(format!(
+            "not enough fields on row {}: expected {}, got {}", row, EXPECTED_COLUMNS.len(), record.len())));
+    }
+    parse_field::<u32>(record, 0, row)
+}
+
+// creates a transaction record from a csv record without using serde.
+// the numeric fields are parsed first so a bad row never adds accounts to the graph,
+// then the account names are interned straight from the record without allocating.
+// shared by the batch loader and the streaming mode, so both parse fields the same way.
+// takes in `record` as an argument- csv record containing transaction data,
+// `balance_columns` - where the balance columns are, if the file has them,
+// and `accounts` - the account table the names are interned into
+// returns a result containing either a transaction record or the reason it could not be parsed
+pub(crate) fn record_from_csv(record: &csv::StringRecord, balance_columns: Option<[usize; 4]>, accounts: &mut AccountTable) -> Result<TransactionRecord, FraudError> {
+    let row = record.position().map(|p| p.line()).unwrap_or(0);
+    let step = step_from_csv(record)?;
     let r#type = record[1].to_string();
-    
-    let amount = record[2].parse::<f64>()
-        .map_err(|e| format!("Failed to parse amount: {}", e))?;
-        
-    let name_orig = record[3].to_string();
-    let name_dest = record[4].to_string();
-    
-    let is_fraud = record[5].parse::<u8>()
-        .map_err(|e| format!("Failed to parse is_fraud: {}", e))?;
+    let amount = parse_field::<f64>(record, 2, row)?;
+    let is_fraud = parse_field::<u8>(record, 5, row)?;
+    let balances = match balance_columns {
+        Some(columns) => parse_balances(record, columns, row)?,
+        None => None,
+    };
+    let name_orig = accounts.intern(&record[3]);
+    let name_dest = accounts.intern(&record[4]);
         
-    Ok(Transaction {
+    Ok(TransactionRecord {
         step,
         r#type,
         amount,
         name_orig,
         name_dest,
         is_fraud,
+        balances,
     })
 }
 
 // loads transaction data from a csv file and builds a transaction graph.
 // uses manual parsing instead of serde deserialization.
+// if the file also has the oldbalanceOrg, newbalanceOrig, oldbalanceDest and newbalanceDest
+// columns (in any order after the expected ones), every transaction carries its balances.
 // takes in `file_path` as an argument- path to the csv file containing transaction data
 // returns a result containing either a populated transactiongraph or an error
-// returns an error if the file cannot be opened or if csv parsing fails
-pub fn read_transaction_dataset(file_path: &str) -> Result<TransactionGraph, Box<dyn Error>> {
+// returns an error if the file cannot be opened or the header does not match the expected schema.
+// malformed rows are reported as warnings and skipped
+pub fn read_transaction_dataset(file_path: &str) -> Result<TransactionGraph, FraudError> {
+    let mut graph = TransactionGraph::new();
+    append_transaction_dataset(&mut graph, file_path)?;
+    Ok(graph)
+}
+
+// loads transaction data from a csv file without building a transaction graph, for callers
+// that only need the records, such as CsrGraph::from_account_records.
+// takes in `file_path` - path to the csv file containing transaction data
+// returns the interned accounts and the transactions in file order, with the same errors and
+// warnings as read_transaction_dataset
+pub fn read_transaction_records(file_path: &str) -> Result<(AccountTable, Vec<TransactionRecord>), FraudError> {
+    let mut accounts = AccountTable::new();
+    let mut transactions = Vec::new();
+    for_each_csv_record(file_path, |record, balance_columns| {
+        transactions.push(record_from_csv(record, balance_columns, &mut accounts)?);
+        Ok(())
+    })?;
+    Ok((accounts, transactions))
+}
+
+// loads a batch of transactions from a csv file into an existing transaction graph.
+// takes in `graph` - the graph to extend, and `file_path` - the csv file holding the new batch
+// returns the accounts touched by the batch (sorted, without duplicates), ready to be passed
+// to FraudAnalysis::apply_batch, or an error if the file cannot be read or has the wrong schema
+pub fn append_transaction_dataset(graph: &mut TransactionGraph, file_path: &str) -> Result<Vec<AccountId>, FraudError> {
+    let mut touched = Vec::new();
+    for_each_csv_record(file_path, |record, balance_columns| {
+        let transaction = record_from_csv(record, balance_columns, &mut graph.accounts)?;
+        touched.push(transaction.name_orig);
+        touched.push(transaction.name_dest);
+        graph.add_record(transaction);
+        Ok(())
+    })?;
+
+    touched.sort_unstable();
+    touched.dedup();
+    Ok(touched)
+}
+
+// opens a dataset, checks its header and hands every row to `add` with the balance columns.
+// rows that cannot be read or that `add` rejects are reported as warnings and skipped.
+// takes in `file_path` - the csv file, and `add` - called with every row in file order
+// returns an error if the file cannot be opened or the header does not match the expected schema
+fn for_each_csv_record<F>(file_path: &str, mut add: F) -> Result<(), FraudError>
+where
+    F: FnMut(&csv::StringRecord, Option<[usize; 4]>) -> Result<(), FraudError>,
+{
     let file = File::open(file_path)?;
     let mut reader = ReaderBuilder::new()
         .has_headers(true)
         .from_reader(file);
 
-    let mut graph = TransactionGraph::new();
-    
+    validate_headers(reader.headers()?)?;
+    let balance_columns = balance_columns(reader.headers()?);
+
     // parsing the csv file
     for result in reader.records() {
         match result {
             Ok(record) => {
-                match transaction_from_record(&record) {
-                    Ok(transaction) => graph.add_transaction(transaction),
-                    Err(e) => eprintln!("Warning: failed to parse record: {}", e),
+                if let Err(e) = add(&record, balance_columns) {
+                    eprintln!("Warning: skipping record: {}", e);
                 }
             },
-            Err(e) => eprintln!("Warning: error reading CSV record: {}", e),
+            Err(e) => eprintln!("Warning: {}", FraudError::from(e)),
         }
     }
-
-    Ok(graph)
+    Ok(())
 }
 
 // prints an error message to stderr.
@@ -1,19 +1,17 @@
 // integration tests for the transaction fraud detection system.
 // tests graph construction, account metric calculations, and fraud detection algorithms.
 
-// allow dead code in test context
-#![allow(dead_code)]
-
-// tests for fraud detection core components
-mod graph {
-    include!("../src/graph.rs");
-}
-mod analysis {
-    include!("../src/analysis.rs");
-}
-
-use graph::{TransactionGraph, Transaction};
-use analysis::FraudAnalysis;
+// the tests use the public api of the fraud_detection library
+use std::path::Path;
+use fraud_detection::{
+    AccountClass, AccountClassifier, AccountId, AccountLists, AccountTable, AlertKind, AnalysisConfig, Balances, ClassThresholds, CollectorThresholds, CommunityRanking, ConnectionOptions, CsrGraph, Detector, DiGraph, Direction, EgoOptions, FraudAnalysis, FraudError,
+    FeatureMatrix, GraphError, GraphExport, LogisticModel, NodeIndex, OnlineDetector, ReciprocityThresholds, StreamConfig, TrainingOptions, TransactionFilter, Transaction, TransactionGraph, load_snapshot, read_transaction_dataset, read_transaction_records, save_snapshot,
+};
+use fraud_detection::lists::parse_account_list;
+use fraud_detection::snapshot::load_or_build;
+use fraud_detection::streaming::run_stream;
+use fraud_detection::sweep::{ThresholdParameter, write_sweep_points, write_sweep_summary};
+use fraud_detection::synthetic::{GeneratorConfig, SyntheticDataset, Typology};
 
 // creates a standardized transaction for testing purposes. 
 // arguments
@@ -30,6 +28,7 @@ fn create_transaction(amount: f64, from: &str, to: &str) -> Transaction {
         name_orig: from.to_string(),
         name_dest: to.to_string(),
         is_fraud: 0,
+        balances: None,
     }
 }
 
@@ -74,9 +73,9 @@ fn test_collector_detection() {
     let collectors = fraud_analysis.identify_collector_accounts();
     
     // check detection
-    let collector_names: Vec<&String> = collectors.iter().map(|(account, _)| account).collect();
-    assert!(collector_names.contains(&&"Collector".to_string()), "Failed to detect collector account");
-    assert!(!collector_names.contains(&&"Normal".to_string()), "Incorrectly flagged normal account as collector");
+    let collector_names: Vec<&str> = collectors.iter().map(|(account, _)| graph.account_name(*account)).collect();
+    assert!(collector_names.contains(&"Collector"), "Failed to detect collector account");
+    assert!(!collector_names.contains(&"Normal"), "Incorrectly flagged normal account as collector");
 }
 
 // tests that the system correctly identifies money mule accounts.
@@ -99,7 +98,1166 @@ fn test_money_mule_detection() {
     let mules = fraud_analysis.identify_money_mule_accounts();
     
     // check detection
-    let mule_names: Vec<&String> = mules.iter().map(|(account, _)| account).collect();
-    assert!(mule_names.contains(&&"Mule".to_string()), "Failed to detect money mule account");
-    assert!(!mule_names.contains(&&"Normal".to_string()), "Incorrectly flagged normal account as money mule");
-} 
\ No newline at end of file
+    let mule_names: Vec<&str> = mules.iter().map(|(account, _)| graph.account_name(*account)).collect();
+    assert!(mule_names.contains(&"Mule"), "Failed to detect money mule account");
+    assert!(!mule_names.contains(&"Normal"), "Incorrectly flagged normal account as money mule");
+} 
+
+// writes `contents` to a uniquely named csv file in the temp directory.
+// returns the path of the written file
+fn write_temp_csv(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!("fraud_test_{}_{}.csv", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+// verifies that loading reports typed errors callers can match on.
+// a bad header is a schema error and a missing file is an i/o error.
+#[test]
+fn test_loader_error_kinds() {
+    let path = write_temp_csv("bad_header", "step,type,amount,from,to,isFraud\n1,TRANSFER,10.0,A,B,0\n");
+    match read_transaction_dataset(&path) {
+        Err(FraudError::Schema(msg)) => assert!(msg.contains("nameOrig"), "unexpected message: {}", msg),
+        other => panic!("expected a schema error, got {:?}", other.map(|g| g.transactions.len())),
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    match read_transaction_dataset("this/file/does/not/exist.csv") {
+        Err(FraudError::Io(_)) => {}
+        other => panic!("expected an i/o error, got {:?}", other.map(|g| g.transactions.len())),
+    }
+
+    // malformed rows are skipped rather than failing the whole load
+    let path = write_temp_csv("bad_row", "step,type,amount,nameOrig,nameDest,isFraud\n1,TRANSFER,abc,A,B,0\n2,CASH_OUT,5.0,B,C,1\n");
+    let graph = read_transaction_dataset(&path).unwrap();
+    assert_eq!(graph.transactions.len(), 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+// verifies that the csr form keeps every edge in both directions
+// and that metrics computed on it match the transactions.
+#[test]
+fn test_csr_adjacency_and_metrics() {
+    let mut graph = TransactionGraph::new();
+    graph.add_transaction(create_transaction(100.0, "A", "B"));
+    graph.add_transaction(create_transaction(200.0, "A", "C"));
+    graph.add_transaction(create_transaction(50.0, "B", "C"));
+
+    let csr = graph.to_csr();
+    assert_eq!(csr.node_count(), 3);
+    assert_eq!(csr.edge_count(), 3);
+
+    // positions follow node insertion order: A=0, B=1, C=2
+    assert_eq!(graph.account_name(*csr.node(0)), "A");
+    assert_eq!(csr.out_neighbors(0), &[1, 2]);
+    assert_eq!(csr.out_weights(0), &[100.0, 200.0]);
+    assert_eq!(csr.in_neighbors(2), &[0, 1]);
+    assert_eq!(csr.in_weights(2), &[200.0, 50.0]);
+    assert_eq!(csr.in_degree(0), 0);
+
+    let metrics = graph.calculate_account_metrics();
+    let c = &metrics[&graph.account_id("C").unwrap()];
+    assert_eq!(c.incoming_count, 2);
+    assert_eq!(c.incoming_volume, 250.0);
+    assert_eq!(c.retention_rate, 1.0);
+    let b = &metrics[&graph.account_id("B").unwrap()];
+    assert_eq!((b.incoming_count, b.outgoing_count), (1, 1));
+    assert_eq!(b.retention_rate, 0.5);
+
+    // building from the records matches freezing the hash-map graph, with a repeated transfer keeping the latest amount
+    graph.add_transaction(create_transaction(75.0, "A", "B"));
+    let (from_records, from_digraph) = (graph.to_csr(), CsrGraph::from_digraph(&graph.graph));
+    assert_eq!(from_records.edge_count(), 3);
+    for pos in 0..from_records.node_count() {
+        assert_eq!(from_records.node(pos), from_digraph.node(pos));
+        assert_eq!(from_records.out_neighbors(pos), from_digraph.out_neighbors(pos));
+        assert_eq!(from_records.out_weights(pos), from_digraph.out_weights(pos));
+        assert_eq!(from_records.in_weights(pos), from_digraph.in_weights(pos));
+    }
+    assert_eq!(from_records.out_weights(0), &[75.0, 200.0]);
+
+    // reading a file straight into records gives the same csr as loading it into a graph first,
+    // including when a malformed row is skipped
+    let path = write_temp_csv("csr_records", "step,type,amount,nameOrig,nameDest,isFraud\n\
+        1,TRANSFER,100.0,A,B,0\n2,TRANSFER,abc,X,Y,0\n3,CASH_OUT,50.0,B,C,1\n4,TRANSFER,75.0,A,B,0\n5,TRANSFER,20.0,C,A,0\n");
+    let loaded = read_transaction_dataset(&path).unwrap();
+    let (accounts, transactions) = read_transaction_records(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (direct, frozen) = (CsrGraph::from_account_records(&accounts, &transactions), loaded.to_csr());
+    assert_eq!((direct.node_count(), direct.edge_count()), (frozen.node_count(), frozen.edge_count()));
+    assert_eq!(transactions.len(), loaded.transactions.len());
+    for pos in 0..direct.node_count() {
+        assert_eq!(accounts.name(*direct.node(pos)), loaded.account_name(*frozen.node(pos)));
+        assert_eq!(direct.out_neighbors(pos), frozen.out_neighbors(pos));
+        assert_eq!(direct.out_weights(pos), frozen.out_weights(pos));
+        assert_eq!(direct.in_weights(pos), frozen.in_weights(pos));
+    }
+}
+
+// verifies that account names are interned once and resolve back to the same handle.
+#[test]
+fn test_account_interning() {
+    let mut table = AccountTable::new();
+    let a = table.intern("C1625226992");
+    let b = table.intern("M1979787155");
+    assert_ne!(a, b);
+    assert_eq!(table.intern("C1625226992"), a);
+    assert_eq!(table.name(b), "M1979787155");
+    assert_eq!(table.get("C000"), None);
+
+    // enough names to force the lookup table to grow several times
+    for i in 0..1000 {
+        table.intern(&format!("C{}", i));
+    }
+    assert_eq!(table.len(), 1002);
+    assert_eq!(table.get("C999").map(|id| table.name(id)), Some("C999"));
+    assert_eq!(table.get("C1625226992"), Some(a));
+
+    // the graph stores each account once no matter how many transactions mention it
+    let mut graph = TransactionGraph::new();
+    graph.add_transaction(create_transaction(100.0, "A", "B"));
+    graph.add_transaction(create_transaction(200.0, "B", "A"));
+    assert_eq!(graph.accounts.len(), 2);
+    assert_eq!(graph.transactions[1].name_orig, graph.account_id("B").unwrap());
+}
+
+// builds a deterministic pseudo-random graph with `count` transactions over `accounts` accounts.
+// uses a small linear congruential generator so the graph is the same on every run.
+fn pseudo_random_graph(count: usize, accounts: u64) -> TransactionGraph {
+    let mut graph = TransactionGraph::new();
+    let mut state: u64 = 42;
+    let mut next = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    };
+    for _ in 0..count {
+        let from = format!("C{}", next() % accounts);
+        let to = format!("C{}", next() % accounts);
+        let amount = (next() % 50_000) as f64 + 0.37;
+        graph.add_transaction(create_transaction(amount, &from, &to));
+    }
+    graph
+}
+
+// verifies that the parallel path produces exactly the same metrics and detections
+// as the sequential one, for several thread counts.
+#[test]
+fn test_parallel_matches_sequential() {
+    let graph = pseudo_random_graph(5000, 400);
+    let sequential = FraudAnalysis::new(&graph);
+    let csr = graph.to_csr();
+    let sequential_metrics = csr.account_metrics();
+
+    for threads in [2, 3, 8, 0] {
+        assert_eq!(csr.account_metrics_parallel(threads), sequential_metrics);
+
+        let parallel = FraudAnalysis::with_config(&graph, AnalysisConfig { threads, ..AnalysisConfig::default() });
+        assert_eq!(parallel.identify_collector_accounts(), sequential.identify_collector_accounts());
+        assert_eq!(parallel.identify_money_mule_accounts(), sequential.identify_money_mule_accounts());
+    }
+}
+
+// verifies that a snapshot restores the same graph and that
+// corrupt or stale snapshots are rejected.
+#[test]
+fn test_snapshot_round_trip_and_rejection() {
+    let graph = pseudo_random_graph(300, 50);
+    let source = write_temp_csv("snapshot_source", "step,type,amount,nameOrig,nameDest,isFraud\n");
+    let snapshot = std::env::temp_dir().join(format!("fraud_test_{}_graph.snap", std::process::id()));
+
+    save_snapshot(&graph, &snapshot, Some(Path::new(&source))).unwrap();
+    let restored = load_snapshot(&snapshot, Some(Path::new(&source))).unwrap();
+    assert_eq!(restored.transactions.len(), graph.transactions.len());
+    assert_eq!(restored.node_map, graph.node_map);
+    assert_eq!(restored.graph.edge_count(), graph.graph.edge_count());
+    assert_eq!(restored.account_id("C7"), graph.account_id("C7"));
+    assert_eq!(restored.calculate_account_metrics(), graph.calculate_account_metrics());
+
+    // a custom classifier is saved with the graph, so its classes survive the round trip
+    let shops = AccountClassifier {
+        merchant_prefixes: vec!["Shop".to_string()],
+        digits_only: false,
+        customer_patterns: vec!["*@bank".to_string()],
+        ..AccountClassifier::default()
+    };
+    let mut custom = TransactionGraph::with_classifier(shops.clone());
+    custom.add_transaction(create_transaction(10.0, "C1", "Shop7"));
+    save_snapshot(&custom, &snapshot, None).unwrap();
+    let restored = load_snapshot(&snapshot, None).unwrap();
+    assert_eq!(restored.classifier(), &shops);
+    assert_eq!(restored.account_class(restored.account_id("Shop7").unwrap()), AccountClass::Merchant);
+    assert_eq!(restored.classifier().classify("alice@bank"), AccountClass::Customer);
+
+    // a snapshot written by load_or_build uses the classifier it was given, and a later load with another classifier reclassifies
+    let csv = write_temp_csv("snapshot_classes", "step,type,amount,nameOrig,nameDest,isFraud\n1,TRANSFER,10.0,C1,Shop7,0\n");
+    let built = load_or_build(Path::new(&csv), &snapshot, &shops).unwrap();
+    assert_eq!(built.account_class(built.account_id("Shop7").unwrap()), AccountClass::Merchant);
+    let reloaded = load_or_build(Path::new(&csv), &snapshot, &AccountClassifier::default()).unwrap();
+    assert_eq!(reloaded.account_class(reloaded.account_id("Shop7").unwrap()), AccountClass::Other);
+    std::fs::remove_file(&csv).unwrap();
+    save_snapshot(&graph, &snapshot, Some(Path::new(&source))).unwrap();
+
+    // flipping a payload byte breaks the checksum
+    let mut bytes = std::fs::read(&snapshot).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&snapshot, &bytes).unwrap();
+    assert!(matches!(load_snapshot(&snapshot, Some(Path::new(&source))), Err(FraudError::Snapshot(_))));
+
+    // changing the source csv makes the snapshot stale
+    save_snapshot(&graph, &snapshot, Some(Path::new(&source))).unwrap();
+    std::fs::write(&source, "step,type,amount,nameOrig,nameDest,isFraud\n1,TRANSFER,1.0,A,B,0\n").unwrap();
+    match load_snapshot(&snapshot, Some(Path::new(&source))) {
+        Err(FraudError::Snapshot(msg)) => assert!(msg.contains("stale")),
+        other => panic!("expected a stale snapshot error, got {:?}", other.map(|g| g.transactions.len())),
+    }
+
+    // a rewrite of the same size within the same second is noticed too
+    let second = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    let set_modified = |offset: u64| {
+        let file = std::fs::File::options().write(true).open(&source).unwrap();
+        file.set_modified(second + std::time::Duration::from_nanos(offset)).unwrap();
+    };
+    set_modified(100);
+    save_snapshot(&graph, &snapshot, Some(Path::new(&source))).unwrap();
+    std::fs::write(&source, "step,type,amount,nameOrig,nameDest,isFraud\n1,TRANSFER,2.0,A,B,0\n").unwrap();
+    set_modified(200);
+    assert!(matches!(load_snapshot(&snapshot, Some(Path::new(&source))), Err(FraudError::Snapshot(msg)) if msg.contains("stale")));
+
+    // a payload with a valid checksum but an account handle outside the string table is
+    // rejected instead of panicking: drop the last account name and re-sign the file
+    let mut dangling = TransactionGraph::new();
+    dangling.add_transaction(create_transaction(10.0, "FirstAcct", "LastAcct"));
+    save_snapshot(&dangling, &snapshot, None).unwrap();
+    let mut bytes = std::fs::read(&snapshot).unwrap();
+    let find = |bytes: &[u8], name: &str| {
+        let mut needle = (name.len() as u32).to_le_bytes().to_vec();
+        needle.extend_from_slice(name.as_bytes());
+        bytes.windows(needle.len()).position(|window| window == needle).unwrap()
+    };
+    let last = find(&bytes, "LastAcct");
+    bytes.drain(last..last + 4 + "LastAcct".len());
+    let count = find(&bytes, "FirstAcct") - 8;
+    bytes[count..count + 8].copy_from_slice(&1u64.to_le_bytes());
+    let header_len = 8 + 4 + 8 + 8 + 8 + 8;
+    let payload_len = (bytes.len() - header_len) as u64;
+    bytes[header_len - 16..header_len - 8].copy_from_slice(&payload_len.to_le_bytes());
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes[..header_len - 8].iter().chain(&bytes[header_len..]) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    bytes[header_len - 8..header_len].copy_from_slice(&hash.to_le_bytes());
+    std::fs::write(&snapshot, &bytes).unwrap();
+    match load_snapshot(&snapshot, None) {
+        Err(FraudError::Snapshot(msg)) => assert!(msg.contains("account handle 1"), "unexpected message: {}", msg),
+        other => panic!("expected an inconsistent snapshot error, got {:?}", other.map(|g| g.transactions.len())),
+    }
+
+    std::fs::remove_file(&snapshot).unwrap();
+    std::fs::remove_file(&source).unwrap();
+}
+
+// verifies that appending a batch updates only the touched accounts, matches a full
+// recomputation, and reports just the accounts whose flags changed.
+#[test]
+fn test_incremental_batch_updates() {
+    let mut graph = TransactionGraph::new();
+    for i in 1..=5 {
+        graph.add_transaction(create_transaction(1000.0 * i as f64, &format!("User{}", i), "Collector"));
+    }
+    graph.add_transaction(create_transaction(20000.0, "Source", "Mule"));
+    let mut analysis = FraudAnalysis::new(&graph);
+    assert!(analysis.identify_collector_accounts().is_empty());
+
+    // the sixth sender makes Collector a collector; Mule starts forwarding and becomes a mule
+    let touched = graph.add_batch(vec![
+        create_transaction(6000.0, "User6", "Collector"),
+        create_transaction(9000.0, "Mule", "Dest1"),
+        create_transaction(10000.0, "Mule", "Dest2"),
+    ]);
+    let changes = analysis.apply_batch(&graph, &touched);
+
+    let collector = graph.account_id("Collector").unwrap();
+    let mule = graph.account_id("Mule").unwrap();
+    let summary: Vec<(AccountId, Detector, bool)> = changes.iter().map(|c| (c.account, c.detector, c.flagged)).collect();
+    assert_eq!(summary, vec![(collector, Detector::Collector, true), (mule, Detector::MoneyMule, true)]);
+
+    // the incremental state matches a fresh analysis of the extended graph
+    let fresh = FraudAnalysis::new(&graph);
+    assert_eq!(analysis.identify_collector_accounts(), fresh.identify_collector_accounts());
+    assert_eq!(analysis.identify_money_mule_accounts(), fresh.identify_money_mule_accounts());
+    for (account, metrics) in graph.calculate_account_metrics() {
+        assert_eq!(analysis.metrics(account), Some(&metrics));
+    }
+
+    // a large outflow clears the collector flag again
+    let touched = graph.add_batch(vec![create_transaction(8000.0, "Collector", "Elsewhere")]);
+    let changes = analysis.apply_batch(&graph, &touched);
+    assert_eq!(changes.len(), 1);
+    assert!(!changes[0].flagged);
+}
+
+// verifies that the online detector alerts exactly when an account first matches
+// a pattern, raises each alert once, skips transactions that arrive out of order,
+// and reads quoted csv fields.
+#[test]
+fn test_streaming_alerts() {
+    let feed = "\
+step,type,amount,nameOrig,nameDest,isFraud
+1,TRANSFER,20000.0,Source,Mule,1
+2,CASH_OUT,19000.0,Mule,Dest1,1
+3,TRANSFER,500.0,Source,Mule,0
+1,TRANSFER,10.0,Late,Mule,0
+4,CASH_OUT,100.0,Mule,Dest2,0
+5,\"TRANSFER\",300.0,\"Shell, Ltd\",Dest3,0
+";
+    let mut detector = OnlineDetector::new(StreamConfig::default());
+    let mut alerts = Vec::new();
+    let consumed = run_stream(feed.as_bytes(), false, &mut detector, |detector, alert| {
+        alerts.push((alert.step, detector.accounts().name(alert.account).to_string(), alert.kind));
+    }).unwrap();
+
+    // the out-of-order step 1 line is skipped, and quoted fields parse as in the batch loader
+    assert_eq!(consumed, 5);
+    assert!(detector.accounts().get("Shell, Ltd").is_some());
+    // the rejected row leaves no trace in the account table
+    assert_eq!(detector.accounts().get("Late"), None);
+    assert_eq!(alerts, vec![
+        (2, "Mule".to_string(), AlertKind::TransferCashout),
+        (2, "Mule".to_string(), AlertKind::MoneyMule),
+    ]);
+
+    // processing directly reports out-of-order input as an error
+    let (known, late) = (detector.accounts().len(), Transaction { step: 1, ..create_transaction(1.0, "A", "B") });
+    assert!(matches!(detector.process(late), Err(FraudError::Schema(_))));
+    assert_eq!(detector.accounts().len(), known);
+}
+
+// verifies that the review deduplicates accounts across detectors, suppresses
+// allowlisted accounts with their reason, always reports denylisted accounts
+// (listing entries that match no account), and marks accounts that the previous run did not report.
+#[test]
+fn test_review_with_allow_and_deny_lists() {
+    let mut graph = TransactionGraph::new();
+    for i in 1..=6 {
+        graph.add_transaction(create_transaction(1000.0 * i as f64, &format!("User{}", i), "Merchant"));
+        graph.add_transaction(create_transaction(1000.0 * i as f64, &format!("Payer{}", i), "Collector"));
+    }
+    graph.add_transaction(create_transaction(20000.0, "Source", "Mule"));
+    graph.add_transaction(create_transaction(19000.0, "Mule", "Dest"));
+
+    let mut lists = AccountLists::new();
+    lists.allow = parse_account_list("# reviewed merchants\nMerchant, known merchant\n", "allowlisted");
+    lists.deny = parse_account_list("Dest\nGhost, closed last year\n", "denylisted");
+    lists.previous = Some(["Collector".to_string()].into_iter().collect());
+
+    let mut analysis = FraudAnalysis::new(&graph);
+    analysis.set_lists(lists);
+    let review = analysis.review(&graph.accounts);
+
+    let reported: Vec<(&str, Vec<Detector>, bool)> = review.reported.iter()
+        .map(|f| (graph.account_name(f.account), f.detectors.clone(), f.is_new))
+        .collect();
+    assert!(reported.contains(&("Collector", vec![Detector::Collector], false)));
+    assert!(reported.contains(&("Mule", vec![Detector::MoneyMule], true)));
+    // denylisted with no detector match
+    assert!(reported.contains(&("Dest", vec![], true)));
+    assert_eq!(reported.len(), 3);
+    let dest = review.reported.iter().find(|f| graph.account_name(f.account) == "Dest").unwrap();
+    assert_eq!(dest.denylisted.as_deref(), Some("denylisted"));
+
+    assert_eq!(review.suppressed.len(), 1);
+    assert_eq!(graph.account_name(review.suppressed[0].account), "Merchant");
+    assert_eq!(review.suppressed[0].reason, "known merchant");
+
+    // an entry naming no known account is listed instead of dropped
+    assert_eq!(review.unmatched_denylist, [("Ghost".to_string(), "closed last year".to_string())]);
+
+    // a denylisted account without transactions in the analyzed graph is still reported, with empty metrics
+    let mut lists = AccountLists::new();
+    lists.deny = parse_account_list("Idle\n", "denylisted");
+    let mut accounts = graph.accounts.clone();
+    let idle = accounts.intern("Idle");
+    analysis.set_lists(lists);
+    let review = analysis.review(&accounts);
+    let flagged = review.reported.iter().find(|f| f.account == idle).unwrap();
+    assert_eq!(flagged.metrics.incoming_count, 0);
+    assert!(review.unmatched_denylist.is_empty());
+}
+
+// verifies that the exporters write node metrics, detector flags and edge attributes,
+// and that a k-hop neighborhood export only contains the nearby accounts.
+#[test]
+fn test_graph_export_formats() {
+    let mut graph = TransactionGraph::new();
+    graph.add_transaction(create_transaction(20000.0, "Source", "Mule"));
+    // an earlier, fraud-labeled transfer on the mule's outgoing edge
+    graph.add_transaction(Transaction { step: 5, is_fraud: 1, ..create_transaction(500.0, "Mule", "Dest") });
+    graph.add_transaction(Transaction { step: 7, r#type: "CASH_OUT".to_string(), ..create_transaction(19000.0, "Mule", "Dest") });
+    graph.add_transaction(create_transaction(50.0, "Dest", "Far"));
+    graph.add_transaction(create_transaction(10.0, "Far", "Farther"));
+    let analysis = FraudAnalysis::new(&graph);
+
+    let export = GraphExport::new(&graph, &analysis);
+    let mut graphml = Vec::new();
+    export.write_graphml(&mut graphml).unwrap();
+    let graphml = String::from_utf8(graphml).unwrap();
+    assert_eq!(graphml.matches("<node ").count(), 5);
+    assert_eq!(graphml.matches("<edge ").count(), 4);
+    assert!(graphml.contains(r#"<data key="name">Mule</data>"#));
+    assert!(graphml.contains(r#"<data key="is_money_mule">true</data>"#));
+    // edges sum every transfer between the pair and are fraud if any transfer is
+    assert!(graphml.contains(r#"<data key="amount">19500</data>"#));
+    assert!(graphml.contains(r#"<data key="transfers">2</data>"#));
+    assert!(graphml.contains(r#"<data key="is_fraud">true</data>"#));
+    // the types used on the pair, and the latest transfer the mule's metrics are computed from
+    assert!(graphml.contains(r#"<data key="types">CASH_OUT|TRANSFER</data>"#));
+    assert!(graphml.contains(r#"<key id="types" for="edge" attr.name="types" attr.type="string"/>"#));
+    assert!(graphml.contains(r#"<data key="weight">19000</data>"#));
+    assert_eq!(analysis.metrics(graph.account_id("Mule").unwrap()).unwrap().outgoing_volume, 19000.0);
+
+    let mut dot = Vec::new();
+    export.write_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph transactions {"));
+    assert!(dot.contains(r#"amount=19500 weight=19000 transfers=2 first_step=5 last_step=7 types="CASH_OUT|TRANSFER" is_fraud=true"#));
+
+    // everything within one hop of the mule
+    let mule = graph.node_map[&graph.account_id("Mule").unwrap()];
+    let neighborhood = graph.graph.k_hop_neighborhood(&[mule], 1);
+    let (mut nodes, mut edges) = (Vec::new(), Vec::new());
+    GraphExport::new(&graph, &analysis).with_nodes(&neighborhood).write_csv(&mut nodes, &mut edges).unwrap();
+    let nodes = String::from_utf8(nodes).unwrap();
+    let edges = String::from_utf8(edges).unwrap();
+    assert!(nodes.starts_with("Id,Label,incoming_count,"));
+    assert_eq!(nodes.lines().count(), 4);
+    assert!(!nodes.contains("Far"));
+    // the edge table has one row per transaction
+    assert_eq!(edges.lines().count(), 4);
+    assert!(edges.lines().any(|line| line.ends_with(",Directed,19000,7,CASH_OUT,0")));
+    assert!(edges.lines().any(|line| line.ends_with(",Directed,500,5,TRANSFER,1")));
+}
+
+// verifies ego network extraction: direction, hop limit, step range and node budget,
+// and that the result is an induced subgraph with its own account table.
+#[test]
+fn test_ego_network_extraction() {
+    let mut graph = TransactionGraph::new();
+    // Source -> Seed -> Mid -> Far, plus a late transfer Seed -> Late and a back edge Mid -> Seed
+    graph.add_transaction(create_transaction(100.0, "Source", "Seed"));
+    graph.add_transaction(create_transaction(90.0, "Seed", "Mid"));
+    graph.add_transaction(create_transaction(80.0, "Mid", "Far"));
+    graph.add_transaction(create_transaction(5.0, "Mid", "Seed"));
+    graph.add_transaction(Transaction { step: 50, ..create_transaction(70.0, "Seed", "Late") });
+    let seed = graph.account_id("Seed").unwrap();
+    let names = |sub: &TransactionGraph| {
+        let mut names: Vec<String> = sub.accounts.iter().map(|(_, name)| name.to_string()).collect();
+        names.sort();
+        names
+    };
+
+    let both = graph.ego_network(&[seed], &EgoOptions::default());
+    assert_eq!(names(&both), ["Far", "Late", "Mid", "Seed", "Source"]);
+    assert_eq!(both.transactions.len(), 5);
+
+    let out = graph.ego_network(&[seed], &EgoOptions { direction: Direction::Outgoing, hops: 1, ..Default::default() });
+    assert_eq!(names(&out), ["Late", "Mid", "Seed"]);
+    // induced: the back edge between included accounts is kept
+    assert_eq!(out.graph.edge_count(), 3);
+
+    let incoming = graph.ego_network(&[seed], &EgoOptions { direction: Direction::Incoming, ..Default::default() });
+    assert_eq!(names(&incoming), ["Mid", "Seed", "Source"]);
+
+    let early = graph.ego_network(&[seed], &EgoOptions { steps: Some(1..=10), ..Default::default() });
+    assert_eq!(names(&early), ["Far", "Mid", "Seed", "Source"]);
+
+    // budget of 3: the seed, then the first two of {Late, Mid, Source} in handle order
+    let capped = graph.ego_network(&[seed], &EgoOptions { max_nodes: Some(3), ..Default::default() });
+    assert_eq!(capped.node_map.len(), 3);
+    assert!(capped.account_id("Seed").is_some());
+    assert!(capped.account_id("Far").is_none());
+
+    let metrics = FraudAnalysis::new(&out);
+    assert_eq!(metrics.metrics(out.account_id("Seed").unwrap()).unwrap().outgoing_count, 2);
+}
+
+// verifies that filtered views keep only the matching transactions and edges,
+// and that the analysis of a view only sees those transactions.
+#[test]
+fn test_transaction_filter_views() {
+    let mut graph = TransactionGraph::new();
+    for i in 0..6 {
+        // transfers over 50k between steps 100 and 200 make a collector
+        graph.add_transaction(Transaction { step: 100 + i, ..create_transaction(60000.0, &format!("Payer{}", i), "Target") });
+        // the same pattern, but cash outs or too small, should not
+        graph.add_transaction(Transaction { step: 150, r#type: "CASH_OUT".to_string(), ..create_transaction(60000.0, &format!("Cash{}", i), "Other") });
+        graph.add_transaction(Transaction { step: 150, ..create_transaction(100.0, &format!("Small{}", i), "Other") });
+    }
+    graph.add_transaction(Transaction { step: 300, ..create_transaction(60000.0, "Target", "Late") });
+
+    let filter = TransactionFilter {
+        steps: Some(100..=200),
+        amounts: Some(50000.0..=f64::INFINITY),
+        types: Some(vec!["TRANSFER".to_string()]),
+        ..TransactionFilter::new()
+    };
+    let view = graph.filter(&filter);
+    assert_eq!(view.transactions.len(), 6);
+    assert_eq!(view.node_map.len(), 7);
+    assert!(view.account_id("Other").is_none());
+    let collectors = FraudAnalysis::new(&view).identify_collector_accounts();
+    assert_eq!(collectors.len(), 1);
+    assert_eq!(view.account_name(collectors[0].0), "Target");
+
+    // custom transaction and edge predicates
+    let even_steps = graph.filter(&TransactionFilter::new()
+        .matching(|r| r.step % 2 == 0)
+        .matching_edges(|_, _, weight| weight > 1000.0));
+    assert_eq!(even_steps.transactions.len(), 10);
+    assert!(even_steps.transactions.iter().all(|r| r.amount > 1000.0));
+}
+
+// verifies that label propagation separates two loosely connected groups and that the
+// community report ranks the ring with fraud labels and flagged accounts first.
+#[test]
+fn test_community_detection_report() {
+    let mut graph = TransactionGraph::new();
+    // a ring of four accounts passing fraud-labeled money around, with a mule in it
+    for (from, to) in [("R1", "R2"), ("R2", "R3"), ("R3", "R4"), ("R4", "R1"), ("R1", "R3")] {
+        graph.add_transaction(Transaction { is_fraud: 1, ..create_transaction(20000.0, from, to) });
+    }
+    graph.add_transaction(create_transaction(19000.0, "R3", "Mule"));
+    graph.add_transaction(create_transaction(18000.0, "Mule", "R4"));
+    // an ordinary group of customers paying each other, weakly tied to the ring
+    for (from, to) in [("C1", "C2"), ("C2", "C3"), ("C3", "C1"), ("C1", "C4"), ("C4", "C2")] {
+        graph.add_transaction(create_transaction(500.0, from, to));
+    }
+    graph.add_transaction(create_transaction(1.0, "C4", "R1"));
+
+    let communities = graph.graph.communities(20);
+    assert_eq!(communities.len(), 2);
+    let name = |idx| graph.account_name(graph.graph[idx]);
+    let mut ring: Vec<&str> = communities.iter()
+        .find(|c| c.iter().any(|idx| name(*idx) == "R1"))
+        .unwrap()
+        .iter()
+        .map(|idx| name(*idx))
+        .collect();
+    ring.sort();
+    assert_eq!(ring, ["Mule", "R1", "R2", "R3", "R4"]);
+
+    let analysis = FraudAnalysis::new(&graph);
+    for ranking in [CommunityRanking::FlaggedDensity, CommunityRanking::FraudRate] {
+        let report = analysis.community_report(&graph.to_csr(), &graph.transactions, 3, ranking);
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].members.len(), 5);
+        assert_eq!(report[0].transactions, 7);
+        assert_eq!(report[0].fraud_transactions, 5);
+        assert!(report[0].flagged >= 1);
+        assert_eq!(report[1].fraud_rate, 0.0);
+    }
+
+    // the iteration limit comes from the config: with no passes every account stays on its own
+    let config = AnalysisConfig { community_iterations: 0, ..AnalysisConfig::default() };
+    let report = FraudAnalysis::with_config(&graph, config).community_report(&graph.to_csr(), &graph.transactions, 3, CommunityRanking::FlaggedDensity);
+    assert!(report.is_empty());
+}
+
+// verifies the fewest-hop, cheapest and widest paths between two accounts,
+// and that time ordering rules out paths whose steps decrease.
+#[test]
+fn test_connection_paths() {
+    let mut graph = TransactionGraph::new();
+    // short route with a small transfer: A -> B -> D
+    graph.add_transaction(Transaction { step: 1, ..create_transaction(100.0, "A", "B") });
+    graph.add_transaction(Transaction { step: 2, ..create_transaction(5000.0, "B", "D") });
+    // long route with large transfers: A -> C -> E -> D, where the last hop happens too early
+    graph.add_transaction(Transaction { step: 3, ..create_transaction(9000.0, "A", "C") });
+    graph.add_transaction(Transaction { step: 4, ..create_transaction(8000.0, "C", "E") });
+    graph.add_transaction(Transaction { step: 1, ..create_transaction(7000.0, "E", "D") });
+    graph.add_transaction(create_transaction(1.0, "Lonely", "Other"));
+    let id = |name: &str| graph.account_id(name).unwrap();
+    let names = |accounts: &[AccountId]| accounts.iter().map(|a| graph.account_name(*a)).collect::<Vec<_>>();
+
+    let connection = graph.connection(id("A"), id("D")).unwrap();
+    assert_eq!(names(&connection.fewest_hops.accounts), ["A", "B", "D"]);
+    assert_eq!(connection.fewest_hops.value, 2.0);
+    assert_eq!(names(&connection.widest.accounts), ["A", "C", "E", "D"]);
+    assert_eq!(connection.widest.value, 7000.0);
+    assert_eq!(connection.widest.steps, [3, 4, 1]);
+    // 1/9000 + 1/8000 + 1/7000 is cheaper than 1/100 + 1/5000
+    assert_eq!(names(&connection.cheapest.accounts), ["A", "C", "E", "D"]);
+
+    let ordered = ConnectionOptions { time_ordered: true, ..ConnectionOptions::default() };
+    let connection = graph.connection_with(id("A"), id("D"), &ordered).unwrap();
+    assert_eq!(names(&connection.widest.accounts), ["A", "B", "D"]);
+    assert_eq!(connection.widest.value, 100.0);
+    assert_eq!(names(&connection.cheapest.accounts), ["A", "B", "D"]);
+
+    assert!(graph.connection(id("D"), id("A")).is_none());
+    assert!(graph.connection(id("A"), id("Lonely")).is_none());
+
+    // a repeated transfer does not hide an earlier one: A -> B at steps 1 and 100 and B -> C at
+    // step 50 is connected in time order through the transfer at step 1
+    let mut repeated = TransactionGraph::new();
+    repeated.add_transaction(Transaction { step: 1, ..create_transaction(100.0, "A", "B") });
+    repeated.add_transaction(Transaction { step: 100, ..create_transaction(200.0, "A", "B") });
+    repeated.add_transaction(Transaction { step: 50, ..create_transaction(150.0, "B", "C") });
+    let id = |name: &str| repeated.account_id(name).unwrap();
+    let connection = repeated.connection_with(id("A"), id("C"), &ordered).unwrap();
+    assert_eq!(connection.fewest_hops.steps, [1, 50]);
+    assert_eq!(connection.widest.steps, [1, 50]);
+    assert!(repeated.connection_with(id("B"), id("A"), &ordered).is_none());
+
+    // every hop reports the amount of the transfer it takes, not the last one on the edge:
+    // A -> B carries 100 at step 1, 2000 at step 10, 900 at step 2 and finally 50 at step 5
+    let mut amounts = TransactionGraph::new();
+    amounts.add_transaction(Transaction { step: 1, ..create_transaction(100.0, "A", "B") });
+    amounts.add_transaction(Transaction { step: 10, ..create_transaction(2000.0, "A", "B") });
+    amounts.add_transaction(Transaction { step: 2, ..create_transaction(900.0, "A", "B") });
+    amounts.add_transaction(Transaction { step: 5, ..create_transaction(50.0, "A", "B") });
+    amounts.add_transaction(Transaction { step: 3, ..create_transaction(500.0, "B", "C") });
+    let id = |name: &str| amounts.account_id(name).unwrap();
+    let connection = amounts.connection(id("A"), id("C")).unwrap();
+    assert_eq!(connection.widest.amounts, [2000.0, 500.0]);
+    assert_eq!(connection.widest.steps, [10, 3]);
+    assert_eq!(connection.widest.value, 500.0);
+    assert_eq!(connection.cheapest.amounts, [2000.0, 500.0]);
+    assert!((connection.cheapest.value - (1.0 / 2000.0 + 1.0 / 500.0)).abs() < 1e-12);
+
+    // in time order only the transfers at steps 1 and 2 can reach B before it pays C at step 3
+    let connection = amounts.connection_with(id("A"), id("C"), &ordered).unwrap();
+    assert_eq!(connection.widest.amounts, [900.0, 500.0]);
+    assert_eq!(connection.widest.steps, [2, 3]);
+    assert_eq!(connection.widest.value, 500.0);
+    assert_eq!(connection.cheapest.amounts, [900.0, 500.0]);
+    assert_eq!(connection.fewest_hops.amounts.len(), 2);
+}
+
+// verifies the max flow value between groups of accounts and that the minimum cut
+// names the mule every route has to pass through.
+#[test]
+fn test_max_flow_min_cut() {
+    let mut graph = TransactionGraph::new();
+    // two sources feed a single mule, which splits the money over two collectors
+    graph.add_transaction(create_transaction(5000.0, "Src1", "Mule"));
+    graph.add_transaction(create_transaction(7000.0, "Src2", "Mule"));
+    graph.add_transaction(create_transaction(3000.0, "Mule", "Hop"));
+    graph.add_transaction(create_transaction(4000.0, "Hop", "Col1"));
+    graph.add_transaction(create_transaction(2000.0, "Mule", "Col2"));
+    // a direct transfer that bypasses the mule
+    graph.add_transaction(create_transaction(500.0, "Src1", "Col2"));
+    let ids = |names: &[&str]| names.iter().map(|n| graph.account_id(n).unwrap()).collect::<Vec<_>>();
+
+    let flow = graph.max_flow(&ids(&["Src1", "Src2"]), &ids(&["Col1", "Col2"]));
+    assert_eq!(flow.value, 5500.0);
+    let cut: Vec<(&str, &str)> = flow.cut_edges.iter()
+        .map(|(s, t, _)| (graph.account_name(*s), graph.account_name(*t)))
+        .collect();
+    assert_eq!(cut.len(), 3);
+    assert!(cut.contains(&("Mule", "Hop")) && cut.contains(&("Mule", "Col2")) && cut.contains(&("Src1", "Col2")));
+    assert_eq!(flow.cut_edges.iter().map(|(_, _, c)| c).sum::<f64>(), flow.value);
+    // Mule and Hop sit on cut transfers; the sources and sinks are never chokepoints
+    assert_eq!(flow.chokepoints, ids(&["Mule", "Hop"]));
+
+    // no route back from the collectors
+    assert_eq!(graph.max_flow(&ids(&["Col1"]), &ids(&["Src1"])).value, 0.0);
+
+    // repeated transfers on the bottleneck add up: Mule -> Hop carries 3000 then 1000 more
+    let (sources, sinks) = (ids(&["Src1", "Src2"]), ids(&["Col1"]));
+    graph.add_transaction(create_transaction(1000.0, "Mule", "Hop"));
+    let flow = graph.max_flow(&sources, &sinks);
+    assert_eq!(flow.value, 4000.0);
+    assert_eq!(flow.cut_edges.len(), 1);
+    assert_eq!(flow.cut_edges[0].2, 4000.0);
+}
+
+// verifies that removing accounts and transfers keeps the adjacency, node map, stored
+// transactions and metrics consistent, and that node indices are never reused.
+#[test]
+fn test_remove_account_and_transfer() {
+    let mut graph = TransactionGraph::new();
+    for i in 1..=6 {
+        graph.add_transaction(create_transaction(1000.0, &format!("User{}", i), "Hub"));
+    }
+    graph.add_transaction(create_transaction(20000.0, "Source", "Mule"));
+    graph.add_transaction(create_transaction(19000.0, "Mule", "Dest"));
+    graph.add_transaction(create_transaction(500.0, "Mule", "Hub"));
+    let mut analysis = FraudAnalysis::new(&graph);
+    let id = |graph: &TransactionGraph, name: &str| graph.account_id(name).unwrap();
+    let hub = id(&graph, "Hub");
+    let hub_idx = graph.node_map[&hub];
+    let mule = id(&graph, "Mule");
+    assert!(analysis.identify_collector_accounts().iter().any(|(a, _)| *a == hub));
+
+    // pruning the hub removes its node, its 7 incoming edges and its transactions
+    let removed = graph.remove_account(hub);
+    assert_eq!(removed.len(), 7);
+    assert!(!graph.node_map.contains_key(&hub));
+    assert!(!graph.graph.contains_node(hub_idx));
+    assert_eq!(graph.graph.edge_count(), 2);
+    assert_eq!(graph.transactions.len(), 2);
+    assert_eq!(graph.graph.neighbors(graph.node_map[&mule]).count(), 1);
+
+    let mut touched: Vec<AccountId> = removed.iter().flat_map(|r| [r.name_orig, r.name_dest]).collect();
+    touched.sort_unstable();
+    touched.dedup();
+    let changes = analysis.apply_batch(&graph, &touched);
+    assert!(changes.iter().any(|c| c.account == hub && c.detector == Detector::Collector && !c.flagged));
+    assert!(analysis.metrics(hub).is_none());
+    assert_eq!(analysis.metrics(mule), FraudAnalysis::new(&graph).metrics(mule));
+
+    // the hub comes back with a fresh index
+    graph.add_transaction(create_transaction(10.0, "User1", "Hub"));
+    assert!(graph.node_map[&hub].0 > hub_idx.0);
+
+    // undoing a single transfer keeps both accounts
+    let removed = graph.remove_transfer(mule, id(&graph, "Dest"));
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].amount, 19000.0);
+    assert!(graph.node_map.contains_key(&id(&graph, "Dest")));
+    assert!(graph.remove_transfer(mule, id(&graph, "Dest")).is_empty());
+    assert_eq!(graph.account_metrics(mule).unwrap().outgoing_count, 0);
+}
+
+// verifies that the checked graph operations report missing nodes and edges instead of
+// panicking or half-inserting, and that validate catches an inconsistent graph.
+#[test]
+fn test_checked_graph_operations() {
+    let mut graph: DiGraph<&str, f64> = DiGraph::new();
+    let a = graph.add_node("A");
+    let b = graph.add_node("B");
+    let missing = NodeIndex(99);
+
+    assert_eq!(graph.try_add_edge(a, b, 10.0), Ok(None));
+    assert_eq!(graph.try_add_edge(a, b, 20.0), Ok(Some(10.0)));
+    assert_eq!(graph.try_add_edge(a, missing, 1.0), Err(GraphError::MissingNode(missing)));
+    assert_eq!(graph.edge_count(), 1);
+    assert_eq!(graph.try_node(b), Ok(&"B"));
+    assert_eq!(graph.try_node(missing), Err(GraphError::MissingNode(missing)));
+    *graph.try_node_mut(b).unwrap() = "B2";
+    assert_eq!(graph[b], "B2");
+    assert_eq!(graph.try_edge_weight(b, a), Err(GraphError::MissingEdge(b, a)));
+    assert_eq!(graph.validate(), Ok(()));
+
+    // the unchecked add_edge leaves a half-inserted edge that validate reports
+    graph.add_edge(missing, a, 1.0);
+    assert!(matches!(graph.validate(), Err(GraphError::Inconsistent(_))));
+    let err: FraudError = graph.validate().unwrap_err().into();
+    assert!(matches!(err, FraudError::Graph(GraphError::Inconsistent(_))));
+
+    let mut transactions = TransactionGraph::new();
+    transactions.add_transaction(create_transaction(100.0, "A", "B"));
+    transactions.add_transaction(create_transaction(50.0, "B", "C"));
+    assert_eq!(transactions.validate(), Ok(()));
+    // an edge removed behind the transactions' back
+    let (b, c) = (transactions.node_map[&transactions.account_id("B").unwrap()], transactions.node_map[&transactions.account_id("C").unwrap()]);
+    transactions.graph.remove_edge(b, c);
+    assert_eq!(transactions.validate(), Err(GraphError::MissingEdge(b, c)));
+}
+
+// verifies that the lazy edge iterators yield every edge with its weight,
+// and that a node's out- and in-edges match its neighbors.
+#[test]
+fn test_lazy_edge_iterators() {
+    let mut graph = TransactionGraph::new();
+    graph.add_transaction(create_transaction(100.0, "A", "B"));
+    graph.add_transaction(create_transaction(200.0, "A", "C"));
+    graph.add_transaction(create_transaction(300.0, "C", "A"));
+    let node = |name: &str| graph.node_map[&graph.account_id(name).unwrap()];
+    let (a, b, c) = (node("A"), node("B"), node("C"));
+
+    let mut all: Vec<(usize, usize, f64)> = graph.graph.edge_references()
+        .map(|e| (e.source().0, e.target().0, *e.weight()))
+        .collect();
+    all.sort_by_key(|(s, t, _)| (*s, *t));
+    assert_eq!(all, [(a.0, b.0, 100.0), (a.0, c.0, 200.0), (c.0, a.0, 300.0)]);
+    assert_eq!(graph.graph.edge_references().map(|e| *e.weight()).sum::<f64>(), 600.0);
+
+    let mut out: Vec<(usize, f64)> = graph.graph.out_edges(a).map(|e| (e.target().0, *e.weight())).collect();
+    out.sort_by_key(|(t, _)| *t);
+    assert_eq!(out, [(b.0, 100.0), (c.0, 200.0)]);
+    assert!(graph.graph.out_edges(a).all(|e| e.source() == a));
+    let incoming: Vec<(NodeIndex, f64)> = graph.graph.in_edges(a).map(|e| (e.source(), *e.weight())).collect();
+    assert_eq!(incoming, [(c, 300.0)]);
+    assert_eq!(graph.graph.in_edges(b).count(), 1);
+    assert_eq!(graph.graph.out_edges(NodeIndex(99)).count(), 0);
+}
+
+// verifies the network statistics: counts, reciprocity, self-loops, components,
+// fraud rates per type, amount quantiles, a power-law fit on a heavy-tailed graph, and the json.
+#[test]
+fn test_network_stats_report() {
+    let mut graph = TransactionGraph::new();
+    graph.add_transaction(create_transaction(100.0, "A", "B"));
+    graph.add_transaction(create_transaction(200.0, "B", "A"));
+    graph.add_transaction(create_transaction(300.0, "A", "A"));
+    graph.add_transaction(Transaction { r#type: "CASH_OUT".to_string(), is_fraud: 1, ..create_transaction(400.0, "C", "D") });
+    graph.add_transaction(Transaction { r#type: "CASH_OUT".to_string(), ..create_transaction(500.0, "D", "E") });
+
+    let stats = graph.stats();
+    assert_eq!((stats.accounts, stats.edges, stats.transactions), (5, 5, 5));
+    assert_eq!(stats.self_loops, 1);
+    // A -> B and B -> A are reciprocated; C -> D and D -> E are not
+    assert_eq!(stats.reciprocity, 0.5);
+    assert_eq!(stats.density, 5.0 / 20.0);
+    assert_eq!((stats.components, stats.largest_component), (2, 3));
+    assert_eq!(stats.in_degree.max, 2);
+    assert_eq!(stats.amount_quantiles.first(), Some(&(0.0, 100.0)));
+    assert_eq!(stats.amount_quantiles.last(), Some(&(1.0, 500.0)));
+    assert_eq!(stats.fraud_rate_by_type.len(), 2);
+    assert_eq!(stats.fraud_rate_by_type[0].r#type, "CASH_OUT");
+    assert_eq!(stats.fraud_rate_by_type[0].rate, 0.5);
+    assert_eq!(stats.fraud_rate_by_type[1].rate, 0.0);
+    assert!(stats.in_degree.power_law.is_none());
+
+    let json = stats.to_json();
+    assert!(json.starts_with('{') && json.trim_end().ends_with('}'));
+    assert!(json.contains("\"self_loops\": 1,"));
+    assert!(json.contains("{\"type\": \"CASH_OUT\", \"transactions\": 2, \"fraud\": 1, \"rate\": 0.5}"));
+
+    // hub i receives from 400 / i^2 payers, a heavy-tailed in-degree distribution
+    let mut heavy = TransactionGraph::new();
+    for hub in 1..=20 {
+        for payer in 0..(400 / (hub * hub)).max(1) {
+            heavy.add_transaction(create_transaction(1.0, &format!("P{}-{}", hub, payer), &format!("H{}", hub)));
+        }
+    }
+    let fit = heavy.stats().in_degree.power_law.unwrap();
+    assert!(fit.tail_size >= 10);
+    assert!(fit.alpha > 1.0 && fit.alpha.is_finite());
+}
+
+// verifies reciprocal pair detection: reversals, timing gaps and amount symmetry of each pair,
+// and that the detector applies its symmetry, count and timing thresholds.
+#[test]
+fn test_reciprocal_pairs() {
+    let mut graph = TransactionGraph::new();
+    // A and B ping-pong similar amounts every couple of steps
+    graph.add_transaction(Transaction { step: 1, ..create_transaction(1000.0, "A", "B") });
+    graph.add_transaction(Transaction { step: 3, ..create_transaction(950.0, "B", "A") });
+    graph.add_transaction(Transaction { step: 4, ..create_transaction(1000.0, "A", "B") });
+    graph.add_transaction(Transaction { step: 8, ..create_transaction(1000.0, "B", "A") });
+    // C pays D back once, a tenth of the amount
+    graph.add_transaction(Transaction { step: 1, ..create_transaction(500.0, "C", "D") });
+    graph.add_transaction(Transaction { step: 2, ..create_transaction(50.0, "D", "C") });
+    // one-way transfers and self-transfers are not pairs
+    graph.add_transaction(create_transaction(10.0, "A", "E"));
+    graph.add_transaction(create_transaction(10.0, "E", "E"));
+
+    let id = |name: &str| graph.accounts.get(name).unwrap();
+    let pairs = graph.reciprocal_pairs();
+    assert_eq!(pairs.len(), 2);
+    let ab = &pairs[0];
+    assert_eq!((ab.a, ab.b), (id("A"), id("B")));
+    assert_eq!((ab.a_to_b, ab.b_to_a), (2, 2));
+    assert_eq!(ab.reversals, 3);
+    assert_eq!(ab.min_gap, 1);
+    assert_eq!(ab.mean_gap, 7.0 / 3.0);
+    assert_eq!(ab.amount_symmetry, 1950.0 / 2000.0);
+    assert_eq!(ab.volume(), 3950.0);
+    assert_eq!(pairs[1].reversals, 1);
+    assert_eq!(pairs[1].amount_symmetry, 0.1);
+
+    let analysis = FraudAnalysis::new(&graph);
+    let flagged = analysis.identify_reciprocal_pairs(&graph.transactions);
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].a, id("A"));
+
+    // relaxing the thresholds lets C and D through; a tight timing limit rejects A and B
+    let config = AnalysisConfig {
+        reciprocity: ReciprocityThresholds { min_reversals: 1, min_symmetry: 0.05, max_mean_gap: Some(2.0) },
+        ..AnalysisConfig::default()
+    };
+    let flagged = FraudAnalysis::with_config(&graph, config).identify_reciprocal_pairs(&graph.transactions);
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].a, id("C"));
+}
+
+// verifies that the loader reads the optional balance columns, that snapshots keep them, and that
+// mismatched transactions and broken balance paths are flagged.
+#[test]
+fn test_balance_consistency() {
+    let source = write_temp_csv("balances", "\
+step,type,amount,nameOrig,nameDest,isFraud,oldbalanceOrg,newbalanceOrig,oldbalanceDest,newbalanceDest
+1,TRANSFER,100.0,A,B,0,500.0,400.0,0.0,100.0
+2,TRANSFER,50.0,B,C,0,100.0,50.0,10.0,60.0
+3,CASH_OUT,300.0,A,C,1,900.0,0.0,70.0,370.0
+4,TRANSFER,5.0,C,D,0,,,,
+5,TRANSFER,5.0,C,D,0,abc,1.0,1.0,1.0
+");
+    let graph = read_transaction_dataset(&source).unwrap();
+    // the malformed row is skipped and the empty one has no balances
+    assert_eq!(graph.transactions.len(), 4);
+    assert_eq!(graph.transactions[0].balances, Some(Balances { old_orig: 500.0, new_orig: 400.0, old_dest: 0.0, new_dest: 100.0 }));
+    assert_eq!(graph.transactions[3].balances, None);
+
+    // snapshots keep the balances
+    let snapshot = std::env::temp_dir().join(format!("fraud_test_{}_balances.snap", std::process::id()));
+    save_snapshot(&graph, &snapshot, None).unwrap();
+    let restored = load_snapshot(&snapshot, None).unwrap();
+    assert_eq!(restored.transactions, graph.transactions);
+    std::fs::remove_file(&snapshot).unwrap();
+    std::fs::remove_file(&source).unwrap();
+
+    // A -> C takes 900 from A but sends only 300
+    let analysis = FraudAnalysis::new(&graph);
+    let mismatches = analysis.identify_balance_mismatches(&graph.transactions);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].transaction.step, 3);
+    assert_eq!(mismatches[0].origin_error, 600.0);
+    assert_eq!(mismatches[0].destination_error, 0.0);
+
+    let id = |name: &str| graph.accounts.get(name).unwrap();
+    let paths = analysis.identify_inconsistent_balance_paths(&graph.transactions);
+    let flagged: Vec<AccountId> = paths.iter().map(|path| path.account).collect();
+    // A's balance jumps from 400 to 900 before its mismatched transfer; C's from 60 to 70
+    assert_eq!(flagged, [id("A"), id("C")]);
+    assert_eq!((paths[0].breaks, paths[0].mismatches, paths[0].largest_break), (1, 1, 500.0));
+    assert_eq!(paths[0].balances, [(1, 500.0, 400.0), (3, 900.0, 0.0)]);
+    assert_eq!((paths[1].breaks, paths[1].largest_break), (1, 10.0));
+    assert!(graph.balance_paths(0.01, true).iter().any(|path| path.account == id("B") && !path.is_inconsistent()));
+
+    // without the destination side only sent transactions are replayed: C never sent with
+    // balances, so its gap between two received transfers is not a break
+    let paths = graph.balance_paths(0.01, false);
+    let accounts: Vec<AccountId> = paths.iter().map(|path| path.account).collect();
+    assert_eq!(accounts, [id("A"), id("B")]);
+    assert_eq!((paths[0].breaks, paths[0].mismatches), (1, 1));
+    assert_eq!(paths[1].balances, [(2, 100.0, 50.0)]);
+    assert!(!paths[1].is_inconsistent());
+}
+
+// verifies account classification by id prefix and that each detector uses the thresholds
+// of the account's class: a merchant receiving many payments is not a collector by default.
+#[test]
+fn test_account_class_thresholds() {
+    let mut graph = TransactionGraph::new();
+    for i in 0..8 {
+        graph.add_transaction(create_transaction(1000.0, &format!("C{}", i), "M100"));
+        graph.add_transaction(create_transaction(1000.0, &format!("C{}", i), "C200"));
+    }
+    let id = |graph: &TransactionGraph, name: &str| graph.accounts.get(name).unwrap();
+    assert_eq!(graph.account_class(id(&graph, "M100")), AccountClass::Merchant);
+    assert_eq!(graph.account_class(id(&graph, "C200")), AccountClass::Customer);
+    let classifier = AccountClassifier::default();
+    assert_eq!(classifier.classify("Collector"), AccountClass::Other);
+    assert_eq!(classifier.classify("M"), AccountClass::Other);
+
+    // patterns match the whole id, with `*` for any run, `?` for one character and `#` for one digit
+    let patterns = AccountClassifier {
+        customer_patterns: vec!["*@bank".to_string()],
+        merchant_patterns: vec!["SHOP-####".to_string(), "m?rchant*x".to_string()],
+        ..AccountClassifier::default()
+    };
+    assert_eq!(patterns.classify("alice@bank"), AccountClass::Customer);
+    assert_eq!(patterns.classify("alice@bank.com"), AccountClass::Other);
+    assert_eq!(patterns.classify("SHOP-0042"), AccountClass::Merchant);
+    assert_eq!(patterns.classify("SHOP-42"), AccountClass::Other);
+    assert_eq!(patterns.classify("SHOP-004a"), AccountClass::Other);
+    assert_eq!(patterns.classify("merchant-x-x"), AccountClass::Merchant);
+    assert_eq!(patterns.classify("merchant-xy"), AccountClass::Other);
+    // the prefixes still apply alongside the patterns
+    assert_eq!(patterns.classify("C123"), AccountClass::Customer);
+
+    let analysis = FraudAnalysis::new(&graph);
+    let collectors: Vec<AccountId> = analysis.identify_collector_accounts().into_iter().map(|(a, _)| a).collect();
+    assert_eq!(collectors, [id(&graph, "C200")]);
+    // the metrics' own checks take the class's thresholds and agree with the analysis
+    let defaults = ClassThresholds::default();
+    let merchant = analysis.metrics(id(&graph, "M100")).unwrap();
+    assert!(!merchant.is_collector(defaults.for_class(AccountClass::Merchant)));
+    assert!(merchant.is_collector(defaults.for_class(AccountClass::Customer)));
+    assert!(analysis.metrics(id(&graph, "C200")).unwrap().is_collector(defaults.for_class(AccountClass::Customer)));
+
+    // merchants can be given collector thresholds of their own, and customers stricter ones
+    let mut thresholds = ClassThresholds::default();
+    thresholds.merchant.collector = Some(CollectorThresholds::default());
+    thresholds.customer.collector = Some(CollectorThresholds { min_incoming: 10, ..CollectorThresholds::default() });
+    let config = AnalysisConfig { thresholds, ..AnalysisConfig::default() };
+    let analysis = FraudAnalysis::with_config(&graph, config);
+    let collectors: Vec<AccountId> = analysis.identify_collector_accounts().into_iter().map(|(a, _)| a).collect();
+    assert_eq!(collectors, [id(&graph, "M100")]);
+
+    // custom prefixes reclassify the graph, and views keep the classifier
+    graph.set_classifier(AccountClassifier { merchant_prefixes: vec!["C2".to_string()], customer_prefixes: Vec::new(), digits_only: false, ..AccountClassifier::default() });
+    assert_eq!(graph.account_class(id(&graph, "C200")), AccountClass::Merchant);
+    assert_eq!(graph.account_class(id(&graph, "M100")), AccountClass::Other);
+    let view = graph.filter(&TransactionFilter::new());
+    assert_eq!(view.account_class(id(&view, "C200")), AccountClass::Merchant);
+    assert!(view.validate().is_ok());
+    let collectors: Vec<AccountId> = FraudAnalysis::new(&graph).identify_collector_accounts().into_iter().map(|(a, _)| a).collect();
+    assert_eq!(collectors, [id(&graph, "M100")]);
+}
+
+// verifies the step split, model training on graph features, evaluation on later steps,
+// saving and loading a model, and scoring a graph through the analysis.
+#[test]
+fn test_model_training_and_scoring() {
+    let mut graph = TransactionGraph::new();
+    // in every period, fraud victims send everything to a mule that cashes it out at once,
+    // while ordinary accounts make small payments to one shared shop
+    for period in 0..4u32 {
+        let step = period * 10 + 1;
+        for i in 0..6 {
+            let victim = format!("V{}-{}", period, i);
+            let mule = format!("X{}-{}", period, i);
+            graph.add_transaction(Transaction { step, is_fraud: 1, ..create_transaction(50000.0, &victim, &mule) });
+            graph.add_transaction(Transaction { step, is_fraud: 1, ..create_transaction(50000.0, &mule, &format!("Out{}-{}", period, i)) });
+        }
+        for i in 0..12 {
+            graph.add_transaction(Transaction { step, ..create_transaction(20.0 + i as f64, &format!("P{}-{}", period, i), "Shop") });
+        }
+    }
+
+    let (train_graph, test_graph) = graph.split_at_step(20);
+    assert!(train_graph.transactions.iter().all(|r| r.step <= 20));
+    assert!(test_graph.transactions.iter().all(|r| r.step > 20));
+    assert_eq!(train_graph.transactions.len() + test_graph.transactions.len(), graph.transactions.len());
+
+    let train = FeatureMatrix::from_graph(&train_graph);
+    let test = FeatureMatrix::from_graph(&test_graph);
+    assert_eq!(train.labels.iter().filter(|label| **label).count(), 36);
+    let model = LogisticModel::train(&train, &TrainingOptions::default());
+    let evaluation = model.evaluate(&test, 0.5);
+    assert_eq!(evaluation.positives, 36);
+    assert!(evaluation.auc > 0.9, "auc {}", evaluation.auc);
+    assert!(evaluation.recall > 0.9);
+
+    // a saved model loads back identical, and files that are not models are rejected
+    let path = std::env::temp_dir().join(format!("fraud_test_{}_model.txt", std::process::id()));
+    model.save(&path).unwrap();
+    assert_eq!(LogisticModel::load(&path).unwrap(), model);
+    std::fs::write(&path, "fraud-model 1\nfeatures amount\n").unwrap();
+    assert!(matches!(LogisticModel::load(&path), Err(FraudError::Model(_))));
+    std::fs::remove_file(&path).unwrap();
+
+    // the shop, the accounts paying it and the mules are scored through the analysis
+    let analysis = FraudAnalysis::new(&test_graph);
+    let scores = analysis.score_accounts(&test_graph.to_csr(), &test_graph.transactions, &model);
+    assert_eq!(scores.len(), test.len());
+    assert!(scores.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    let shop = test_graph.accounts.get("Shop").unwrap();
+    let mule = test_graph.accounts.get("X3-0").unwrap();
+    let score_of = |account: AccountId| scores.iter().find(|(a, _)| *a == account).unwrap().1;
+    assert!(score_of(mule) > score_of(shop));
+
+    // scoring uses the classes of the analysis, so an analysis that took the graph's classes
+    // scores merchants with the same features the model was trained on
+    let mut shops = TransactionGraph::new();
+    for i in 0..5 {
+        shops.add_transaction(create_transaction(30.0 + i as f64, &format!("C{}", i), "M1"));
+    }
+    let expected = model.score(&FeatureMatrix::from_graph(&shops));
+    let mut analysis = FraudAnalysis::from_csr(&shops.to_csr(), AnalysisConfig::default());
+    analysis.set_classes(std::mem::take(&mut shops.classes));
+    assert_eq!(analysis.score_accounts(&shops.to_csr(), &shops.transactions, &model), expected);
+}
+
+// verifies that a threshold sweep counts flags against the fraud labels at every value,
+// builds the roc and pr areas from the points, and suggests the right thresholds
+#[test]
+fn test_threshold_sweep() {
+    let mut graph = TransactionGraph::new();
+    // fraud mules forward large amounts, ordinary forwarders the same pattern at a tenth of the size
+    for i in 0..4 {
+        graph.add_transaction(Transaction { is_fraud: 1, ..create_transaction(50000.0, &format!("Victim{}", i), &format!("Mule{}", i)) });
+        graph.add_transaction(Transaction { is_fraud: 1, ..create_transaction(50000.0, &format!("Mule{}", i), &format!("Out{}", i)) });
+        graph.add_transaction(create_transaction(5000.0, &format!("Payer{}", i), &format!("Relay{}", i)));
+        graph.add_transaction(create_transaction(5000.0, &format!("Relay{}", i), &format!("Payee{}", i)));
+    }
+
+    let analysis = FraudAnalysis::new(&graph);
+    let curve = analysis.threshold_sweep(&graph, ThresholdParameter::MuleMinIncomingVolume, &[0.0, 10000.0, 100000.0]);
+    let counts: Vec<(usize, usize, usize, usize)> = curve.points.iter()
+        .map(|p| (p.true_positives, p.false_positives, p.false_negatives, p.true_negatives))
+        .collect();
+    assert_eq!(counts, vec![(4, 4, 8, 8), (4, 0, 8, 12), (0, 0, 12, 12)]);
+    assert!((curve.points[0].precision - 0.5).abs() < 1e-9);
+    assert!((curve.points[1].f1 - 0.5).abs() < 1e-9);
+    assert!((curve.roc_auc - 5.0 / 9.0).abs() < 1e-9, "roc auc {}", curve.roc_auc);
+    assert!((curve.pr_auc - 1.0 / 3.0).abs() < 1e-9, "pr auc {}", curve.pr_auc);
+    assert_eq!(curve.best_f1().unwrap().value, 10000.0);
+    assert_eq!(curve.for_precision(0.9).unwrap().value, 10000.0);
+    assert!(curve.for_precision(1.1).is_none());
+
+    // a detector the class thresholds disable stays disabled when its thresholds are swept
+    let mut merchant = ClassThresholds::default().merchant;
+    ThresholdParameter::CollectorMinRetention.apply(&mut merchant, 0.1);
+    assert!(merchant.collector.is_none());
+
+    // the csv output has one row per point and one summary row per curve
+    let mut points = Vec::new();
+    let mut summary = Vec::new();
+    write_sweep_points(std::slice::from_ref(&curve), &mut points).unwrap();
+    write_sweep_summary(std::slice::from_ref(&curve), 0.9, &mut summary).unwrap();
+    assert_eq!(String::from_utf8(points).unwrap().lines().count(), 4);
+    let summary = String::from_utf8(summary).unwrap();
+    assert_eq!(summary.lines().count(), 2);
+    assert!(summary.lines().nth(1).unwrap().starts_with("money mule,min_incoming_volume,"));
+}
+
+// verifies that the synthetic generator is deterministic, labels every injected transaction,
+// keeps balances consistent, and that the detectors find the injected mules and collectors
+#[test]
+fn test_synthetic_generator() {
+    let config = GeneratorConfig { customers: 300, merchants: 30, steps: 50, transactions: 2000, ..GeneratorConfig::default() };
+    let dataset = SyntheticDataset::generate(&config);
+    let again = SyntheticDataset::generate(&config);
+    assert_eq!(dataset.transactions, again.transactions);
+    assert_eq!(dataset.patterns, again.patterns);
+    let other = SyntheticDataset::generate(&GeneratorConfig { seed: 7, ..config.clone() });
+    assert_ne!(dataset.transactions, other.transactions);
+
+    // every injected transaction is labeled and nothing else is
+    let injected: usize = dataset.patterns.iter().map(|pattern| pattern.transactions).sum();
+    assert_eq!(dataset.transactions.iter().filter(|t| t.is_fraud == 1).count(), injected);
+    assert_eq!(dataset.transactions.len(), config.transactions + injected);
+    assert!(dataset.transactions.windows(2).all(|pair| pair[0].step <= pair[1].step));
+    assert!(dataset.transactions.iter().all(|t| (1..=config.steps).contains(&t.step)));
+    for typology in Typology::ALL {
+        let expected = match typology {
+            Typology::MoneyMule => config.mules,
+            Typology::Collector => config.collectors,
+            Typology::Cycle => config.cycles,
+            Typology::Structuring => config.structuring,
+            Typology::Layering => config.layering,
+        };
+        assert_eq!(dataset.central_accounts(typology).len(), expected);
+    }
+    for pattern in &dataset.patterns {
+        match pattern.typology {
+            Typology::Layering => assert_eq!(pattern.accounts.len(), config.chain_length + 2),
+            Typology::Cycle => assert_eq!(pattern.transactions, pattern.accounts.len()),
+            _ => {}
+        }
+    }
+    assert!(dataset.transactions.iter()
+        .filter(|t| t.is_fraud == 1 && dataset.central_accounts(Typology::Structuring).contains(&t.name_orig.as_str()))
+        .all(|t| t.amount < config.structuring_limit));
+
+    // the balances add up, and every account is a customer or a merchant
+    let graph = dataset.to_graph();
+    assert!(graph.balance_mismatches(0.01, true).is_empty());
+    assert!(graph.balance_paths(0.01, true).iter().all(|path| !path.is_inconsistent()));
+    assert!(graph.node_map.keys().all(|account| graph.account_class(*account) != AccountClass::Other));
+
+    // the injected mules and collectors are all flagged
+    let analysis = FraudAnalysis::new(&graph);
+    let flagged = |found: Vec<(AccountId, fraud_detection::AccountMetrics)>| -> Vec<String> {
+        found.iter().map(|(account, _)| graph.accounts.name(*account).to_string()).collect()
+    };
+    let mules = flagged(analysis.identify_money_mule_accounts());
+    let collectors = flagged(analysis.identify_collector_accounts());
+    assert!(dataset.central_accounts(Typology::MoneyMule).iter().all(|mule| mules.iter().any(|m| m == mule)));
+    assert!(dataset.central_accounts(Typology::Collector).iter().all(|collector| collectors.iter().any(|c| c == collector)));
+
+    // the csv output reads back as the same transactions
+    let path = std::env::temp_dir().join(format!("fraud_test_{}_synthetic.csv", std::process::id()));
+    dataset.write_csv(std::fs::File::create(&path).unwrap()).unwrap();
+    let loaded = read_transaction_dataset(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.transactions.len(), dataset.transactions.len());
+    assert_eq!(loaded.transactions, graph.transactions);
+}